@@ -0,0 +1,295 @@
+// ============================================
+// CONSTANT-PRODUCT AMM
+// ============================================
+// A two-token pool priced by the x * y = k invariant. Liquidity providers
+// receive LP tokens; swaps pay a fee in basis points that stays in the pool.
+
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::pubkey::Pubkey;
+use crate::token::Mint;
+
+/// Program that owns every pool vault
+pub const AMM_PROGRAM_ID: Pubkey = Pubkey::new(*b"AmmProgram1111111111111111111111");
+/// Prefix of every LP mint symbol; only pools create mints with it
+pub const LP_MINT_PREFIX: &str = "LP-";
+/// Basis points in 100%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+/// Highest fee a pool may charge (10%)
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Struct representing a liquidity pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pool {
+    pub name: String,
+    pub mint_a: String,
+    pub mint_b: String,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    pub fee_bps: u16,
+    pub lp_mint: String, // Mint of the LP shares
+    pub lp_supply: u64,
+    pub vault: Pubkey, // PDA of AMM_PROGRAM_ID holding the reserves
+}
+
+/// Tokens moved by a deposit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deposit {
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub shares: u64,
+}
+
+impl Pool {
+    /// Create an empty pool for two different mints. The name must also make
+    /// a valid LP symbol, so at most 13 letters, digits or '-'.
+    pub fn new(name: String, mint_a: String, mint_b: String, fee_bps: u16) -> Result<Self> {
+        AppError::validate_account_name(&name)?;
+        let lp_mint = format!("{}{}", LP_MINT_PREFIX, name);
+        if Mint::validate_symbol(&lp_mint).is_err() {
+            return Err(AppError::InvalidAccountName(name));
+        }
+        if mint_a == mint_b {
+            return Err(AppError::InvalidPool(format!(
+                "{} cannot be paired with itself",
+                mint_a
+            )));
+        }
+        if fee_bps > MAX_FEE_BPS {
+            return Err(AppError::InvalidPool(format!(
+                "fee of {} bps is above the {} bps limit",
+                fee_bps, MAX_FEE_BPS
+            )));
+        }
+
+        let (vault, _) = Pubkey::find_program_address(&Pool::vault_seeds(&hash::sha256(name.as_bytes())), &AMM_PROGRAM_ID)?;
+        Ok(Self {
+            name,
+            mint_a,
+            mint_b,
+            reserve_a: 0,
+            reserve_b: 0,
+            fee_bps,
+            lp_mint,
+            lp_supply: 0,
+            vault,
+        })
+    }
+
+    /// Seeds of the vault PDA, from the hash of the pool name
+    pub fn vault_seeds(name_hash: &Hash) -> [&[u8]; 2] {
+        [b"pool", name_hash]
+    }
+
+    /// Check whether `mint` is one side of the pool; returns true for side A
+    pub fn side_of(&self, mint: &str) -> Result<bool> {
+        if mint == self.mint_a {
+            Ok(true)
+        } else if mint == self.mint_b {
+            Ok(false)
+        } else {
+            Err(AppError::InvalidPool(format!(
+                "{} is not traded in pool {}",
+                mint, self.name
+            )))
+        }
+    }
+
+    /// The invariant k = reserve_a * reserve_b
+    pub fn invariant(&self) -> u128 {
+        self.reserve_a as u128 * self.reserve_b as u128
+    }
+
+    /// Work out a deposit of at most `max_a` / `max_b`. The first deposit sets
+    /// the price and mints sqrt(a * b) shares; later deposits take the two
+    /// tokens in the current ratio, rounding in the pool's favour.
+    pub fn quote_deposit(&self, max_a: u64, max_b: u64) -> Result<Deposit> {
+        AppError::validate_amount(max_a)?;
+        AppError::validate_amount(max_b)?;
+
+        if self.lp_supply == 0 {
+            let shares = isqrt(max_a as u128 * max_b as u128);
+            return Ok(Deposit {
+                amount_a: max_a,
+                amount_b: max_b,
+                shares: to_u64(shares)?,
+            });
+        }
+
+        let supply = self.lp_supply as u128;
+        let shares_a = max_a as u128 * supply / self.reserve_a as u128;
+        let shares_b = max_b as u128 * supply / self.reserve_b as u128;
+        let shares = shares_a.min(shares_b);
+        if shares == 0 {
+            return Err(AppError::InvalidAmount(0));
+        }
+
+        Ok(Deposit {
+            amount_a: to_u64(div_ceil(shares * self.reserve_a as u128, supply))?,
+            amount_b: to_u64(div_ceil(shares * self.reserve_b as u128, supply))?,
+            shares: to_u64(shares)?,
+        })
+    }
+
+    /// Tokens paid out for burning `shares`, rounding down
+    pub fn quote_withdraw(&self, shares: u64) -> Result<(u64, u64)> {
+        AppError::validate_amount(shares)?;
+        if shares > self.lp_supply {
+            return Err(AppError::insufficient_balance(self.lp_supply, shares));
+        }
+
+        let supply = self.lp_supply as u128;
+        let amount_a = shares as u128 * self.reserve_a as u128 / supply;
+        let amount_b = shares as u128 * self.reserve_b as u128 / supply;
+        Ok((to_u64(amount_a)?, to_u64(amount_b)?))
+    }
+
+    /// Output for selling `amount_in` of `mint_in`, after the fee
+    pub fn quote_swap(&self, mint_in: &str, amount_in: u64) -> Result<u64> {
+        AppError::validate_amount(amount_in)?;
+        let (reserve_in, reserve_out) = if self.side_of(mint_in)? {
+            (self.reserve_a, self.reserve_b)
+        } else {
+            (self.reserve_b, self.reserve_a)
+        };
+        if reserve_in == 0 || reserve_out == 0 {
+            return Err(AppError::InvalidPool(format!("pool {} has no liquidity", self.name)));
+        }
+
+        // out = reserve_out * in_after_fee / (reserve_in + in_after_fee)
+        let in_after_fee = amount_in as u128 * (BPS_DENOMINATOR - self.fee_bps as u64) as u128;
+        let numerator = reserve_out as u128 * in_after_fee;
+        let denominator = reserve_in as u128 * BPS_DENOMINATOR as u128 + in_after_fee;
+        to_u64(numerator / denominator)
+    }
+
+    /// Apply a deposit to the reserves
+    pub fn apply_deposit(&mut self, deposit: &Deposit) -> Result<()> {
+        self.reserve_a = checked_add(self.reserve_a, deposit.amount_a)?;
+        self.reserve_b = checked_add(self.reserve_b, deposit.amount_b)?;
+        self.lp_supply = checked_add(self.lp_supply, deposit.shares)?;
+        Ok(())
+    }
+
+    /// Apply a withdrawal to the reserves
+    pub fn apply_withdraw(&mut self, shares: u64, amount_a: u64, amount_b: u64) -> Result<()> {
+        self.reserve_a = checked_sub(self.reserve_a, amount_a)?;
+        self.reserve_b = checked_sub(self.reserve_b, amount_b)?;
+        self.lp_supply = checked_sub(self.lp_supply, shares)?;
+        Ok(())
+    }
+
+    /// Apply a swap to the reserves
+    pub fn apply_swap(&mut self, mint_in: &str, amount_in: u64, amount_out: u64) -> Result<()> {
+        if self.side_of(mint_in)? {
+            self.reserve_a = checked_add(self.reserve_a, amount_in)?;
+            self.reserve_b = checked_sub(self.reserve_b, amount_out)?;
+        } else {
+            self.reserve_b = checked_add(self.reserve_b, amount_in)?;
+            self.reserve_a = checked_sub(self.reserve_a, amount_out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fail with `SlippageExceeded` if `actual` is below `minimum`
+pub fn check_slippage(actual: u64, minimum: u64) -> Result<()> {
+    if actual < minimum {
+        return Err(AppError::SlippageExceeded { minimum, actual });
+    }
+    Ok(())
+}
+
+fn checked_add(a: u64, b: u64) -> Result<u64> {
+    a.checked_add(b).ok_or(AppError::InvalidAmount(b))
+}
+
+fn checked_sub(a: u64, b: u64) -> Result<u64> {
+    a.checked_sub(b).ok_or(AppError::InvalidAmount(b))
+}
+
+fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| AppError::InvalidAmount(u64::MAX))
+}
+
+fn div_ceil(numerator: u128, denominator: u128) -> u128 {
+    numerator.div_ceil(denominator)
+}
+
+/// Integer square root (floor)
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    // Newton's method from an over-estimate
+    let mut x = 1u128 << ((128 - value.leading_zeros()).div_ceil(2));
+    loop {
+        let y = (x + value / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded_pool() -> Pool {
+        let mut pool = Pool::new("SOL-USDC".into(), "SOL".into(), "USDC".into(), 30).unwrap();
+        let deposit = pool.quote_deposit(1_000, 100_000).unwrap();
+        pool.apply_deposit(&deposit).unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for value in [0u128, 1, 2, 3, 4, 15, 16, 17, 1 << 40, u64::MAX as u128 * u64::MAX as u128] {
+            let root = isqrt(value);
+            assert!(root * root <= value);
+            assert!((root + 1).checked_mul(root + 1).is_none_or(|sq| sq > value));
+        }
+    }
+
+    #[test]
+    fn test_pool_validation() {
+        assert!(Pool::new("Pool1".into(), "SOL".into(), "SOL".into(), 30).is_err());
+        assert!(Pool::new("Pool1".into(), "SOL".into(), "USDC".into(), MAX_FEE_BPS + 1).is_err());
+        let pool = Pool::new("Pool1".into(), "SOL".into(), "USDC".into(), 30).unwrap();
+        assert!(pool.quote_swap("SOL", 10).is_err()); // no liquidity yet
+        assert!(pool.quote_swap("BONK", 10).is_err());
+    }
+
+    #[test]
+    fn test_deposit_quotes() {
+        let pool = seeded_pool();
+        assert_eq!(pool.lp_supply, 10_000);
+
+        // Asking for an unbalanced deposit only takes the matching ratio
+        let deposit = pool.quote_deposit(100, 50_000).unwrap();
+        assert_eq!(deposit, Deposit { amount_a: 100, amount_b: 10_000, shares: 1_000 });
+
+        let (a, b) = pool.quote_withdraw(5_000).unwrap();
+        assert_eq!((a, b), (500, 50_000));
+        assert!(pool.quote_withdraw(10_001).is_err());
+    }
+
+    #[test]
+    fn test_swap_keeps_invariant() {
+        let mut pool = seeded_pool();
+        for (mint, amount) in [("SOL", 10u64), ("USDC", 2_500), ("SOL", 1), ("USDC", 99_999)] {
+            let k_before = pool.invariant();
+            let out = pool.quote_swap(mint, amount).unwrap();
+            pool.apply_swap(mint, amount, out).unwrap();
+            assert!(pool.invariant() >= k_before);
+        }
+
+        // With the fee, selling 10 SOL returns less than the spot price of 1000 USDC
+        let pool = seeded_pool();
+        let out = pool.quote_swap("SOL", 10).unwrap();
+        assert_eq!(out, 987);
+        assert!(check_slippage(out, 988).is_err());
+        assert!(check_slippage(out, 987).is_ok());
+    }
+}
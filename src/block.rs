@@ -0,0 +1,547 @@
+// ============================================
+// BLOCK PRODUCTION
+// ============================================
+// The ledger pools submitted transactions and packs the highest priority
+// ones into one block per slot. Each block header commits to its parent,
+// the transactions it carries and the state they left behind, so the chain
+// of headers pins down history.
+// Submissions must reference a recent block hash, or a durable nonce they
+// advance first, and are rejected if already seen, so a transaction lands at
+// most once.
+
+use std::collections::HashMap;
+
+use crate::blockhash::RecentBlockhashes;
+use crate::error::Result;
+use crate::error::AppError;
+use crate::hash::{self, Hash, HASH_BYTES};
+use crate::instruction::Instruction;
+use crate::mempool::Mempool;
+use crate::processor::Processor;
+use crate::state::AppState;
+use crate::transaction::Transaction;
+
+pub use crate::blockhash::MAX_RECENT_BLOCKHASHES;
+pub use crate::mempool::TransactionStatus;
+
+/// Most transactions executed in one block; the rest wait for the next
+pub const MAX_BLOCK_TRANSACTIONS: usize = 256;
+
+/// Header of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub slot: u64,
+    pub parent_hash: Hash,
+    pub transactions_root: Hash,
+    pub state_root: Hash, // State after the block's transactions
+}
+
+impl BlockHeader {
+    /// Hash identifying the block
+    pub fn hash(&self) -> Hash {
+        hash::hashv(&[
+            &self.slot.to_le_bytes(),
+            &self.parent_hash,
+            &self.transactions_root,
+            &self.state_root,
+        ])
+    }
+}
+
+/// A produced block with the outcome of each of its transactions
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub hash: Hash,
+    pub transactions: Vec<Transaction>,
+    pub results: Vec<Result<Vec<String>>>,
+}
+
+/// Hash committing to a list of transactions, in order
+pub fn transactions_root(transactions: &[Transaction]) -> Hash {
+    let mut hasher = hash::Hasher::new();
+    for transaction in transactions {
+        hasher.update(&transaction.hash());
+    }
+    hasher.finalize()
+}
+
+/// Check that `transaction` starts by advancing the durable nonce it
+/// references in place of a recent block hash
+fn check_durable_nonce(state: &AppState, transaction: &Transaction) -> Result<()> {
+    let expired = || AppError::BlockhashNotFound(hash::to_hex(&transaction.recent_blockhash));
+    let Some(Instruction::AdvanceNonce { name, .. }) = transaction.instructions.first() else {
+        return Err(expired());
+    };
+    match state.get_nonce(name) {
+        Ok(nonce) if nonce.nonce == transaction.recent_blockhash => Ok(()),
+        _ => Err(expired()),
+    }
+}
+
+/// The chain: current state, queued transactions and every block so far
+pub struct Ledger {
+    state: AppState,
+    processor: Processor,
+    mempool: Mempool,
+    blocks: Vec<Block>, // One per slot, starting at the genesis slot
+    recent_blockhashes: RecentBlockhashes,
+    transaction_index: HashMap<Hash, (u64, usize)>, // Transaction hash -> (slot, position in block)
+}
+
+impl Ledger {
+    /// Start a chain from `state`, using only the built-in programs
+    pub fn new(state: AppState) -> Self {
+        Self::with_processor(Processor::new(), state)
+    }
+
+    /// Start a chain from `state`; the genesis block is produced at its current slot
+    pub fn with_processor(processor: Processor, state: AppState) -> Self {
+        let header = BlockHeader {
+            slot: state.current_slot(),
+            parent_hash: [0; HASH_BYTES],
+            transactions_root: transactions_root(&[]),
+            state_root: state.state_root(),
+        };
+        let genesis = Block {
+            header,
+            hash: header.hash(),
+            transactions: Vec::new(),
+            results: Vec::new(),
+        };
+
+        let mut recent_blockhashes = RecentBlockhashes::default();
+        recent_blockhashes.register(genesis.hash);
+
+        Self {
+            state,
+            processor,
+            mempool: Mempool::default(),
+            blocks: vec![genesis],
+            recent_blockhashes,
+            transaction_index: HashMap::new(),
+        }
+    }
+
+    /// Current state
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// Block hash new transactions should reference
+    pub fn recent_blockhash(&self) -> Hash {
+        self.latest_block().hash
+    }
+
+    /// Add a transaction to the mempool; returns its hash. Fails if a
+    /// signature is bad or missing, if its block hash has expired (or its
+    /// durable nonce does not match), if the same transaction is pending or
+    /// was processed, or if the pool is full.
+    pub fn submit(&mut self, transaction: Transaction) -> Result<Hash> {
+        transaction.verify_signatures()?;
+        self.check_lifetime(&transaction)?;
+        self.mempool.insert(transaction)
+    }
+
+    /// Run a transaction against the current state without committing it.
+    /// The same signature, block hash and nonce checks as `submit` apply.
+    pub fn simulate(&self, transaction: &Transaction) -> Result<Vec<String>> {
+        transaction.verify_signatures()?;
+        self.check_lifetime(transaction)?;
+        self.processor.dry_run(&transaction.instructions, &self.state)
+    }
+
+    /// Check that `transaction` references a recent block hash or the current
+    /// value of its durable nonce, and was not processed yet
+    fn check_lifetime(&self, transaction: &Transaction) -> Result<()> {
+        if self.recent_blockhashes.is_recent(&transaction.recent_blockhash) {
+            self.recent_blockhashes.check(transaction)
+        } else {
+            check_durable_nonce(&self.state, transaction)
+        }
+    }
+
+    /// Number of transactions waiting for a block
+    pub fn pending_count(&self) -> usize {
+        self.mempool.len()
+    }
+
+    /// Status of a submitted transaction, while the mempool remembers it
+    pub fn transaction_status(&self, hash: &Hash) -> Option<&TransactionStatus> {
+        self.mempool.status(hash)
+    }
+
+    /// Advance to the next slot and execute the highest priority pending
+    /// transactions in it. Those that expired or became replays while waiting
+    /// are dropped and left out of the block.
+    pub fn produce_block(&mut self) -> &Block {
+        self.state.advance_slots(1);
+        let slot = self.state.current_slot();
+        let mut transactions = Vec::new();
+        let mut results = Vec::new();
+        for transaction in self.mempool.take_batch(MAX_BLOCK_TRANSACTIONS) {
+            let hash = transaction.hash();
+            // An earlier transaction in the block may have advanced the nonce
+            let durable = !self.recent_blockhashes.is_recent(&transaction.recent_blockhash);
+            let admitted = if durable {
+                check_durable_nonce(&self.state, &transaction)
+            } else {
+                self.recent_blockhashes.record(&transaction)
+            };
+            if let Err(e) = admitted {
+                self.mempool.mark_dropped(hash, e);
+                continue;
+            }
+
+            let result = self.processor.process_transaction(&transaction.instructions, &mut self.state);
+            if durable && result.is_err() {
+                // The nonce still advances, as on Solana, so the signed
+                // transaction can never land again
+                let advance = &transaction.instructions[..1];
+                let _ = self.processor.process_transaction(advance, &mut self.state);
+            }
+            self.mempool.mark_processed(hash, slot, result.as_ref().map(|_| ()).map_err(AppError::clone));
+            self.transaction_index.insert(hash, (slot, transactions.len()));
+            transactions.push(transaction);
+            results.push(result);
+        }
+
+        let header = BlockHeader {
+            slot,
+            parent_hash: self.latest_block().hash,
+            transactions_root: transactions_root(&transactions),
+            state_root: self.state.state_root(),
+        };
+        self.recent_blockhashes.register(header.hash());
+        self.blocks.push(Block {
+            header,
+            hash: header.hash(),
+            transactions,
+            results,
+        });
+        self.latest_block()
+    }
+
+    /// Produce `slots` blocks; pending transactions land in the first ones
+    pub fn advance_slots(&mut self, slots: u64) {
+        for _ in 0..slots {
+            self.produce_block();
+        }
+    }
+
+    /// Most recent block
+    pub fn latest_block(&self) -> &Block {
+        // The genesis block is always there
+        &self.blocks[self.blocks.len() - 1]
+    }
+
+    /// Block produced at `slot`, if any
+    pub fn block(&self, slot: u64) -> Option<&Block> {
+        let genesis_slot = self.blocks[0].header.slot;
+        let index = usize::try_from(slot.checked_sub(genesis_slot)?).ok()?;
+        self.blocks.get(index)
+    }
+
+    /// Block that executed transaction `hash`, and its position in the block
+    pub fn find_transaction(&self, hash: &Hash) -> Option<(&Block, usize)> {
+        let (slot, index) = self.transaction_index.get(hash)?;
+        Some((self.block(*slot)?, *index))
+    }
+
+    /// Every block from genesis on
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockhash::MAX_RECENT_BLOCKHASHES;
+    use crate::keypair::Keypair;
+
+    fn transfer(ledger: &Ledger, amount: u64) -> Transaction {
+        Transaction::new(
+            vec![Instruction::transfer("Alice".into(), "Bob".into(), amount)],
+            ledger.recent_blockhash(),
+        )
+    }
+
+    fn ledger() -> Ledger {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+        Ledger::new(state)
+    }
+
+    #[test]
+    fn test_blocks_chain_together() {
+        let mut ledger = ledger();
+        let genesis = ledger.latest_block().clone();
+        assert_eq!(genesis.header.slot, 0);
+
+        ledger.submit(transfer(&ledger, 100)).unwrap();
+        ledger.submit(transfer(&ledger, 5000)).unwrap();
+        let block = ledger.produce_block().clone();
+        assert_eq!(block.header.slot, 1);
+        assert_eq!(block.header.parent_hash, genesis.hash);
+        assert_eq!(block.header.state_root, ledger.state().state_root());
+        assert!(block.results[0].is_ok());
+        assert!(matches!(block.results[1], Err(AppError::InsufficientBalance { .. })));
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 101);
+        assert_eq!(ledger.pending_count(), 0);
+
+        ledger.advance_slots(3);
+        assert_eq!(ledger.latest_block().header.slot, 4);
+        assert_eq!(ledger.block(1).unwrap().hash, block.hash);
+        assert!(ledger.block(5).is_none());
+        assert_eq!(ledger.blocks().len(), 5);
+
+        // An empty block still moves the chain forward
+        let empty = ledger.block(2).unwrap();
+        assert_eq!(empty.header.transactions_root, transactions_root(&[]));
+        assert_eq!(empty.header.parent_hash, block.hash);
+    }
+
+    #[test]
+    fn test_roots_track_contents() {
+        let mut a = ledger();
+        let mut b = ledger();
+        a.submit(transfer(&a, 100)).unwrap();
+        b.submit(transfer(&b, 200)).unwrap();
+        let (block_a, block_b) = (a.produce_block().clone(), b.produce_block().clone());
+        assert_ne!(block_a.header.transactions_root, block_b.header.transactions_root);
+        assert_ne!(block_a.header.state_root, block_b.header.state_root);
+        assert_eq!(block_a.header.parent_hash, block_b.header.parent_hash);
+    }
+
+    #[test]
+    fn test_replay_protection() {
+        let mut ledger = ledger();
+        let transaction = transfer(&ledger, 100);
+        ledger.submit(transaction.clone()).unwrap();
+        // A duplicate is rejected while queued and after it ran
+        assert!(matches!(ledger.submit(transaction.clone()), Err(AppError::AlreadyProcessed(_))));
+        ledger.produce_block();
+        assert!(matches!(ledger.submit(transaction.clone()), Err(AppError::AlreadyProcessed(_))));
+        assert!(matches!(ledger.simulate(&transaction), Err(AppError::AlreadyProcessed(_))));
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 101);
+
+        // Simulation reports the outcome but commits nothing
+        let simulated = transfer(&ledger, 50);
+        assert!(ledger.simulate(&simulated).is_ok());
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 101);
+        assert_eq!(ledger.pending_count(), 0);
+
+        // A retry under a newer block hash is a distinct transaction
+        ledger.submit(transfer(&ledger, 100)).unwrap();
+        ledger.produce_block();
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 201);
+
+        // Once the block hash is too old the transaction is expired, not a duplicate
+        ledger.advance_slots(MAX_RECENT_BLOCKHASHES as u64);
+        assert!(matches!(ledger.submit(transaction), Err(AppError::BlockhashNotFound(_))));
+        assert!(matches!(
+            ledger.submit(Transaction::new(vec![], [7; HASH_BYTES])),
+            Err(AppError::BlockhashNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_mempool_feeds_blocks_by_priority() {
+        let mut ledger = ledger();
+        let cheap = ledger.submit(transfer(&ledger, 600)).unwrap();
+        let urgent = ledger.submit(transfer(&ledger, 700).with_priority_fee(10)).unwrap();
+        assert_eq!(ledger.transaction_status(&urgent), Some(&TransactionStatus::Pending));
+
+        // Only one of the two fits Alice's balance: the higher bid goes first
+        let block = ledger.produce_block().clone();
+        assert_eq!(block.transactions[0].hash(), urgent);
+        assert!(block.results[0].is_ok());
+        assert!(block.results[1].is_err());
+        assert_eq!(
+            ledger.transaction_status(&urgent),
+            Some(&TransactionStatus::Processed { slot: 1, result: Ok(()) })
+        );
+        assert!(matches!(
+            ledger.transaction_status(&cheap),
+            Some(TransactionStatus::Processed { slot: 1, result: Err(AppError::InsufficientBalance { .. }) })
+        ));
+        assert!(ledger.transaction_status(&[9; HASH_BYTES]).is_none());
+        let (found, index) = ledger.find_transaction(&cheap).unwrap();
+        assert_eq!((found.header.slot, index), (1, 1));
+        assert!(ledger.find_transaction(&[9; HASH_BYTES]).is_none());
+
+        // Blocks are capped; the rest wait for the next one
+        for amount in 1..=MAX_BLOCK_TRANSACTIONS as u64 + 5 {
+            ledger.submit(transfer(&ledger, amount)).unwrap();
+        }
+        assert_eq!(ledger.produce_block().transactions.len(), MAX_BLOCK_TRANSACTIONS);
+        assert_eq!(ledger.pending_count(), 5);
+        assert_eq!(ledger.produce_block().transactions.len(), 5);
+    }
+
+    #[test]
+    fn test_durable_nonce_transactions() {
+        let mut ledger = ledger();
+        let setup = Transaction::new(
+            vec![Instruction::create_nonce_account("Alice".into(), "Cold".into(), "Alice".into(), 10)],
+            ledger.recent_blockhash(),
+        );
+        ledger.submit(setup).unwrap();
+        ledger.produce_block();
+
+        // Signed against the nonce, then held far longer than the blockhash window
+        let nonce = ledger.state().get_nonce("Cold").unwrap().nonce;
+        let offline = |amount: u64| {
+            Transaction::new(
+                vec![
+                    Instruction::advance_nonce("Cold".into(), "Alice".into()),
+                    Instruction::transfer("Alice".into(), "Bob".into(), amount),
+                ],
+                nonce,
+            )
+        };
+        ledger.advance_slots(MAX_RECENT_BLOCKHASHES as u64 + 10);
+
+        let transaction = offline(100);
+        ledger.submit(transaction.clone()).unwrap();
+        assert!(matches!(ledger.submit(transaction.clone()), Err(AppError::AlreadyProcessed(_))));
+        // A second transaction on the same nonce is dropped once the first advanced it
+        let conflicting = ledger.submit(offline(200)).unwrap();
+        let block = ledger.produce_block().clone();
+        assert_eq!(block.transactions.len(), 1);
+        assert!(block.results[0].is_ok());
+        assert!(matches!(
+            ledger.transaction_status(&conflicting),
+            Some(TransactionStatus::Dropped(AppError::BlockhashNotFound(_)))
+        ));
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 101);
+        assert_ne!(ledger.state().get_nonce("Cold").unwrap().nonce, nonce);
+
+        // Replays are now stale
+        assert!(matches!(ledger.submit(transaction), Err(AppError::BlockhashNotFound(_))));
+        // The nonce instruction has to come first
+        let unadvanced = Transaction::new(
+            vec![Instruction::transfer("Alice".into(), "Bob".into(), 1)],
+            ledger.state().get_nonce("Cold").unwrap().nonce,
+        );
+        assert!(matches!(ledger.submit(unadvanced), Err(AppError::BlockhashNotFound(_))));
+    }
+
+    #[test]
+    fn test_failed_durable_nonce_transaction_still_advances_the_nonce() {
+        let mut ledger = ledger();
+        let setup = Transaction::new(
+            vec![Instruction::create_nonce_account("Alice".into(), "Cold".into(), "Alice".into(), 10)],
+            ledger.recent_blockhash(),
+        );
+        ledger.submit(setup).unwrap();
+        ledger.produce_block();
+        ledger.advance_slots(MAX_RECENT_BLOCKHASHES as u64 + 1);
+
+        // The transfer is more than Alice holds, so the transaction fails
+        let nonce = ledger.state().get_nonce("Cold").unwrap().nonce;
+        let transaction = Transaction::new(
+            vec![
+                Instruction::advance_nonce("Cold".into(), "Alice".into()),
+                Instruction::transfer("Alice".into(), "Bob".into(), 1_000_000),
+            ],
+            nonce,
+        );
+        let balance = ledger.state().get_account("Alice").unwrap().balance;
+        ledger.submit(transaction.clone()).unwrap();
+        assert!(ledger.produce_block().results[0].is_err());
+        assert_ne!(ledger.state().get_nonce("Cold").unwrap().nonce, nonce);
+        assert_eq!(ledger.state().get_account("Alice").unwrap().balance, balance);
+
+        // Resubmitting it is stale however long the status cache remembers it
+        assert!(matches!(ledger.submit(transaction), Err(AppError::BlockhashNotFound(_))));
+    }
+
+    #[test]
+    fn test_keypair_accounts_require_signatures() {
+        let mut ledger = ledger();
+        let payer = Keypair::from_seed([5; 32]);
+        let create = Transaction::new(
+            vec![Instruction::create_account(payer.account_name(), 300)],
+            ledger.recent_blockhash(),
+        );
+        assert!(matches!(ledger.submit(create.clone()), Err(AppError::MissingSignature(_))));
+        ledger.submit(create.sign(&[&payer])).unwrap();
+        ledger.produce_block();
+
+        let pay = Transaction::new(
+            vec![Instruction::transfer(payer.account_name(), "Bob".into(), 50)],
+            ledger.recent_blockhash(),
+        );
+        assert!(matches!(ledger.simulate(&pay), Err(AppError::MissingSignature(_))));
+        let forged = pay.clone().sign(&[&Keypair::from_seed([6; 32])]);
+        assert!(matches!(ledger.submit(forged), Err(AppError::MissingSignature(_))));
+
+        ledger.submit(pay.sign(&[&payer])).unwrap();
+        ledger.produce_block();
+        assert_eq!(ledger.state().get_account(&payer.account_name()).unwrap().balance, 250);
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 51);
+    }
+
+    #[test]
+    fn test_registered_names_cannot_spend_for_keypair_accounts() {
+        let mut ledger = ledger();
+        let victim = Keypair::from_seed([5; 32]);
+        let create = Transaction::new(
+            vec![Instruction::create_account(victim.account_name(), 1000)],
+            ledger.recent_blockhash(),
+        );
+        ledger.submit(create.sign(&[&victim])).unwrap();
+        ledger.produce_block();
+
+        // Bob points a name of his at the victim and pays himself through it
+        let theft = Transaction::new(
+            vec![
+                Instruction::register_name("evil.sol".into(), "Bob".into(), victim.account_name(), 100),
+                Instruction::transfer("evil.sol".into(), "Bob".into(), 999),
+            ],
+            ledger.recent_blockhash(),
+        );
+        ledger.submit(theft).unwrap();
+        let block = ledger.produce_block();
+        assert!(matches!(block.results[0], Err(AppError::Unauthorized(_))));
+        assert_eq!(ledger.state().get_account(&victim.account_name()).unwrap().balance, 1000);
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 1);
+    }
+
+    #[test]
+    fn test_keypair_account_must_sign_to_hand_over_authority() {
+        let mut ledger = ledger();
+        let victim = Keypair::from_seed([5; 32]);
+        let create = Transaction::new(
+            vec![Instruction::create_account(victim.account_name(), 1000)],
+            ledger.recent_blockhash(),
+        );
+        ledger.submit(create.sign(&[&victim])).unwrap();
+        ledger.produce_block();
+
+        // Bob puts the victim under a multisig of his own and drains it
+        let blockhash = ledger.recent_blockhash();
+        let takeover = |signers: Vec<String>| {
+            Transaction::new(
+                vec![
+                    Instruction::create_multisig("Gang".into(), vec!["Bob".into()], 1),
+                    Instruction::set_authority(victim.account_name(), Some("Gang".into()), signers),
+                    Instruction::multisig_transfer(victim.account_name(), "Bob".into(), 999, vec!["Bob".into()]),
+                ],
+                blockhash,
+            )
+        };
+        assert!(matches!(
+            ledger.submit(takeover(vec![victim.account_name()])),
+            Err(AppError::MissingSignature(_))
+        ));
+        ledger.submit(takeover(Vec::new())).unwrap();
+        let block = ledger.produce_block();
+        assert!(matches!(block.results[0], Err(AppError::Unauthorized(_))));
+        assert_eq!(ledger.state().get_account(&victim.account_name()).unwrap().balance, 1000);
+        assert_eq!(ledger.state().get_account("Bob").unwrap().balance, 1);
+    }
+}
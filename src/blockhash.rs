@@ -0,0 +1,115 @@
+// ============================================
+// RECENT BLOCKHASHES
+// ============================================
+// Transactions name a recent block hash and are only accepted while that
+// hash is among the last MAX_RECENT_BLOCKHASHES blocks. That bounds how long
+// a transaction stays valid, so replays can be caught by remembering just the
+// transactions processed under each recent hash; older ones are forgotten
+// along with their block hash.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::transaction::Transaction;
+
+/// Number of latest block hashes a transaction may reference
+pub const MAX_RECENT_BLOCKHASHES: usize = 150;
+
+/// Bounded queue of recent block hashes and the transactions seen under each
+#[derive(Debug, Clone)]
+pub struct RecentBlockhashes {
+    hashes: VecDeque<Hash>,                  // Oldest first
+    processed: HashMap<Hash, HashSet<Hash>>, // Block hash -> transaction hashes
+    max_age: usize,
+}
+
+impl Default for RecentBlockhashes {
+    fn default() -> Self {
+        Self::new(MAX_RECENT_BLOCKHASHES)
+    }
+}
+
+impl RecentBlockhashes {
+    /// Keep the last `max_age` block hashes
+    pub fn new(max_age: usize) -> Self {
+        Self {
+            hashes: VecDeque::new(),
+            processed: HashMap::new(),
+            max_age,
+        }
+    }
+
+    /// Record a new block hash, expiring the oldest one past `max_age`
+    pub fn register(&mut self, blockhash: Hash) {
+        self.hashes.push_back(blockhash);
+        while self.hashes.len() > self.max_age {
+            if let Some(expired) = self.hashes.pop_front() {
+                self.processed.remove(&expired);
+            }
+        }
+    }
+
+    /// Whether `blockhash` may still be referenced
+    pub fn is_recent(&self, blockhash: &Hash) -> bool {
+        self.hashes.contains(blockhash)
+    }
+
+    /// Check that `transaction` is neither expired nor a replay
+    pub fn check(&self, transaction: &Transaction) -> Result<()> {
+        if !self.is_recent(&transaction.recent_blockhash) {
+            return Err(AppError::BlockhashNotFound(hash::to_hex(&transaction.recent_blockhash)));
+        }
+        // The hash covers the block hash, so a replay can only be filed under the same one
+        let signature = transaction.hash();
+        if self.processed
+            .get(&transaction.recent_blockhash)
+            .is_some_and(|seen| seen.contains(&signature))
+        {
+            return Err(AppError::AlreadyProcessed(hash::to_hex(&signature)));
+        }
+        Ok(())
+    }
+
+    /// Check `transaction` and remember it, so later copies are rejected
+    pub fn record(&mut self, transaction: &Transaction) -> Result<()> {
+        self.check(transaction)?;
+        self.processed
+            .entry(transaction.recent_blockhash)
+            .or_default()
+            .insert(transaction.hash());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction;
+
+    fn transfer(blockhash: Hash) -> Transaction {
+        Transaction::new(vec![Instruction::transfer("Alice".into(), "Bob".into(), 1)], blockhash)
+    }
+
+    #[test]
+    fn test_replays_and_expiry() {
+        let mut recent = RecentBlockhashes::new(3);
+        recent.register([1; 32]);
+        assert!(recent.is_recent(&[1; 32]));
+
+        let transaction = transfer([1; 32]);
+        recent.record(&transaction).unwrap();
+        assert!(matches!(recent.record(&transaction), Err(AppError::AlreadyProcessed(_))));
+        assert!(matches!(recent.check(&transfer([9; 32])), Err(AppError::BlockhashNotFound(_))));
+
+        // Once its block hash ages out the transaction is rejected as expired,
+        // and the queue no longer remembers it
+        for n in 2..=4 {
+            recent.register([n; 32]);
+        }
+        assert!(!recent.is_recent(&[1; 32]));
+        assert!(matches!(recent.check(&transaction), Err(AppError::BlockhashNotFound(_))));
+        assert!(recent.processed.is_empty());
+        assert_eq!(recent.hashes.len(), 3);
+    }
+}
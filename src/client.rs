@@ -0,0 +1,278 @@
+// ============================================
+// RPC CLIENT
+// ============================================
+// Talks to an `RpcServer` over HTTP for services that use this crate as a
+// library. Low-level calls mirror the JSON-RPC methods; the helpers on top
+// fetch a block hash, sign with the given keypairs and submit in one go.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::codec;
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::instruction::Instruction;
+use crate::json::Json;
+use crate::keypair::Keypair;
+use crate::transaction::Transaction;
+
+/// How long to wait for the server before giving up on a call
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause between polls while waiting for a transaction
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// JSON-RPC client for one server
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    addr: String,
+}
+
+fn request_failed(reason: impl ToString) -> AppError {
+    AppError::RequestFailed(reason.to_string())
+}
+
+impl RpcClient {
+    /// Client for the server at `url`, as `host:port` or `http://host:port`
+    pub fn new(url: &str) -> Self {
+        let addr = url.strip_prefix("http://").unwrap_or(url);
+        Self {
+            addr: addr.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Make one call; returns its `result`. Error responses become
+    /// `RequestFailed` carrying the server's message.
+    pub fn call(&self, method: &str, params: Vec<Json>) -> Result<Json> {
+        let body = Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("id", Json::from(1u32)),
+            ("method", Json::from(method)),
+            ("params", Json::from(params)),
+        ])
+        .to_string();
+
+        let mut stream = TcpStream::connect(&self.addr).map_err(request_failed)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT)).map_err(request_failed)?;
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            self.addr,
+            body.len(),
+            body
+        )
+        .map_err(request_failed)?;
+        // The server closes the connection after answering
+        let mut response = String::new();
+        stream.read_to_string(&mut response).map_err(request_failed)?;
+
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| request_failed("malformed HTTP response"))?;
+        let status = head.lines().next().unwrap_or_default();
+        if !status.starts_with("HTTP/1.1 200") {
+            return Err(request_failed(status));
+        }
+        let mut response = Json::parse(body).map_err(request_failed)?;
+        if let Some(error) = response.get("error") {
+            let message = error.get("message").and_then(Json::as_str).unwrap_or("unknown error");
+            return Err(request_failed(message));
+        }
+        match &mut response {
+            Json::Object(fields) => fields
+                .iter()
+                .position(|(key, _)| key == "result")
+                .map(|index| fields.swap_remove(index).1)
+                .ok_or_else(|| request_failed("response has no result")),
+            _ => Err(request_failed("response is not an object")),
+        }
+    }
+
+    /// Balance of account `name`
+    pub fn get_balance(&self, name: &str) -> Result<u64> {
+        let result = self.call("getBalance", vec![Json::from(name)])?;
+        value(&result)
+            .as_u64()
+            .ok_or_else(|| request_failed("balance is not a number"))
+    }
+
+    /// Contents of account `name` as `getAccountInfo` returns them; `None` if
+    /// it does not exist
+    pub fn get_account_info(&self, name: &str) -> Result<Option<Json>> {
+        let result = self.call("getAccountInfo", vec![Json::from(name)])?;
+        let account = value(&result);
+        Ok((!account.is_null()).then(|| account.clone()))
+    }
+
+    /// Balance of account `name` at the end of `slot`
+    pub fn get_balance_at(&self, name: &str, slot: u64) -> Result<u64> {
+        let result = self.call("getBalanceAt", vec![Json::from(name), Json::from(slot)])?;
+        value(&result)
+            .as_u64()
+            .ok_or_else(|| request_failed("balance is not a number"))
+    }
+
+    /// One page of `name`'s balance changes in slots `from..=to`, as
+    /// `getAccountHistory` returns it: `changes`, and the `next` cursor
+    pub fn get_account_history(&self, name: &str, from: u64, to: u64, cursor: u64, limit: u64) -> Result<Json> {
+        let config = Json::object([
+            ("fromSlot", Json::from(from)),
+            ("toSlot", Json::from(to)),
+            ("cursor", Json::from(cursor)),
+            ("limit", Json::from(limit)),
+        ]);
+        let result = self.call("getAccountHistory", vec![Json::from(name), config])?;
+        Ok(value(&result).clone())
+    }
+
+    /// Block hash to build new transactions on
+    pub fn get_latest_blockhash(&self) -> Result<Hash> {
+        let result = self.call("getLatestBlockhash", Vec::new())?;
+        value(&result)
+            .get("blockhash")
+            .and_then(Json::as_str)
+            .and_then(hash::from_hex)
+            .ok_or_else(|| request_failed("blockhash is not 64 hex digits"))
+    }
+
+    /// Current slot of the server's ledger
+    pub fn get_slot(&self) -> Result<u64> {
+        self.call("getSlot", Vec::new())?
+            .as_u64()
+            .ok_or_else(|| request_failed("slot is not a number"))
+    }
+
+    /// Submit a signed transaction; returns its hash
+    pub fn send_transaction(&self, transaction: &Transaction) -> Result<Hash> {
+        let result = self.call("sendTransaction", encoded(transaction))?;
+        result
+            .as_str()
+            .and_then(hash::from_hex)
+            .ok_or_else(|| request_failed("transaction hash is not 64 hex digits"))
+    }
+
+    /// Logs of a dry run of `transaction`; a failing run is an error
+    pub fn simulate_transaction(&self, transaction: &Transaction) -> Result<Vec<String>> {
+        let result = self.call("simulateTransaction", encoded(transaction))?;
+        let simulation = value(&result);
+        if let Some(message) = simulation.get("err").and_then(|err| err.get("message")).and_then(Json::as_str) {
+            return Err(request_failed(message));
+        }
+        let logs = simulation.get("logs").and_then(Json::as_array).unwrap_or_default();
+        Ok(logs.iter().filter_map(Json::as_str).map(str::to_string).collect())
+    }
+
+    /// What the server knows about transaction `hash`, as `getTransaction`
+    /// returns it; `None` if it never saw it or forgot it
+    pub fn get_transaction(&self, hash: &Hash) -> Result<Option<Json>> {
+        let result = self.call("getTransaction", vec![Json::from(hash::to_hex(hash))])?;
+        Ok((!result.is_null()).then_some(result))
+    }
+
+    /// Poll until transaction `hash` is processed or dropped, for at most `timeout`
+    pub fn confirm_transaction(&self, hash: &Hash, timeout: Duration) -> Result<Json> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(transaction) = self.get_transaction(hash)?
+                && transaction.get("status").and_then(Json::as_str) != Some("pending")
+            {
+                return Ok(transaction);
+            }
+            if Instant::now() >= deadline {
+                return Err(request_failed(format!("transaction {} not confirmed", hash::to_hex(hash))));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Build a transaction of `instructions` on the latest block hash, sign it
+    /// with `signers` and submit it; returns its hash
+    pub fn send_instructions(&self, instructions: Vec<Instruction>, signers: &[&Keypair]) -> Result<Hash> {
+        let transaction = Transaction::new(instructions, self.get_latest_blockhash()?).sign(signers);
+        self.send_transaction(&transaction)
+    }
+
+    /// Open the account controlled by `owner` with `balance`
+    pub fn create_account(&self, owner: &Keypair, balance: u64) -> Result<Hash> {
+        self.send_instructions(vec![Instruction::create_account(owner.account_name(), balance)], &[owner])
+    }
+
+    /// Send `amount` from `payer`'s account to account `to`
+    pub fn transfer(&self, payer: &Keypair, to: &str, amount: u64) -> Result<Hash> {
+        self.send_instructions(
+            vec![Instruction::transfer(payer.account_name(), to.to_string(), amount)],
+            &[payer],
+        )
+    }
+}
+
+/// The `value` of a result carrying a context
+fn value(result: &Json) -> &Json {
+    result.get("value").unwrap_or(&Json::Null)
+}
+
+fn encoded(transaction: &Transaction) -> Vec<Json> {
+    vec![
+        Json::from(codec::base64_encode(&transaction.encode())),
+        Json::object([("encoding", Json::from("base64"))]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Ledger;
+    use crate::rpc::{self, RpcServer};
+    use crate::state::AppState;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_client_against_server() {
+        let mut state = AppState::new();
+        state.create_account("Bob".into(), 1).unwrap();
+        let ledger = Arc::new(Mutex::new(Ledger::new(state)));
+        let server = RpcServer::bind("127.0.0.1:0", Arc::clone(&ledger)).unwrap();
+        let client = RpcClient::new(&format!("http://{}/", server.local_addr().unwrap()));
+        server.spawn();
+
+        let payer = Keypair::from_seed([9; 32]);
+        client.create_account(&payer, 500).unwrap();
+        rpc::lock(&ledger).produce_block();
+        assert_eq!(client.get_balance(&payer.account_name()).unwrap(), 500);
+        assert_eq!(client.get_slot().unwrap(), 1);
+
+        let hash = client.transfer(&payer, "Bob", 200).unwrap();
+        rpc::lock(&ledger).produce_block();
+        let confirmed = client.confirm_transaction(&hash, Duration::from_secs(1)).unwrap();
+        assert_eq!(confirmed.get("status").and_then(Json::as_str), Some("processed"));
+        assert_eq!(client.get_balance("Bob").unwrap(), 201);
+        assert_eq!(client.get_balance_at("Bob", 1).unwrap(), 1);
+        let history = client.get_account_history("Bob", 0, u64::MAX, 1, 10).unwrap();
+        let changes = history.get("changes").and_then(Json::as_array).unwrap();
+        assert_eq!(changes[0].get("counterparty").and_then(Json::as_str), Some(payer.account_name().as_str()));
+        assert!(client.get_account_info("Nobody").unwrap().is_none());
+
+        // Unsigned and failing transactions are refused with the server's reason
+        let unsigned = Transaction::new(
+            vec![Instruction::transfer(payer.account_name(), "Bob".into(), 1)],
+            client.get_latest_blockhash().unwrap(),
+        );
+        let error = client.send_transaction(&unsigned).unwrap_err();
+        assert!(error.to_string().contains("Missing signature"));
+        let overdraft = Transaction::new(
+            vec![Instruction::transfer(payer.account_name(), "Bob".into(), 10_000)],
+            client.get_latest_blockhash().unwrap(),
+        )
+        .sign(&[&payer]);
+        assert!(matches!(client.simulate_transaction(&overdraft), Err(AppError::RequestFailed(_))));
+        assert!(client.simulate_transaction(&unsigned.sign(&[&payer])).is_ok());
+    }
+
+    #[test]
+    fn test_unreachable_server() {
+        // Nothing listens on port 1 of the loopback interface
+        let client = RpcClient::new("127.0.0.1:1");
+        assert!(matches!(client.get_slot(), Err(AppError::RequestFailed(_))));
+    }
+}
@@ -0,0 +1,300 @@
+// ============================================
+// BINARY ENCODING
+// ============================================
+// A small deterministic encoding used wherever values are hashed or signed:
+// integers are little-endian, strings and vectors carry a u32 length prefix
+// and options a 0/1 tag byte. `Decode` reads it back, for values that travel
+// over the wire; base64 wraps those bytes in text.
+
+use crate::error::{AppError, Result};
+use crate::pubkey::{Pubkey, PUBKEY_BYTES};
+
+/// Types with a canonical byte encoding
+pub trait Encode {
+    fn encode_to(&self, out: &mut Vec<u8>);
+}
+
+/// Builder that appends encoded values to a buffer
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append one value
+    pub fn put<T: Encode + ?Sized>(mut self, value: &T) -> Self {
+        value.encode_to(&mut self.buf);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Types that can be read back from their canonical encoding
+pub trait Decode: Sized {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self>;
+}
+
+/// Reader over encoded bytes
+#[derive(Debug)]
+pub struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Read the next `len` raw bytes
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(AppError::InvalidInstructionData("unexpected end of data".to_string()));
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    /// Read one value
+    pub fn get<T: Decode>(&mut self) -> Result<T> {
+        T::decode_from(self)
+    }
+
+    /// Fail if anything is left over
+    pub fn finish(self) -> Result<()> {
+        if !self.data.is_empty() {
+            return Err(AppError::InvalidInstructionData(format!("{} trailing bytes", self.data.len())));
+        }
+        Ok(())
+    }
+}
+
+macro_rules! encode_int {
+    ($($ty:ty),*) => {
+        $(impl Encode for $ty {
+            fn encode_to(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+                let bytes = decoder.take(std::mem::size_of::<$ty>())?;
+                Ok(<$ty>::from_le_bytes(bytes.try_into().expect("length checked by take")))
+            }
+        })*
+    };
+}
+
+encode_int!(u8, u16, u32, u64);
+
+impl Encode for bool {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl Encode for str {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode_to(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.as_str().encode_to(out);
+    }
+}
+
+impl Encode for Pubkey {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_ref());
+    }
+}
+
+/// Fixed-size byte arrays (hashes) are written as-is, without a length
+impl<const N: usize> Encode for [u8; N] {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode_to(out);
+        for item in self {
+            item.encode_to(out);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode_to(out);
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for bool {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        match decoder.get::<u8>()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(AppError::InvalidInstructionData(format!("invalid bool {}", other))),
+        }
+    }
+}
+
+impl Decode for String {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        let len = decoder.get::<u32>()? as usize;
+        let bytes = decoder.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| AppError::InvalidInstructionData("invalid UTF-8".to_string()))
+    }
+}
+
+impl Decode for Pubkey {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Pubkey::new(decoder.get::<[u8; PUBKEY_BYTES]>()?))
+    }
+}
+
+impl<const N: usize> Decode for [u8; N] {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        Ok(decoder.take(N)?.try_into().expect("length checked by take"))
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        // No preallocation: the length prefix is untrusted
+        let len = decoder.get::<u32>()?;
+        (0..len).map(|_| decoder.get()).collect()
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        match decoder.get::<u8>()? {
+            0 => Ok(None),
+            1 => Ok(Some(decoder.get()?)),
+            other => Err(AppError::InvalidInstructionData(format!("invalid option tag {}", other))),
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as padded standard base64
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(BASE64_ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode padded standard base64, returning `None` on malformed input
+pub fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    for (index, chunk) in s.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && index != s.len() / 4 - 1) {
+            return None;
+        }
+        let mut group = 0u32;
+        for &c in &chunk[..4 - padding] {
+            group = group << 6 | BASE64_ALPHABET.iter().position(|&a| a == c)? as u32;
+        }
+        group <<= 6 * padding;
+        out.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_is_unambiguous() {
+        let bytes = Encoder::new().put("ab").put(&7u64).put(&Some(true)).finish();
+        assert_eq!(bytes, [2, 0, 0, 0, b'a', b'b', 7, 0, 0, 0, 0, 0, 0, 0, 1, 1]);
+
+        // Length prefixes keep ("a", "bc") and ("ab", "c") apart
+        let left = Encoder::new().put("a").put("bc").finish();
+        let right = Encoder::new().put("ab").put("c").finish();
+        assert_ne!(left, right);
+
+        let none: Option<u8> = None;
+        assert_eq!(Encoder::new().put(&none).put(&vec![1u8, 2]).finish(), [0, 2, 0, 0, 0, 1, 2]);
+    }
+
+    /// Decode a whole buffer as one value
+    fn decode<T: Decode>(data: &[u8]) -> Result<T> {
+        let mut decoder = Decoder::new(data);
+        let value = decoder.get()?;
+        decoder.finish()?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let bytes = Encoder::new().put("ab").put(&7u64).put(&Some(true)).put(&vec![3u16, 4]).finish();
+        let mut decoder = Decoder::new(&bytes);
+        assert_eq!(decoder.get::<String>().unwrap(), "ab");
+        assert_eq!(decoder.get::<u64>().unwrap(), 7);
+        assert_eq!(decoder.get::<Option<bool>>().unwrap(), Some(true));
+        assert_eq!(decoder.get::<Vec<u16>>().unwrap(), [3, 4]);
+        decoder.finish().unwrap();
+
+        // Truncated, trailing and out-of-range input is rejected
+        assert!(decode::<u64>(&[1, 2, 3]).is_err());
+        assert!(decode::<u8>(&[1, 2]).is_err());
+        assert!(decode::<bool>(&[2]).is_err());
+        assert!(decode::<Vec<u8>>(&[255, 255, 255, 255]).is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        for (plain, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")] {
+            assert_eq!(base64_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), plain.as_bytes());
+        }
+        assert!(base64_decode("Zg=").is_none());
+        assert!(base64_decode("Zg==Zg==").is_none());
+        assert!(base64_decode("Z!==").is_none());
+    }
+}
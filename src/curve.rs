@@ -0,0 +1,449 @@
+// ============================================
+// ED25519 CURVE
+// ============================================
+// Decides whether 32 bytes are a valid compressed ed25519 point.
+// Program derived addresses must NOT be on the curve, so that no private key
+// can ever sign for them. Also provides the point and scalar arithmetic that
+// keypairs use to sign and verify. None of it is constant-time.
+
+/// Field element modulo p = 2^255 - 19, stored as 4 little-endian u64 limbs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fe([u64; 4]);
+
+const P: Fe = Fe([
+    0xffff_ffff_ffff_ffed,
+    0xffff_ffff_ffff_ffff,
+    0xffff_ffff_ffff_ffff,
+    0x7fff_ffff_ffff_ffff,
+]);
+
+const ZERO: Fe = Fe([0, 0, 0, 0]);
+const ONE: Fe = Fe([1, 0, 0, 0]);
+/// Curve constant d = -121665 / 121666
+const D: Fe = Fe([0x75eb4dca135978a3, 0x00700a4d4141d8ab, 0x8cc740797779e898, 0x52036cee2b6ffe73]);
+/// 2d, used by point addition
+const D2: Fe = Fe([0xebd69b9426b2f159, 0x00e0149a8283b156, 0x198e80f2eef3d130, 0x2406d9dc56dffce7]);
+/// A square root of -1
+const SQRT_M1: Fe = Fe([0xc4ee1b274a0ea0b0, 0x2f431806ad2fe478, 0x2b4d00993dfbd7a7, 0x2b8324804fc1df0b]);
+
+/// Order of the base point, L = 2^252 + 27742317777372353535851937790883648493
+const L: [u64; 4] = [0x5812631a5cf5d3ed, 0x14def9dea2f79cd6, 0, 0x1000000000000000];
+
+impl Fe {
+    #[cfg(test)]
+    fn from_u64(value: u64) -> Self {
+        Fe([value, 0, 0, 0])
+    }
+
+    /// 32 little-endian bytes as 4 limbs, unreduced
+    fn from_le_limbs(bytes: &[u8; 32]) -> [u64; 4] {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(&bytes[i * 8..i * 8 + 8]);
+            *limb = u64::from_le_bytes(word);
+        }
+        limbs
+    }
+
+    /// Read 32 little-endian bytes, ignoring the top (sign) bit
+    fn from_bytes(bytes: &[u8; 32]) -> Self {
+        let mut limbs = Fe::from_le_limbs(bytes);
+        limbs[3] &= 0x7fff_ffff_ffff_ffff;
+        Fe(limbs).reduce_once()
+    }
+
+    /// Canonical 32 little-endian bytes
+    fn to_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (chunk, limb) in bytes.chunks_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Whether the canonical encoding is odd, the "sign" of x in a compressed point
+    fn is_negative(&self) -> bool {
+        self.0[0] & 1 == 1
+    }
+
+    fn ge(&self, other: &Fe) -> bool {
+        for i in (0..4).rev() {
+            if self.0[i] != other.0[i] {
+                return self.0[i] > other.0[i];
+            }
+        }
+        true
+    }
+
+    /// Subtract p if the value is at least p (input must be < 2p)
+    fn reduce_once(self) -> Fe {
+        if !self.ge(&P) {
+            return self;
+        }
+        let mut out = [0u64; 4];
+        let mut borrow = 0u64;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (d1, b1) = self.0[i].overflowing_sub(P.0[i]);
+            let (d2, b2) = d1.overflowing_sub(borrow);
+            *limb = d2;
+            borrow = (b1 || b2) as u64;
+        }
+        Fe(out)
+    }
+
+    /// Fold a 256-bit value (plus a small carry above it) back below p
+    fn fold(limbs: [u64; 4], carry: u64) -> Fe {
+        // 2^256 = 38 (mod p)
+        let mut out = limbs;
+        let mut extra = carry as u128 * 38;
+        while extra != 0 {
+            for limb in out.iter_mut() {
+                let sum = *limb as u128 + extra;
+                *limb = sum as u64;
+                extra = sum >> 64;
+            }
+            extra *= 38;
+        }
+
+        // 2^255 = 19 (mod p)
+        let top = out[3] >> 63;
+        out[3] &= 0x7fff_ffff_ffff_ffff;
+        let mut extra = top as u128 * 19;
+        for limb in out.iter_mut() {
+            let sum = *limb as u128 + extra;
+            *limb = sum as u64;
+            extra = sum >> 64;
+        }
+        Fe(out).reduce_once()
+    }
+
+    fn add(&self, other: &Fe) -> Fe {
+        let mut out = [0u64; 4];
+        let mut carry = 0u128;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let sum = self.0[i] as u128 + other.0[i] as u128 + carry;
+            *limb = sum as u64;
+            carry = sum >> 64;
+        }
+        Fe::fold(out, carry as u64)
+    }
+
+    fn neg(&self) -> Fe {
+        if *self == ZERO {
+            return ZERO;
+        }
+        let mut out = [0u64; 4];
+        let mut borrow = 0u64;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (d1, b1) = P.0[i].overflowing_sub(self.0[i]);
+            let (d2, b2) = d1.overflowing_sub(borrow);
+            *limb = d2;
+            borrow = (b1 || b2) as u64;
+        }
+        Fe(out)
+    }
+
+    fn sub(&self, other: &Fe) -> Fe {
+        self.add(&other.neg())
+    }
+
+    fn mul(&self, other: &Fe) -> Fe {
+        let mut wide = [0u64; 8];
+        for i in 0..4 {
+            let mut carry = 0u128;
+            for j in 0..4 {
+                let cur = wide[i + j] as u128 + self.0[i] as u128 * other.0[j] as u128 + carry;
+                wide[i + j] = cur as u64;
+                carry = cur >> 64;
+            }
+            wide[i + 4] = carry as u64;
+        }
+
+        // Fold the high half: hi * 2^256 = hi * 38 (mod p)
+        let mut out = [0u64; 4];
+        let mut carry = 0u128;
+        for i in 0..4 {
+            let cur = wide[i] as u128 + wide[i + 4] as u128 * 38 + carry;
+            out[i] = cur as u64;
+            carry = cur >> 64;
+        }
+        Fe::fold(out, carry as u64)
+    }
+
+    fn square(&self) -> Fe {
+        self.mul(self)
+    }
+
+    /// Raise to a power given as 4 little-endian u64 limbs
+    fn pow(&self, exponent: &[u64; 4]) -> Fe {
+        let mut result = ONE;
+        for i in (0..4).rev() {
+            for bit in (0..64).rev() {
+                result = result.square();
+                if (exponent[i] >> bit) & 1 == 1 {
+                    result = result.mul(self);
+                }
+            }
+        }
+        result
+    }
+
+    fn invert(&self) -> Fe {
+        // Fermat: a^(p-2)
+        self.pow(&[
+            0xffff_ffff_ffff_ffeb,
+            0xffff_ffff_ffff_ffff,
+            0xffff_ffff_ffff_ffff,
+            0x7fff_ffff_ffff_ffff,
+        ])
+    }
+}
+
+/// An x with -x^2 + y^2 = 1 + d x^2 y^2, if there is one (either root)
+fn recover_x(y: &Fe) -> Option<Fe> {
+    // x^2 = (y^2 - 1) / (d y^2 + 1)
+    let y2 = y.square();
+    let u = y2.sub(&ONE);
+    let v = D.mul(&y2).add(&ONE);
+
+    // Candidate root x = u v^3 (u v^7)^((p-5)/8); u/v is square iff v x^2 = +-u
+    let v3 = v.square().mul(&v);
+    let v7 = v3.square().mul(&v);
+    let x = u.mul(&v3).mul(&u.mul(&v7).pow(&[
+        0xffff_ffff_ffff_fffd,
+        0xffff_ffff_ffff_ffff,
+        0xffff_ffff_ffff_ffff,
+        0x0fff_ffff_ffff_ffff,
+    ]));
+    let check = v.mul(&x.square());
+    if check == u {
+        Some(x)
+    } else if check == u.neg() {
+        Some(x.mul(&SQRT_M1))
+    } else {
+        None
+    }
+}
+
+/// Check whether `bytes` decompress to a point on the ed25519 curve
+pub fn is_on_curve(bytes: &[u8; 32]) -> bool {
+    recover_x(&Fe::from_bytes(bytes)).is_some()
+}
+
+/// A curve point in extended coordinates: x = X/Z, y = Y/Z, xy = T/Z
+#[derive(Debug, Clone, Copy)]
+pub struct Point {
+    x: Fe,
+    y: Fe,
+    z: Fe,
+    t: Fe,
+}
+
+impl Point {
+    /// The neutral element (0, 1)
+    pub const IDENTITY: Point = Point {
+        x: ZERO,
+        y: ONE,
+        z: ONE,
+        t: ZERO,
+    };
+
+    /// The standard base point B (y = 4/5, x even)
+    pub fn base() -> Point {
+        let mut bytes = [0x66u8; 32];
+        bytes[0] = 0x58;
+        Point::decompress(&bytes).expect("base point is on the curve")
+    }
+
+    /// Decode a compressed point; rejects non-canonical y and points off the curve
+    pub fn decompress(bytes: &[u8; 32]) -> Option<Point> {
+        let y = Fe::from_bytes(bytes);
+        let mut canonical = *bytes;
+        canonical[31] &= 0x7f;
+        if y.to_bytes() != canonical {
+            return None;
+        }
+
+        let mut x = recover_x(&y)?;
+        let negative = bytes[31] >> 7 == 1;
+        if x == ZERO && negative {
+            return None;
+        }
+        if x.is_negative() != negative {
+            x = x.neg();
+        }
+        Some(Point {
+            x,
+            y,
+            z: ONE,
+            t: x.mul(&y),
+        })
+    }
+
+    /// Encode as y with the sign of x in the top bit
+    pub fn compress(&self) -> [u8; 32] {
+        let z_inv = self.z.invert();
+        let x = self.x.mul(&z_inv);
+        let mut bytes = self.y.mul(&z_inv).to_bytes();
+        bytes[31] |= (x.is_negative() as u8) << 7;
+        bytes
+    }
+
+    /// Group addition (complete: also doubles)
+    pub fn add(&self, other: &Point) -> Point {
+        let a = self.y.sub(&self.x).mul(&other.y.sub(&other.x));
+        let b = self.y.add(&self.x).mul(&other.y.add(&other.x));
+        let c = self.t.mul(&D2).mul(&other.t);
+        let d = self.z.add(&self.z).mul(&other.z);
+        let (e, f, g, h) = (b.sub(&a), d.sub(&c), d.add(&c), b.add(&a));
+        Point {
+            x: e.mul(&f),
+            y: g.mul(&h),
+            z: f.mul(&g),
+            t: e.mul(&h),
+        }
+    }
+
+    /// Multiply by a 256-bit little-endian scalar
+    pub fn mul(&self, scalar: &[u8; 32]) -> Point {
+        let mut result = Point::IDENTITY;
+        for byte in scalar.iter().rev() {
+            for bit in (0..8).rev() {
+                result = result.add(&result);
+                if (byte >> bit) & 1 == 1 {
+                    result = result.add(self);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl PartialEq for Point {
+    fn eq(&self, other: &Point) -> bool {
+        // X1/Z1 = X2/Z2 and Y1/Z1 = Y2/Z2, without inverting
+        self.x.mul(&other.z) == other.x.mul(&self.z) && self.y.mul(&other.z) == other.y.mul(&self.z)
+    }
+}
+
+fn at_least_l(limbs: &[u64; 4]) -> bool {
+    for i in (0..4).rev() {
+        if limbs[i] != L[i] {
+            return limbs[i] > L[i];
+        }
+    }
+    true
+}
+
+/// Reduce a little-endian integer of any length modulo L
+pub fn reduce_scalar(bytes: &[u8]) -> [u8; 32] {
+    // Shift in one bit at a time, most significant first; the remainder stays below 2L
+    let mut acc = [0u64; 4];
+    for byte in bytes.iter().rev() {
+        for bit in (0..8).rev() {
+            for i in (1..4).rev() {
+                acc[i] = (acc[i] << 1) | (acc[i - 1] >> 63);
+            }
+            acc[0] = (acc[0] << 1) | ((byte >> bit) & 1) as u64;
+            if at_least_l(&acc) {
+                let mut borrow = false;
+                for (limb, l) in acc.iter_mut().zip(L) {
+                    let (d1, b1) = limb.overflowing_sub(l);
+                    let (d2, b2) = d1.overflowing_sub(borrow as u64);
+                    *limb = d2;
+                    borrow = b1 || b2;
+                }
+            }
+        }
+    }
+    Fe(acc).to_bytes()
+}
+
+/// (a * b + c) mod L for little-endian scalars
+pub fn scalar_mul_add(a: &[u8; 32], b: &[u8; 32], c: &[u8; 32]) -> [u8; 32] {
+    let (a, b, c) = (Fe::from_le_limbs(a), Fe::from_le_limbs(b), Fe::from_le_limbs(c));
+    let mut wide = [0u64; 8];
+    wide[..4].copy_from_slice(&c);
+    for i in 0..4 {
+        let mut carry = 0u128;
+        for j in 0..4 {
+            let cur = wide[i + j] as u128 + a[i] as u128 * b[j] as u128 + carry;
+            wide[i + j] = cur as u64;
+            carry = cur >> 64;
+        }
+        for limb in wide[i + 4..].iter_mut() {
+            let cur = *limb as u128 + carry;
+            *limb = cur as u64;
+            carry = cur >> 64;
+        }
+    }
+    let bytes: Vec<u8> = wide.iter().flat_map(|limb| limb.to_le_bytes()).collect();
+    reduce_scalar(&bytes)
+}
+
+/// Whether a little-endian scalar is already reduced below L
+pub fn is_canonical_scalar(bytes: &[u8; 32]) -> bool {
+    !at_least_l(&Fe::from_le_limbs(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_arithmetic() {
+        let a = Fe::from_u64(12345);
+        assert_eq!(a.mul(&a.invert()), ONE);
+        assert_eq!(a.sub(&a), ZERO);
+        assert_eq!(ZERO.sub(&ONE).add(&ONE), ZERO);
+
+        // The precomputed constants
+        assert_eq!(D.mul(&Fe::from_u64(121_666)), Fe::from_u64(121_665).neg());
+        assert_eq!(D.add(&D), D2);
+        assert_eq!(SQRT_M1.square(), ONE.neg());
+    }
+
+    #[test]
+    fn test_point_arithmetic() {
+        let base = Point::base();
+        assert_eq!(Point::decompress(&base.compress()), Some(base));
+        assert_eq!(base.add(&Point::IDENTITY), base);
+        assert_eq!(base.mul(&reduce_scalar(&[3])), base.add(&base).add(&base));
+
+        // L B is the identity, so scalars act modulo L
+        let mut l = [0u8; 32];
+        for (chunk, limb) in l.chunks_mut(8).zip(L) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        assert_eq!(base.mul(&l), Point::IDENTITY);
+        assert_eq!(reduce_scalar(&l), [0; 32]);
+        assert!(!is_canonical_scalar(&l));
+
+        // 2 * 3 + 4 = 10
+        let small = |n: u8| reduce_scalar(&[n]);
+        assert_eq!(scalar_mul_add(&small(2), &small(3), &small(4)), small(10));
+        // (L - 1)^2 = 1 (mod L)
+        let mut l_minus_one = l;
+        l_minus_one[0] -= 1;
+        assert_eq!(scalar_mul_add(&l_minus_one, &l_minus_one, &[0; 32]), small(1));
+    }
+
+    #[test]
+    fn test_is_on_curve() {
+        // The ed25519 base point (y = 4/5)
+        let mut base = [0x66u8; 32];
+        base[0] = 0x58;
+        assert!(is_on_curve(&base));
+
+        // The identity point (y = 1)
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        assert!(is_on_curve(&identity));
+
+        // y = 2 gives x^2 = 3 / (4d + 1), which is not a square
+        let mut off = [0u8; 32];
+        off[0] = 2;
+        assert!(!is_on_curve(&off));
+    }
+}
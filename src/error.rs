@@ -21,6 +21,81 @@ pub enum AppError {
     InvalidAmount(u64),         // Invalid amount (0 or negative)
     InvalidAccountName(String), // Invalid account name
     TransferToSelf(String),     // Transfer to self
+    InvalidDelegate(String),    // Delegate is not approved for the account (contains delegate name)
+    InsufficientAllowance {
+        // Delegate allowance is not sufficient
+        available: u64,
+        required: u64,
+    },
+    AccountFrozen(String),      // Account is frozen and cannot be changed
+    AccountNotFrozen(String),   // Account is not frozen, so it cannot be thawed
+    Unauthorized(String),       // Caller lacks the authority for the action
+    InvalidThreshold {
+        // Multisig threshold M is not between 1 and the N signers
+        threshold: u8,
+        signers: usize,
+    },
+    NotEnoughSignatures {
+        // Fewer than M of the multisig keys signed
+        required: usize,
+        provided: usize,
+    },
+    MultisigNotFound(String),   // Multisig doesn't exist
+    InvalidPubkey(String),      // String is not a base58 32-byte key
+    InvalidSeeds(String),       // PDA seeds rejected (contains the reason)
+    InvalidProgramAddress(String), // Address was not derived from the given seeds
+    EscrowNotFound(String),     // Escrow doesn't exist
+    EscrowNotActive(String),    // Escrow was already released or cancelled
+    EscrowExpired {
+        // Escrow can no longer be released
+        name: String,
+        expiry_slot: u64,
+    },
+    EscrowNotExpired {
+        // Depositor tried to reclaim funds before expiry
+        name: String,
+        expiry_slot: u64,
+    },
+    VestingNotFound(String),    // No vesting schedule for the beneficiary
+    InsufficientVested {
+        // Claim exceeds the unlocked, unclaimed amount
+        available: u64,
+        required: u64,
+    },
+    InvalidVestingSchedule(String), // Schedule parameters rejected (contains the reason)
+    StakeNotFound(String),      // Stake account doesn't exist
+    InvalidStakeState(String),  // Stake account cannot do this in its current state
+    ScheduleNotFound(u64),      // Standing order doesn't exist (contains schedule ID)
+    InvalidSchedule(String),    // Standing order parameters rejected (contains the reason)
+    MintNotFound(String),       // Token mint doesn't exist
+    PoolNotFound(String),       // Liquidity pool doesn't exist
+    InvalidPool(String),        // Pool cannot do this (contains the reason)
+    SlippageExceeded {
+        // Trade would return less than the caller's minimum
+        minimum: u64,
+        actual: u64,
+    },
+    NameNotFound(String),       // Name was never registered
+    NameTaken(String),          // Name is registered to someone else
+    NameExpired(String),        // Name registration has lapsed
+    ProgramNotFound(String),    // No program registered under this ID
+    ProgramAlreadyRegistered(String),
+    InvalidInstructionData(String), // Program could not decode its data
+    CallDepthExceeded(usize),       // Program calls nested deeper than the limit
+    PrivilegeEscalation(String),    // Callee asked for a privilege the caller lacks
+    AccountNotWritable(String),     // Account was passed read-only
+    InvalidBytecode(String),        // Program failed verification
+    ComputeBudgetExceeded(u64),     // Program ran more instructions than allowed
+    VmFault(String),                // Program hit a runtime fault
+    ProgramFailed(u64),             // Program exited with a non-zero code
+    ProgramImmutable(String),       // Program has no upgrade authority
+    BlockhashNotFound(String),      // Transaction's blockhash is unknown or too old
+    AlreadyProcessed(String),       // Transaction was already processed or queued
+    NonceNotFound(String),          // No nonce account under this name
+    MempoolFull(usize),             // Pool is at capacity and the fee is too low to evict
+    InvalidSignature(String),       // Signature does not verify for this key
+    MissingSignature(String),       // Keypair account did not sign the transaction
+    RequestFailed(String),          // RPC call could not be made or was refused
 }
 
 // TODO 2.2: Implement Display trait for AppError
@@ -53,6 +128,173 @@ impl fmt::Display for AppError {
             AppError::TransferToSelf(name) => {
                 write!(f, "Cannot transfer to self. Account: {}", name)
             }
+            AppError::InvalidDelegate(name) => {
+                write!(f, "Invalid delegate: {}", name)
+            }
+            AppError::InsufficientAllowance {
+                available,
+                required,
+            } => {
+                write!(
+                    f,
+                    "Insufficient allowance. Available: {}, Required: {}",
+                    available, required
+                )
+            }
+            AppError::AccountFrozen(name) => {
+                write!(f, "Account is frozen: {}", name)
+            }
+            AppError::AccountNotFrozen(name) => {
+                write!(f, "Account is not frozen: {}", name)
+            }
+            AppError::Unauthorized(name) => {
+                write!(f, "Unauthorized: {}", name)
+            }
+            AppError::InvalidThreshold { threshold, signers } => {
+                write!(
+                    f,
+                    "Invalid multisig threshold {} for {} signers",
+                    threshold, signers
+                )
+            }
+            AppError::NotEnoughSignatures { required, provided } => {
+                write!(
+                    f,
+                    "Not enough signatures. Required: {}, Provided: {}",
+                    required, provided
+                )
+            }
+            AppError::MultisigNotFound(name) => {
+                write!(f, "Multisig not found: {}", name)
+            }
+            AppError::InvalidPubkey(key) => {
+                write!(f, "Invalid public key: {}", key)
+            }
+            AppError::InvalidSeeds(reason) => {
+                write!(f, "Invalid seeds: {}", reason)
+            }
+            AppError::InvalidProgramAddress(address) => {
+                write!(f, "Invalid program address: {}", address)
+            }
+            AppError::EscrowNotFound(name) => {
+                write!(f, "Escrow not found: {}", name)
+            }
+            AppError::EscrowNotActive(name) => {
+                write!(f, "Escrow is no longer active: {}", name)
+            }
+            AppError::EscrowExpired { name, expiry_slot } => {
+                write!(f, "Escrow {} expired at slot {}", name, expiry_slot)
+            }
+            AppError::EscrowNotExpired { name, expiry_slot } => {
+                write!(f, "Escrow {} does not expire until slot {}", name, expiry_slot)
+            }
+            AppError::VestingNotFound(name) => {
+                write!(f, "Vesting schedule not found: {}", name)
+            }
+            AppError::InsufficientVested {
+                available,
+                required,
+            } => {
+                write!(
+                    f,
+                    "Insufficient vested amount. Available: {}, Required: {}",
+                    available, required
+                )
+            }
+            AppError::InvalidVestingSchedule(reason) => {
+                write!(f, "Invalid vesting schedule: {}", reason)
+            }
+            AppError::StakeNotFound(name) => {
+                write!(f, "Stake account not found: {}", name)
+            }
+            AppError::InvalidStakeState(reason) => {
+                write!(f, "Invalid stake state: {}", reason)
+            }
+            AppError::ScheduleNotFound(id) => {
+                write!(f, "Scheduled payment not found: {}", id)
+            }
+            AppError::InvalidSchedule(reason) => {
+                write!(f, "Invalid schedule: {}", reason)
+            }
+            AppError::MintNotFound(symbol) => {
+                write!(f, "Mint not found: {}", symbol)
+            }
+            AppError::PoolNotFound(name) => {
+                write!(f, "Pool not found: {}", name)
+            }
+            AppError::InvalidPool(reason) => {
+                write!(f, "Invalid pool operation: {}", reason)
+            }
+            AppError::SlippageExceeded { minimum, actual } => {
+                write!(
+                    f,
+                    "Slippage exceeded. Minimum: {}, Actual: {}",
+                    minimum, actual
+                )
+            }
+            AppError::NameNotFound(name) => {
+                write!(f, "Name not found: {}", name)
+            }
+            AppError::NameTaken(name) => {
+                write!(f, "Name already taken: {}", name)
+            }
+            AppError::NameExpired(name) => {
+                write!(f, "Name registration expired: {}", name)
+            }
+            AppError::ProgramNotFound(id) => {
+                write!(f, "Program not found: {}", id)
+            }
+            AppError::ProgramAlreadyRegistered(id) => {
+                write!(f, "Program already registered: {}", id)
+            }
+            AppError::InvalidInstructionData(reason) => {
+                write!(f, "Invalid instruction data: {}", reason)
+            }
+            AppError::CallDepthExceeded(max) => {
+                write!(f, "Program calls nested deeper than {}", max)
+            }
+            AppError::PrivilegeEscalation(reason) => {
+                write!(f, "Privilege escalation: {}", reason)
+            }
+            AppError::AccountNotWritable(name) => {
+                write!(f, "Account is not writable: {}", name)
+            }
+            AppError::InvalidBytecode(reason) => {
+                write!(f, "Invalid bytecode: {}", reason)
+            }
+            AppError::ComputeBudgetExceeded(budget) => {
+                write!(f, "Compute budget of {} units exceeded", budget)
+            }
+            AppError::VmFault(reason) => {
+                write!(f, "Program fault: {}", reason)
+            }
+            AppError::ProgramFailed(code) => {
+                write!(f, "Program failed with exit code {}", code)
+            }
+            AppError::ProgramImmutable(id) => {
+                write!(f, "Program is immutable: {}", id)
+            }
+            AppError::BlockhashNotFound(blockhash) => {
+                write!(f, "Blockhash not found or expired: {}", blockhash)
+            }
+            AppError::AlreadyProcessed(signature) => {
+                write!(f, "Transaction already processed: {}", signature)
+            }
+            AppError::NonceNotFound(name) => {
+                write!(f, "Nonce account not found: {}", name)
+            }
+            AppError::MempoolFull(capacity) => {
+                write!(f, "Mempool is full ({} transactions)", capacity)
+            }
+            AppError::InvalidSignature(key) => {
+                write!(f, "Invalid signature for {}", key)
+            }
+            AppError::MissingSignature(key) => {
+                write!(f, "Missing signature for {}", key)
+            }
+            AppError::RequestFailed(reason) => {
+                write!(f, "RPC request failed: {}", reason)
+            }
         }
     }
 }
@@ -61,11 +303,26 @@ impl fmt::Display for AppError {
 // Error trait is the standard trait for all error types in Rust
 impl error::Error for AppError {}
 
+/// Shortest allowed account name
+pub const MIN_NAME_LEN: usize = 2;
+/// Longest allowed account name (fits a base58 address)
+pub const MAX_NAME_LEN: usize = 64;
+/// Names that cannot be used for accounts (compared case-insensitively)
+pub const RESERVED_NAMES: &[&str] = &["system", "root", "admin", "null", "none", "self"];
+
 // TODO 2.4: Implement helper methods for AppError
 impl AppError {
-    /// Check if account name is valid
+    /// Check if account name is valid: 2-64 ASCII letters, digits, '_' or '-',
+    /// and not one of the reserved words
     pub fn validate_account_name(name: &str) -> Result<()> {
-        if name.trim().len() < 2 {
+        let valid_chars = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        let reserved = RESERVED_NAMES
+            .iter()
+            .any(|word| word.eq_ignore_ascii_case(name));
+
+        if name.len() < MIN_NAME_LEN || name.len() > MAX_NAME_LEN || !valid_chars || reserved {
             return Err(AppError::InvalidAccountName(name.to_string()));
         }
         Ok(())
@@ -97,6 +354,53 @@ impl AppError {
             AppError::InvalidAmount(_) => "VAL_001",
             AppError::InvalidAccountName(_) => "VAL_002",
             AppError::TransferToSelf(_) => "TX_001",
+            AppError::InvalidDelegate(_) => "DLG_001",
+            AppError::InsufficientAllowance { .. } => "DLG_002",
+            AppError::AccountFrozen(_) => "ACC_003",
+            AppError::AccountNotFrozen(_) => "ACC_004",
+            AppError::Unauthorized(_) => "AUTH_001",
+            AppError::InvalidThreshold { .. } => "MSIG_001",
+            AppError::NotEnoughSignatures { .. } => "MSIG_002",
+            AppError::MultisigNotFound(_) => "MSIG_003",
+            AppError::InvalidPubkey(_) => "KEY_001",
+            AppError::InvalidSeeds(_) => "PDA_001",
+            AppError::InvalidProgramAddress(_) => "PDA_002",
+            AppError::EscrowNotFound(_) => "ESC_001",
+            AppError::EscrowNotActive(_) => "ESC_002",
+            AppError::EscrowExpired { .. } => "ESC_003",
+            AppError::EscrowNotExpired { .. } => "ESC_004",
+            AppError::VestingNotFound(_) => "VEST_001",
+            AppError::InsufficientVested { .. } => "VEST_002",
+            AppError::InvalidVestingSchedule(_) => "VEST_003",
+            AppError::StakeNotFound(_) => "STK_001",
+            AppError::InvalidStakeState(_) => "STK_002",
+            AppError::ScheduleNotFound(_) => "SCH_001",
+            AppError::InvalidSchedule(_) => "SCH_002",
+            AppError::MintNotFound(_) => "TOK_001",
+            AppError::PoolNotFound(_) => "AMM_001",
+            AppError::InvalidPool(_) => "AMM_002",
+            AppError::SlippageExceeded { .. } => "AMM_003",
+            AppError::NameNotFound(_) => "NAME_001",
+            AppError::NameTaken(_) => "NAME_002",
+            AppError::NameExpired(_) => "NAME_003",
+            AppError::ProgramNotFound(_) => "PROG_001",
+            AppError::ProgramAlreadyRegistered(_) => "PROG_002",
+            AppError::InvalidInstructionData(_) => "PROG_003",
+            AppError::CallDepthExceeded(_) => "CPI_001",
+            AppError::PrivilegeEscalation(_) => "CPI_002",
+            AppError::AccountNotWritable(_) => "ACC_005",
+            AppError::InvalidBytecode(_) => "VM_001",
+            AppError::ComputeBudgetExceeded(_) => "VM_002",
+            AppError::VmFault(_) => "VM_003",
+            AppError::ProgramFailed(_) => "VM_004",
+            AppError::ProgramImmutable(_) => "LDR_001",
+            AppError::BlockhashNotFound(_) => "TX_002",
+            AppError::AlreadyProcessed(_) => "TX_003",
+            AppError::NonceNotFound(_) => "NONCE_001",
+            AppError::MempoolFull(_) => "MEM_001",
+            AppError::InvalidSignature(_) => "SIG_001",
+            AppError::MissingSignature(_) => "SIG_002",
+            AppError::RequestFailed(_) => "RPC_001",
         }
     }
 
@@ -105,11 +409,19 @@ impl AppError {
         // TODO 2.6: Some errors can be recovered (like InsufficientBalance)
         // Some cannot (like InvalidAccountName)
         // Return true/false based on error type
-        match self {
-            AppError::InsufficientBalance { .. } => true,
-            AppError::AccountNotFound(_) => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            AppError::InsufficientBalance { .. }
+                | AppError::AccountNotFound(_)
+                | AppError::InsufficientAllowance { .. }
+                | AppError::NotEnoughSignatures { .. }
+                | AppError::EscrowNotExpired { .. }
+                | AppError::InsufficientVested { .. }
+                | AppError::SlippageExceeded { .. }
+                | AppError::BlockhashNotFound(_)
+                | AppError::MempoolFull(_)
+                | AppError::RequestFailed(_)
+        )
     }
 }
 
@@ -156,6 +468,14 @@ mod tests {
         // Invalid case: too short
         let result = AppError::validate_account_name("A");
         assert!(matches!(result, Err(AppError::InvalidAccountName(_))));
+
+        // Charset, length and reserved words
+        assert!(AppError::validate_account_name("alice_01-x").is_ok());
+        assert!(AppError::validate_account_name("Al ice").is_err());
+        assert!(AppError::validate_account_name("alice.sol").is_err());
+        assert!(AppError::validate_account_name(&"a".repeat(MAX_NAME_LEN)).is_ok());
+        assert!(AppError::validate_account_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+        assert!(AppError::validate_account_name("System").is_err());
     }
 
     #[test]
@@ -168,6 +488,53 @@ mod tests {
         assert_eq!(AppError::InvalidAmount(0).code(), "VAL_001");
         assert_eq!(AppError::InvalidAccountName("".to_string()).code(), "VAL_002");
         assert_eq!(AppError::TransferToSelf("".to_string()).code(), "TX_001");
+        assert_eq!(AppError::InvalidDelegate("".to_string()).code(), "DLG_001");
+        assert_eq!(AppError::InsufficientAllowance { available: 0, required: 0 }.code(), "DLG_002");
+        assert_eq!(AppError::AccountFrozen("".to_string()).code(), "ACC_003");
+        assert_eq!(AppError::AccountNotFrozen("".to_string()).code(), "ACC_004");
+        assert_eq!(AppError::Unauthorized("".to_string()).code(), "AUTH_001");
+        assert_eq!(AppError::InvalidThreshold { threshold: 0, signers: 0 }.code(), "MSIG_001");
+        assert_eq!(AppError::NotEnoughSignatures { required: 0, provided: 0 }.code(), "MSIG_002");
+        assert_eq!(AppError::MultisigNotFound("".to_string()).code(), "MSIG_003");
+        assert_eq!(AppError::InvalidPubkey("".to_string()).code(), "KEY_001");
+        assert_eq!(AppError::InvalidSeeds("".to_string()).code(), "PDA_001");
+        assert_eq!(AppError::InvalidProgramAddress("".to_string()).code(), "PDA_002");
+        assert_eq!(AppError::EscrowNotFound("".to_string()).code(), "ESC_001");
+        assert_eq!(AppError::EscrowNotActive("".to_string()).code(), "ESC_002");
+        assert_eq!(AppError::EscrowExpired { name: "".to_string(), expiry_slot: 0 }.code(), "ESC_003");
+        assert_eq!(AppError::EscrowNotExpired { name: "".to_string(), expiry_slot: 0 }.code(), "ESC_004");
+        assert_eq!(AppError::VestingNotFound("".to_string()).code(), "VEST_001");
+        assert_eq!(AppError::InsufficientVested { available: 0, required: 0 }.code(), "VEST_002");
+        assert_eq!(AppError::InvalidVestingSchedule("".to_string()).code(), "VEST_003");
+        assert_eq!(AppError::StakeNotFound("".to_string()).code(), "STK_001");
+        assert_eq!(AppError::InvalidStakeState("".to_string()).code(), "STK_002");
+        assert_eq!(AppError::ScheduleNotFound(0).code(), "SCH_001");
+        assert_eq!(AppError::InvalidSchedule("".to_string()).code(), "SCH_002");
+        assert_eq!(AppError::MintNotFound("".to_string()).code(), "TOK_001");
+        assert_eq!(AppError::PoolNotFound("".to_string()).code(), "AMM_001");
+        assert_eq!(AppError::InvalidPool("".to_string()).code(), "AMM_002");
+        assert_eq!(AppError::SlippageExceeded { minimum: 0, actual: 0 }.code(), "AMM_003");
+        assert_eq!(AppError::NameNotFound("".to_string()).code(), "NAME_001");
+        assert_eq!(AppError::NameTaken("".to_string()).code(), "NAME_002");
+        assert_eq!(AppError::NameExpired("".to_string()).code(), "NAME_003");
+        assert_eq!(AppError::ProgramNotFound("".to_string()).code(), "PROG_001");
+        assert_eq!(AppError::ProgramAlreadyRegistered("".to_string()).code(), "PROG_002");
+        assert_eq!(AppError::InvalidInstructionData("".to_string()).code(), "PROG_003");
+        assert_eq!(AppError::CallDepthExceeded(0).code(), "CPI_001");
+        assert_eq!(AppError::PrivilegeEscalation("".to_string()).code(), "CPI_002");
+        assert_eq!(AppError::AccountNotWritable("".to_string()).code(), "ACC_005");
+        assert_eq!(AppError::InvalidBytecode("".to_string()).code(), "VM_001");
+        assert_eq!(AppError::ComputeBudgetExceeded(0).code(), "VM_002");
+        assert_eq!(AppError::VmFault("".to_string()).code(), "VM_003");
+        assert_eq!(AppError::ProgramFailed(0).code(), "VM_004");
+        assert_eq!(AppError::ProgramImmutable("".to_string()).code(), "LDR_001");
+        assert_eq!(AppError::BlockhashNotFound("".to_string()).code(), "TX_002");
+        assert_eq!(AppError::AlreadyProcessed("".to_string()).code(), "TX_003");
+        assert_eq!(AppError::NonceNotFound("".to_string()).code(), "NONCE_001");
+        assert_eq!(AppError::MempoolFull(0).code(), "MEM_001");
+        assert_eq!(AppError::InvalidSignature("".to_string()).code(), "SIG_001");
+        assert_eq!(AppError::MissingSignature("".to_string()).code(), "SIG_002");
+        assert_eq!(AppError::RequestFailed("".to_string()).code(), "RPC_001");
     }
 
     #[test]
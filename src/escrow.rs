@@ -0,0 +1,154 @@
+// ============================================
+// ESCROW
+// ============================================
+// Funds locked in a program derived vault until an arbiter releases them to
+// the recipient, or they are cancelled / expire back to the depositor
+
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::pubkey::Pubkey;
+
+/// Program that owns every escrow vault
+pub const ESCROW_PROGRAM_ID: Pubkey = Pubkey::new(*b"EscrowProgram1111111111111111111");
+
+/// Lifecycle of an escrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscrowStatus {
+    Active,
+    Released,
+    Cancelled,
+}
+
+/// Struct representing one escrow agreement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Escrow {
+    pub name: String,
+    pub depositor: String,
+    pub recipient: String,
+    pub arbiter: String,
+    pub amount: u64,
+    pub expiry_slot: u64,
+    pub vault: Pubkey, // PDA of ESCROW_PROGRAM_ID holding the locked funds
+    pub status: EscrowStatus,
+}
+
+impl Escrow {
+    /// Create a new active escrow, deriving its vault address
+    pub fn new(
+        name: String,
+        depositor: String,
+        recipient: String,
+        arbiter: String,
+        amount: u64,
+        expiry_slot: u64,
+    ) -> Result<Self> {
+        AppError::validate_account_name(&name)?;
+        AppError::validate_amount(amount)?;
+        if depositor == recipient {
+            return Err(AppError::TransferToSelf(depositor));
+        }
+
+        let (vault, _) = Pubkey::find_program_address(&Escrow::vault_seeds(&hash::sha256(name.as_bytes())), &ESCROW_PROGRAM_ID)?;
+        Ok(Self {
+            name,
+            depositor,
+            recipient,
+            arbiter,
+            amount,
+            expiry_slot,
+            vault,
+            status: EscrowStatus::Active,
+        })
+    }
+
+    /// Seeds of the vault PDA (the bump is found by `find_program_address`),
+    /// from the hash of the escrow name so that every valid name fits
+    pub fn vault_seeds(name_hash: &Hash) -> [&[u8]; 2] {
+        [b"escrow", name_hash]
+    }
+
+    /// Check whether the escrow has passed its expiry slot
+    pub fn is_expired(&self, slot: u64) -> bool {
+        slot >= self.expiry_slot
+    }
+
+    fn ensure_active(&self) -> Result<()> {
+        if self.status != EscrowStatus::Active {
+            return Err(AppError::EscrowNotActive(self.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Release pays the recipient. The arbiter or the depositor may release,
+    /// but only before expiry.
+    pub fn check_release(&self, signer: &str, slot: u64) -> Result<()> {
+        self.ensure_active()?;
+        if signer != self.arbiter && signer != self.depositor {
+            return Err(AppError::Unauthorized(signer.to_string()));
+        }
+        if self.is_expired(slot) {
+            return Err(AppError::EscrowExpired {
+                name: self.name.clone(),
+                expiry_slot: self.expiry_slot,
+            });
+        }
+        Ok(())
+    }
+
+    /// Cancel refunds the depositor. The arbiter or the recipient may cancel
+    /// at any time; the depositor only once the escrow has expired.
+    pub fn check_cancel(&self, signer: &str, slot: u64) -> Result<()> {
+        self.ensure_active()?;
+        if signer == self.arbiter || signer == self.recipient {
+            return Ok(());
+        }
+        if signer != self.depositor {
+            return Err(AppError::Unauthorized(signer.to_string()));
+        }
+        if !self.is_expired(slot) {
+            return Err(AppError::EscrowNotExpired {
+                name: self.name.clone(),
+                expiry_slot: self.expiry_slot,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escrow() -> Escrow {
+        Escrow::new(
+            "Order42".into(),
+            "Buyer".into(),
+            "Seller".into(),
+            "Market".into(),
+            500,
+            100,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_release_rules() {
+        let escrow = escrow();
+        assert!(escrow.check_release("Market", 10).is_ok());
+        assert!(escrow.check_release("Buyer", 10).is_ok());
+        assert!(matches!(escrow.check_release("Seller", 10), Err(AppError::Unauthorized(_))));
+        assert!(matches!(escrow.check_release("Market", 100), Err(AppError::EscrowExpired { .. })));
+    }
+
+    #[test]
+    fn test_cancel_rules() {
+        let mut escrow = escrow();
+        assert!(escrow.check_cancel("Market", 10).is_ok());
+        assert!(escrow.check_cancel("Seller", 10).is_ok());
+        assert!(matches!(escrow.check_cancel("Buyer", 99), Err(AppError::EscrowNotExpired { .. })));
+        assert!(escrow.check_cancel("Buyer", 100).is_ok());
+
+        escrow.status = EscrowStatus::Released;
+        assert!(matches!(escrow.check_cancel("Market", 10), Err(AppError::EscrowNotActive(_))));
+    }
+}
@@ -0,0 +1,324 @@
+// ============================================
+// ACCOUNT HISTORY
+// ============================================
+// Every change to an account's balance, with the slot and instruction that
+// made it, so a balance can be explained without replaying the ledger. The
+// state notes each account's balance before its first write; after each
+// instruction the accounts whose balance moved get one entry apiece.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::state::Account;
+
+/// Cause of changes made by calling `AppState` methods outside any instruction
+pub const DIRECT: &str = "Direct";
+/// Cause of epoch rewards paid into stake accounts
+pub const EPOCH_REWARD: &str = "EpochReward";
+/// Cause of standing orders executed as the clock advances
+pub const SCHEDULED_PAYMENT: &str = "ScheduledPayment";
+
+/// Entries per shared chunk of a timeline
+const CHUNK: usize = 256;
+
+/// One change to an account's balance
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceChange {
+    pub slot: u64,
+    pub timestamp: u64, // Wall clock (seconds) when it was recorded
+    pub previous: u64,  // Balance before the change
+    pub balance: u64,   // Balance after the change
+    pub counterparty: Option<String>, // The only account that moved the other way, if there was one
+    pub instruction: &'static str,    // Instruction name, or DIRECT, EPOCH_REWARD, SCHEDULED_PAYMENT
+}
+
+impl BalanceChange {
+    /// Signed change to the balance
+    pub fn delta(&self) -> i128 {
+        i128::from(self.balance) - i128::from(self.previous)
+    }
+}
+
+/// One page of an account's history, oldest change first
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryPage {
+    pub changes: Vec<BalanceChange>,
+    pub next: Option<usize>, // Cursor for the following page, if there is one
+}
+
+/// Changes of one account in order. Full chunks are shared between copies of
+/// the state, so cloning it for every transaction does not copy the history.
+#[derive(Debug, Clone, Default)]
+struct Timeline {
+    sealed: Vec<Arc<[BalanceChange]>>,
+    tail: Vec<BalanceChange>,
+}
+
+impl Timeline {
+    fn len(&self) -> usize {
+        self.sealed.len() * CHUNK + self.tail.len()
+    }
+
+    fn get(&self, index: usize) -> &BalanceChange {
+        match self.sealed.get(index / CHUNK) {
+            Some(chunk) => &chunk[index % CHUNK],
+            None => &self.tail[index - self.sealed.len() * CHUNK],
+        }
+    }
+
+    /// Drop every change after the first `len`
+    fn truncate(&mut self, len: usize) {
+        // Changes past `len` in a sealed chunk mean reopening it as the tail
+        while self.sealed.len() * CHUNK > len {
+            let chunk = self.sealed.pop().expect("sealed chunk");
+            self.tail = chunk.to_vec();
+        }
+        self.tail.truncate(len - self.sealed.len() * CHUNK);
+    }
+
+    fn push(&mut self, change: BalanceChange) {
+        self.tail.push(change);
+        if self.tail.len() == CHUNK {
+            self.sealed.push(std::mem::take(&mut self.tail).into());
+        }
+    }
+
+    /// Number of leading changes made in slots before `bound` starts
+    fn position(&self, bound: Bound<&u64>) -> usize {
+        let before = |change: &BalanceChange| match bound {
+            Bound::Included(slot) => change.slot < *slot,
+            Bound::Excluded(slot) => change.slot <= *slot,
+            Bound::Unbounded => false,
+        };
+        // Slots never decrease along a timeline
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if before(self.get(middle)) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    /// Index range of the changes made in `slots`
+    fn span(&self, slots: &impl RangeBounds<u64>) -> (usize, usize) {
+        let end = match slots.end_bound() {
+            Bound::Included(slot) => self.position(Bound::Excluded(slot)),
+            Bound::Excluded(slot) => self.position(Bound::Included(slot)),
+            Bound::Unbounded => self.len(),
+        };
+        (self.position(slots.start_bound()), end)
+    }
+}
+
+/// What the history held when a checkpoint was taken
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    pending: BTreeMap<String, u64>,
+    lengths: HashMap<String, usize>, // Length of each timeline before its first change since
+}
+
+/// Balance changes of every account ever written, deleted ones included
+#[derive(Debug, Clone, Default)]
+pub struct AccountHistory {
+    timelines: HashMap<String, Timeline>,
+    pending: BTreeMap<String, u64>, // Balance before the first write since the last record
+    checkpoints: Vec<Checkpoint>,   // Open checkpoints, innermost last
+}
+
+impl AccountHistory {
+    /// Note that account `name`, holding `balance`, is about to be written
+    pub fn touch(&mut self, name: &str, balance: u64) {
+        if !self.pending.contains_key(name) {
+            self.pending.insert(name.to_string(), balance);
+        }
+    }
+
+    /// Record a change for every written account whose balance moved,
+    /// attributed to `instruction`
+    pub fn record(&mut self, accounts: &HashMap<String, Account>, slot: u64, instruction: &'static str) {
+        let moved: Vec<(String, u64, u64)> = std::mem::take(&mut self.pending)
+            .into_iter()
+            .filter_map(|(name, previous)| {
+                let balance = accounts.get(&name).map_or(0, |account| account.balance);
+                (balance != previous).then_some((name, previous, balance))
+            })
+            .collect();
+        if moved.is_empty() {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        for (name, previous, balance) in &moved {
+            let credited = balance > previous;
+            let mut others = moved.iter().filter(|(_, before, after)| (after > before) != credited);
+            let counterparty = match (others.next(), others.next()) {
+                (Some((other, ..)), None) => Some(other.clone()),
+                _ => None,
+            };
+            let timeline = self.timelines.entry(name.clone()).or_default();
+            if let Some(checkpoint) = self.checkpoints.last_mut() {
+                checkpoint.lengths.entry(name.clone()).or_insert(timeline.len());
+            }
+            timeline.push(BalanceChange {
+                slot,
+                timestamp,
+                previous: *previous,
+                balance: *balance,
+                counterparty,
+                instruction,
+            });
+        }
+    }
+
+    /// Start keeping what the history holds, so `rollback` can return to it.
+    /// Checkpoints nest; each is closed by `commit` or `rollback`.
+    pub fn checkpoint(&mut self) {
+        self.checkpoints.push(Checkpoint {
+            pending: self.pending.clone(),
+            lengths: HashMap::new(),
+        });
+    }
+
+    /// Keep the changes recorded since the innermost checkpoint
+    pub fn commit(&mut self) {
+        let Some(checkpoint) = self.checkpoints.pop() else {
+            return;
+        };
+        if let Some(outer) = self.checkpoints.last_mut() {
+            for (name, len) in checkpoint.lengths {
+                outer.lengths.entry(name).or_insert(len);
+            }
+        }
+    }
+
+    /// Forget the changes recorded and writes noted since the innermost
+    /// checkpoint
+    pub fn rollback(&mut self) {
+        let Some(checkpoint) = self.checkpoints.pop() else {
+            return;
+        };
+        for (name, len) in checkpoint.lengths {
+            if len == 0 {
+                self.timelines.remove(&name);
+            } else if let Some(timeline) = self.timelines.get_mut(&name) {
+                timeline.truncate(len);
+            }
+        }
+        self.pending = checkpoint.pending;
+    }
+
+    /// Up to `limit` of `name`'s changes in `slots`, starting at `cursor` (0,
+    /// or the `next` of the previous page)
+    pub fn page(&self, name: &str, slots: impl RangeBounds<u64>, cursor: usize, limit: usize) -> HistoryPage {
+        let Some(timeline) = self.timelines.get(name) else {
+            return HistoryPage { changes: Vec::new(), next: None };
+        };
+        let (start, end) = timeline.span(&slots);
+        let start = start.max(cursor);
+        let stop = end.min(start.saturating_add(limit));
+        HistoryPage {
+            changes: (start..stop).map(|index| timeline.get(index).clone()).collect(),
+            next: (stop < end).then_some(stop),
+        }
+    }
+
+    /// `name`'s balance at the end of `slot`; `None` if it never changed
+    pub fn balance_at(&self, name: &str, slot: u64) -> Option<u64> {
+        let timeline = self.timelines.get(name)?;
+        match timeline.position(Bound::Excluded(&slot)) {
+            0 => Some(timeline.get(0).previous),
+            after => Some(timeline.get(after - 1).balance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts(balances: &[(&str, u64)]) -> HashMap<String, Account> {
+        balances
+            .iter()
+            .map(|(name, balance)| (name.to_string(), Account::new(name.to_string(), *balance)))
+            .collect()
+    }
+
+    #[test]
+    fn test_record_and_page() {
+        let mut history = AccountHistory::default();
+        history.touch("Alice", 0);
+        history.record(&accounts(&[("Alice", 1000)]), 1, DIRECT);
+
+        // Alice pays Bob 1 in every slot from 2, across several chunks
+        for slot in 2..(2 + CHUNK as u64 * 2) {
+            let paid = slot - 1;
+            history.touch("Alice", 1000 - paid + 1);
+            history.touch("Bob", paid - 1);
+            history.touch("Carol", 1);
+            history.record(&accounts(&[("Alice", 1000 - paid), ("Bob", paid), ("Carol", 1)]), slot, "Transfer");
+        }
+
+        let first = history.page("Alice", .., 0, 2);
+        assert_eq!(first.changes[0].delta(), 1000);
+        assert_eq!(first.changes[0].counterparty, None);
+        assert_eq!(first.changes[1].delta(), -1);
+        assert_eq!(first.changes[1].counterparty.as_deref(), Some("Bob"));
+        assert_eq!(first.next, Some(2));
+        // Carol's balance never moved
+        assert!(history.page("Carol", .., 0, 10).changes.is_empty());
+
+        // Paging through a slot range visits every change in it once
+        let mut cursor = 0;
+        let mut slots = Vec::new();
+        loop {
+            let page = history.page("Bob", 300..=400, cursor, 30);
+            slots.extend(page.changes.iter().map(|change| change.slot));
+            match page.next {
+                Some(next) => cursor = next,
+                None => break,
+            }
+        }
+        assert_eq!(slots, (300..=400).collect::<Vec<_>>());
+
+        assert_eq!(history.balance_at("Alice", 0), Some(0));
+        assert_eq!(history.balance_at("Alice", 1), Some(1000));
+        assert_eq!(history.balance_at("Alice", 11), Some(990));
+        assert_eq!(history.balance_at("Bob", 1000), Some(CHUNK as u64 * 2));
+        assert_eq!(history.balance_at("Nobody", 5), None);
+    }
+
+    #[test]
+    fn test_rollback_drops_changes_across_chunks() {
+        let mut history = AccountHistory::default();
+        for slot in 0..(CHUNK as u64 - 1) {
+            history.touch("Alice", slot);
+            history.record(&accounts(&[("Alice", slot + 1)]), slot, "Transfer");
+        }
+
+        history.checkpoint();
+        for slot in (CHUNK as u64 - 1)..(CHUNK as u64 + 2) {
+            history.touch("Alice", slot);
+            history.record(&accounts(&[("Alice", slot + 1)]), slot, "Transfer");
+        }
+        history.touch("Bob", 0);
+        history.record(&accounts(&[("Bob", 5)]), 300, "Transfer");
+        history.touch("Alice", CHUNK as u64 + 2);
+        history.rollback();
+
+        let timeline = &history.timelines["Alice"];
+        assert_eq!(timeline.len(), CHUNK - 1);
+        assert!(timeline.sealed.is_empty());
+        assert_eq!(history.balance_at("Alice", u64::MAX), Some(CHUNK as u64 - 1));
+        assert_eq!(history.balance_at("Bob", 300), None);
+        assert!(history.pending.is_empty());
+    }
+}
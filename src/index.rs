@@ -0,0 +1,186 @@
+// ============================================
+// ACCOUNT INDEXES
+// ============================================
+// Secondary indexes over the accounts for explorer-style queries: by name,
+// owner program, balance, creation time and token held, plus the total
+// supply. Like the state commitment they are brought up to date lazily:
+// writes only mark the account stale and the next query reindexes it, so a
+// query costs the stale accounts plus a tree lookup, not a scan.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::{Bound, RangeBounds};
+
+use crate::pubkey::Pubkey;
+use crate::state::Account;
+
+/// What an account is indexed under, kept to find its entries again
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    owner: Pubkey,
+    balance: u64,
+    created_at: u64,
+    tokens: Vec<String>, // Mints it holds a non-zero balance of
+}
+
+impl Entry {
+    fn of(account: &Account) -> Self {
+        Self {
+            owner: account.owner,
+            balance: account.balance,
+            created_at: account.created_at,
+            tokens: account
+                .tokens
+                .iter()
+                .filter(|(_, amount)| **amount > 0)
+                .map(|(mint, _)| mint.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Indexes over every account, by name
+#[derive(Debug, Clone, Default)]
+pub struct AccountIndex {
+    stale: BTreeSet<String>,
+    entries: BTreeMap<String, Entry>,
+    by_owner: BTreeMap<Pubkey, BTreeSet<String>>,
+    by_balance: BTreeSet<(u64, String)>,
+    by_creation: BTreeSet<(u64, String)>,
+    by_token: BTreeMap<String, BTreeSet<String>>,
+    total: u128,
+}
+
+impl AccountIndex {
+    /// Note that account `name` was written
+    pub fn mark_stale(&mut self, name: &str) {
+        if !self.stale.contains(name) {
+            self.stale.insert(name.to_string());
+        }
+    }
+
+    /// Reindex the accounts written since the last refresh
+    pub fn refresh(&mut self, accounts: &HashMap<String, Account>) {
+        for name in std::mem::take(&mut self.stale) {
+            let entry = accounts.get(&name).map(Entry::of);
+            if self.entries.get(&name) == entry.as_ref() {
+                continue;
+            }
+            self.remove(&name);
+            if let Some(entry) = entry {
+                self.insert(name, entry);
+            }
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        let Some(entry) = self.entries.remove(name) else {
+            return;
+        };
+        if let Some(names) = self.by_owner.get_mut(&entry.owner) {
+            names.remove(name);
+            if names.is_empty() {
+                self.by_owner.remove(&entry.owner);
+            }
+        }
+        self.by_balance.remove(&(entry.balance, name.to_string()));
+        self.by_creation.remove(&(entry.created_at, name.to_string()));
+        for mint in &entry.tokens {
+            if let Some(holders) = self.by_token.get_mut(mint) {
+                holders.remove(name);
+                if holders.is_empty() {
+                    self.by_token.remove(mint);
+                }
+            }
+        }
+        self.total -= u128::from(entry.balance);
+    }
+
+    fn insert(&mut self, name: String, entry: Entry) {
+        self.by_owner.entry(entry.owner).or_default().insert(name.clone());
+        self.by_balance.insert((entry.balance, name.clone()));
+        self.by_creation.insert((entry.created_at, name.clone()));
+        for mint in &entry.tokens {
+            self.by_token.entry(mint.clone()).or_default().insert(name.clone());
+        }
+        self.total += u128::from(entry.balance);
+        self.entries.insert(name, entry);
+    }
+
+    /// Every account name, in order
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    /// Accounts owned by program `owner`, in name order
+    pub fn owned_by(&self, owner: &Pubkey) -> impl Iterator<Item = &String> {
+        self.by_owner.get(owner).into_iter().flatten()
+    }
+
+    /// Accounts whose balance lies in `balances`, smallest first
+    pub fn by_balance(&self, balances: impl RangeBounds<u64>) -> impl DoubleEndedIterator<Item = &String> {
+        keyed_range(&self.by_balance, balances)
+    }
+
+    /// Accounts created at a time (seconds) in `times`, oldest first
+    pub fn by_creation(&self, times: impl RangeBounds<u64>) -> impl DoubleEndedIterator<Item = &String> {
+        keyed_range(&self.by_creation, times)
+    }
+
+    /// Accounts holding a non-zero balance of token `mint`, in name order
+    pub fn holders(&self, mint: &str) -> impl Iterator<Item = &String> {
+        self.by_token.get(mint).into_iter().flatten()
+    }
+
+    /// Sum of all balances
+    pub fn total(&self) -> u128 {
+        self.total
+    }
+}
+
+/// Names in a set of (key, name) pairs whose key lies in `keys`
+fn keyed_range(set: &BTreeSet<(u64, String)>, keys: impl RangeBounds<u64>) -> impl DoubleEndedIterator<Item = &String> {
+    let low = match keys.start_bound() {
+        Bound::Included(key) => Some(*key),
+        Bound::Excluded(key) => key.checked_add(1),
+        Bound::Unbounded => Some(0),
+    };
+    let high = match keys.end_bound() {
+        Bound::Included(key) => Some(*key),
+        Bound::Excluded(key) => key.checked_sub(1),
+        Bound::Unbounded => Some(u64::MAX),
+    };
+    // An empty range yields nothing rather than panicking in `BTreeSet::range`
+    let range = match (low, high) {
+        (Some(low), Some(high)) if low <= high => {
+            let end = match high.checked_add(1) {
+                Some(next) => Bound::Excluded((next, String::new())),
+                None => Bound::Unbounded,
+            };
+            Some(set.range((Bound::Included((low, String::new())), end)))
+        }
+        _ => None,
+    };
+    range.into_iter().flatten().map(|(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyed_range_bounds() {
+        let set: BTreeSet<(u64, String)> = [(0, "a"), (5, "b"), (5, "c"), (u64::MAX, "d")]
+            .into_iter()
+            .map(|(key, name)| (key, name.to_string()))
+            .collect();
+        let names = |keys: (Bound<u64>, Bound<u64>)| keyed_range(&set, keys).cloned().collect::<Vec<_>>();
+
+        assert_eq!(names((Bound::Unbounded, Bound::Unbounded)), ["a", "b", "c", "d"]);
+        assert_eq!(names((Bound::Included(5), Bound::Included(5))), ["b", "c"]);
+        assert_eq!(names((Bound::Excluded(0), Bound::Excluded(u64::MAX))), ["b", "c"]);
+        assert_eq!(names((Bound::Included(u64::MAX), Bound::Unbounded)), ["d"]);
+        assert!(names((Bound::Included(6), Bound::Included(4))).is_empty());
+        assert!(names((Bound::Excluded(u64::MAX), Bound::Unbounded)).is_empty());
+        assert!(names((Bound::Unbounded, Bound::Excluded(0))).is_empty());
+    }
+}
@@ -3,6 +3,12 @@
 // ============================================
 // Objectives: Learn about Enums, Pattern Matching, and Stateless Instructions
 
+use crate::codec::{Decoder, Encoder};
+use crate::error::AppError;
+use crate::program::AccountMeta;
+use crate::pubkey::Pubkey;
+use crate::system_program::SystemInstruction;
+
 /// Instruction represents actions that can be performed on the system
 /// In stateless architecture, each instruction contains all necessary data
 /// to execute without depending on previous state
@@ -47,6 +53,283 @@ pub enum Instruction {
     DeleteAccount {
         name: String,
     },
+
+    /// Allow `delegate` to spend up to `amount` from `owner`
+    Approve {
+        owner: String,
+        delegate: String,
+        amount: u64,
+    },
+
+    /// Remove the delegate of `owner`
+    Revoke {
+        owner: String,
+    },
+
+    /// Transfer from `from` to `to` using the allowance given to `delegate`
+    TransferFrom {
+        delegate: String,
+        from: String,
+        to: String,
+        amount: u64,
+    },
+
+    /// Freeze `name`; must be sent by the freeze authority
+    FreezeAccount {
+        authority: String,
+        name: String,
+    },
+
+    /// Thaw `name`; must be sent by the freeze authority
+    ThawAccount {
+        authority: String,
+        name: String,
+    },
+
+    /// Register an M-of-N multisig with `threshold` = M
+    CreateMultisig {
+        name: String,
+        signers: Vec<String>,
+        threshold: u8,
+    },
+
+    /// Put `name` under a multisig (or release it with `None`);
+    /// `signers` must satisfy the current authority, if any
+    SetAuthority {
+        name: String,
+        authority: Option<String>,
+        signers: Vec<String>,
+    },
+
+    /// Transfer out of a multisig-controlled account
+    MultisigTransfer {
+        from: String,
+        to: String,
+        amount: u64,
+        signers: Vec<String>,
+    },
+
+    /// Create an account at a program derived `address`; the processor
+    /// checks that `seeds` + `bump` under `program_id` really derive it, and
+    /// that `authority` is the deployed program's upgrade authority
+    CreateProgramAccount {
+        address: Pubkey,
+        program_id: Pubkey,
+        seeds: Vec<Vec<u8>>,
+        bump: u8,
+        balance: u64,
+        authority: String,
+    },
+
+    /// Lock `amount` from `depositor` until `arbiter` releases it to
+    /// `recipient`, or it is cancelled / expires at `expiry_slot`
+    EscrowCreate {
+        name: String,
+        depositor: String,
+        recipient: String,
+        arbiter: String,
+        amount: u64,
+        expiry_slot: u64,
+    },
+
+    /// Pay the escrow out to its recipient
+    EscrowRelease {
+        name: String,
+        signer: String,
+    },
+
+    /// Refund the escrow to its depositor
+    EscrowCancel {
+        name: String,
+        signer: String,
+    },
+
+    /// Lock `total` from `funder` for `beneficiary`, unlocking linearly from
+    /// slot `start` over `duration` slots with nothing before `cliff` slots
+    CreateVesting {
+        funder: String,
+        beneficiary: String,
+        total: u64,
+        start: u64,
+        cliff: u64,
+        duration: u64,
+    },
+
+    /// Claim `amount` of the unlocked portion of a vesting schedule
+    ClaimVested {
+        beneficiary: String,
+        amount: u64,
+    },
+
+    /// Open stake account `name` for `staker`, funded with `amount`
+    CreateStake {
+        staker: String,
+        name: String,
+        amount: u64,
+    },
+
+    /// Delegate stake account `name` to `validator`
+    Delegate {
+        name: String,
+        staker: String,
+        validator: String,
+    },
+
+    /// Start cooling down stake account `name`
+    Deactivate {
+        name: String,
+        staker: String,
+    },
+
+    /// Withdraw unlocked lamports from stake account `name` to `to`
+    Withdraw {
+        name: String,
+        staker: String,
+        to: String,
+        amount: u64,
+    },
+
+    /// Pay `amount` from `from` to `to` every `interval` slots, `count` times
+    SchedulePayment {
+        from: String,
+        to: String,
+        amount: u64,
+        interval: u64,
+        count: u32,
+    },
+
+    /// Create token mint `symbol` controlled by `authority`
+    CreateMint {
+        symbol: String,
+        authority: String,
+    },
+
+    /// Mint `amount` new `symbol` tokens to `to`
+    MintTo {
+        symbol: String,
+        authority: String,
+        to: String,
+        amount: u64,
+    },
+
+    /// Transfer `amount` of token `symbol` between two accounts
+    TransferToken {
+        symbol: String,
+        from: String,
+        to: String,
+        amount: u64,
+    },
+
+    /// Create an empty constant-product pool for two mints
+    CreatePool {
+        name: String,
+        mint_a: String,
+        mint_b: String,
+        fee_bps: u16,
+    },
+
+    /// Deposit up to `amount_a` / `amount_b` for at least `min_shares` LP tokens
+    AddLiquidity {
+        pool: String,
+        provider: String,
+        amount_a: u64,
+        amount_b: u64,
+        min_shares: u64,
+    },
+
+    /// Burn `shares` LP tokens for at least `min_a` / `min_b`
+    RemoveLiquidity {
+        pool: String,
+        provider: String,
+        shares: u64,
+        min_a: u64,
+        min_b: u64,
+    },
+
+    /// Sell `amount_in` of `mint_in` for at least `min_out` of the other side
+    Swap {
+        pool: String,
+        trader: String,
+        mint_in: String,
+        amount_in: u64,
+        min_out: u64,
+    },
+
+    /// Register (or renew) `name` for `owner`, pointing at `target`
+    RegisterName {
+        name: String,
+        owner: String,
+        target: String,
+        duration: u64,
+    },
+
+    /// Look up the account a registered name points at
+    ResolveName {
+        name: String,
+    },
+
+    /// Hand a registered name over to `new_owner`
+    TransferName {
+        name: String,
+        owner: String,
+        new_owner: String,
+    },
+
+    /// Upload `bytecode` as a new program named `name`, deployed by `owner`
+    DeployProgram {
+        owner: String,
+        name: String,
+        bytecode: Vec<u8>,
+    },
+
+    /// Replace the bytecode of a deployed program, keeping its ID
+    UpgradeProgram {
+        program_id: Pubkey,
+        authority: String,
+        bytecode: Vec<u8>,
+    },
+
+    /// Hand a program's upgrade authority to `new_authority`; `None` makes it immutable
+    SetUpgradeAuthority {
+        program_id: Pubkey,
+        authority: String,
+        new_authority: Option<String>,
+    },
+
+    /// Drop a program's upgrade authority so it can never change again
+    FinalizeProgram {
+        program_id: Pubkey,
+        authority: String,
+    },
+
+    /// Call the program registered under `program_id` with raw `data`
+    Invoke {
+        program_id: Pubkey,
+        accounts: Vec<AccountMeta>,
+        data: Vec<u8>,
+    },
+
+    /// Open nonce account `name` controlled by `authority`, funded with `amount`
+    CreateNonceAccount {
+        funder: String,
+        name: String,
+        authority: String,
+        amount: u64,
+    },
+
+    /// Replace the stored nonce; must come first in a transaction that uses it
+    AdvanceNonce {
+        name: String,
+        authority: String,
+    },
+
+    /// Withdraw from nonce account `name` to `to`, closing it when emptied
+    WithdrawNonce {
+        name: String,
+        authority: String,
+        to: String,
+        amount: u64,
+    },
 }
 
 // TODO 1.6: Implement methods for Instruction
@@ -54,19 +337,709 @@ impl Instruction {
     /// Create a new CreateAccount instruction
     pub fn create_account(name: String, balance: u64) -> Self {
         // TODO: Return Instruction::CreateAccount with the given parameters
-        return Instruction::CreateAccount { name, balance };
+        Instruction::CreateAccount { name, balance }
     }
 
     /// Create a new Transfer instruction
     pub fn transfer(from: String, to: String, amount: u64) -> Self {
         // TODO: Return Instruction::Transfer with the given parameters
-        return Instruction::Transfer { from, to, amount };
+        Instruction::Transfer { from, to, amount }
     }
 
     /// Create a new UpdateBalance instruction
     pub fn update_balance(name: String, amount: u64) -> Self {
         // TODO: Return Instruction::UpdateBalance with the given parameters
-        return Instruction::UpdateBalance { name, amount };
+        Instruction::UpdateBalance { name, amount }
+    }
+
+    /// Create a new GetBalance instruction
+    pub fn get_balance(name: String) -> Self {
+        Instruction::GetBalance { name }
+    }
+
+    /// Create a new DeleteAccount instruction
+    pub fn delete_account(name: String) -> Self {
+        Instruction::DeleteAccount { name }
+    }
+
+    /// Create a new Approve instruction
+    pub fn approve(owner: String, delegate: String, amount: u64) -> Self {
+        Instruction::Approve { owner, delegate, amount }
+    }
+
+    /// Create a new Revoke instruction
+    pub fn revoke(owner: String) -> Self {
+        Instruction::Revoke { owner }
+    }
+
+    /// Create a new TransferFrom instruction
+    pub fn transfer_from(delegate: String, from: String, to: String, amount: u64) -> Self {
+        Instruction::TransferFrom { delegate, from, to, amount }
+    }
+
+    /// Create a new FreezeAccount instruction
+    pub fn freeze_account(authority: String, name: String) -> Self {
+        Instruction::FreezeAccount { authority, name }
+    }
+
+    /// Create a new ThawAccount instruction
+    pub fn thaw_account(authority: String, name: String) -> Self {
+        Instruction::ThawAccount { authority, name }
+    }
+
+    /// Create a new CreateMultisig instruction
+    pub fn create_multisig(name: String, signers: Vec<String>, threshold: u8) -> Self {
+        Instruction::CreateMultisig { name, signers, threshold }
+    }
+
+    /// Create a new SetAuthority instruction
+    pub fn set_authority(name: String, authority: Option<String>, signers: Vec<String>) -> Self {
+        Instruction::SetAuthority { name, authority, signers }
+    }
+
+    /// Create a new MultisigTransfer instruction
+    pub fn multisig_transfer(from: String, to: String, amount: u64, signers: Vec<String>) -> Self {
+        Instruction::MultisigTransfer { from, to, amount, signers }
+    }
+
+    /// Create a new CreateProgramAccount instruction, finding the bump seed
+    pub fn create_program_account(
+        program_id: Pubkey,
+        authority: String,
+        seeds: Vec<Vec<u8>>,
+        balance: u64,
+    ) -> crate::error::Result<Self> {
+        let seed_refs: Vec<&[u8]> = seeds.iter().map(|seed| seed.as_slice()).collect();
+        let (address, bump) = Pubkey::find_program_address(&seed_refs, &program_id)?;
+        Ok(Instruction::CreateProgramAccount { address, program_id, seeds, bump, balance, authority })
+    }
+
+    /// Create a new EscrowCreate instruction
+    pub fn escrow_create(
+        name: String,
+        depositor: String,
+        recipient: String,
+        arbiter: String,
+        amount: u64,
+        expiry_slot: u64,
+    ) -> Self {
+        Instruction::EscrowCreate { name, depositor, recipient, arbiter, amount, expiry_slot }
+    }
+
+    /// Create a new EscrowRelease instruction
+    pub fn escrow_release(name: String, signer: String) -> Self {
+        Instruction::EscrowRelease { name, signer }
+    }
+
+    /// Create a new EscrowCancel instruction
+    pub fn escrow_cancel(name: String, signer: String) -> Self {
+        Instruction::EscrowCancel { name, signer }
+    }
+
+    /// Create a new CreateVesting instruction
+    pub fn create_vesting(
+        funder: String,
+        beneficiary: String,
+        total: u64,
+        start: u64,
+        cliff: u64,
+        duration: u64,
+    ) -> Self {
+        Instruction::CreateVesting { funder, beneficiary, total, start, cliff, duration }
+    }
+
+    /// Create a new ClaimVested instruction
+    pub fn claim_vested(beneficiary: String, amount: u64) -> Self {
+        Instruction::ClaimVested { beneficiary, amount }
+    }
+
+    /// Create a new CreateStake instruction
+    pub fn create_stake(staker: String, name: String, amount: u64) -> Self {
+        Instruction::CreateStake { staker, name, amount }
+    }
+
+    /// Create a new Delegate instruction
+    pub fn delegate(name: String, staker: String, validator: String) -> Self {
+        Instruction::Delegate { name, staker, validator }
+    }
+
+    /// Create a new Deactivate instruction
+    pub fn deactivate(name: String, staker: String) -> Self {
+        Instruction::Deactivate { name, staker }
+    }
+
+    /// Create a new Withdraw instruction
+    pub fn withdraw(name: String, staker: String, to: String, amount: u64) -> Self {
+        Instruction::Withdraw { name, staker, to, amount }
+    }
+
+    /// Create a new SchedulePayment instruction
+    pub fn schedule_payment(from: String, to: String, amount: u64, interval: u64, count: u32) -> Self {
+        Instruction::SchedulePayment { from, to, amount, interval, count }
+    }
+
+    /// Create a new CreateMint instruction
+    pub fn create_mint(symbol: String, authority: String) -> Self {
+        Instruction::CreateMint { symbol, authority }
+    }
+
+    /// Create a new MintTo instruction
+    pub fn mint_to(symbol: String, authority: String, to: String, amount: u64) -> Self {
+        Instruction::MintTo { symbol, authority, to, amount }
+    }
+
+    /// Create a new TransferToken instruction
+    pub fn transfer_token(symbol: String, from: String, to: String, amount: u64) -> Self {
+        Instruction::TransferToken { symbol, from, to, amount }
+    }
+
+    /// Create a new CreatePool instruction
+    pub fn create_pool(name: String, mint_a: String, mint_b: String, fee_bps: u16) -> Self {
+        Instruction::CreatePool { name, mint_a, mint_b, fee_bps }
+    }
+
+    /// Create a new AddLiquidity instruction
+    pub fn add_liquidity(pool: String, provider: String, amount_a: u64, amount_b: u64, min_shares: u64) -> Self {
+        Instruction::AddLiquidity { pool, provider, amount_a, amount_b, min_shares }
+    }
+
+    /// Create a new RemoveLiquidity instruction
+    pub fn remove_liquidity(pool: String, provider: String, shares: u64, min_a: u64, min_b: u64) -> Self {
+        Instruction::RemoveLiquidity { pool, provider, shares, min_a, min_b }
+    }
+
+    /// Create a new Swap instruction
+    pub fn swap(pool: String, trader: String, mint_in: String, amount_in: u64, min_out: u64) -> Self {
+        Instruction::Swap { pool, trader, mint_in, amount_in, min_out }
+    }
+
+    /// Create a new RegisterName instruction
+    pub fn register_name(name: String, owner: String, target: String, duration: u64) -> Self {
+        Instruction::RegisterName { name, owner, target, duration }
+    }
+
+    /// Create a new ResolveName instruction
+    pub fn resolve_name(name: String) -> Self {
+        Instruction::ResolveName { name }
+    }
+
+    /// Create a new TransferName instruction
+    pub fn transfer_name(name: String, owner: String, new_owner: String) -> Self {
+        Instruction::TransferName { name, owner, new_owner }
+    }
+
+    /// Create a new DeployProgram instruction
+    pub fn deploy_program(owner: String, name: String, bytecode: Vec<u8>) -> Self {
+        Instruction::DeployProgram { owner, name, bytecode }
+    }
+
+    /// Create a new UpgradeProgram instruction
+    pub fn upgrade_program(program_id: Pubkey, authority: String, bytecode: Vec<u8>) -> Self {
+        Instruction::UpgradeProgram { program_id, authority, bytecode }
+    }
+
+    /// Create a new SetUpgradeAuthority instruction
+    pub fn set_upgrade_authority(program_id: Pubkey, authority: String, new_authority: Option<String>) -> Self {
+        Instruction::SetUpgradeAuthority { program_id, authority, new_authority }
+    }
+
+    /// Create a new FinalizeProgram instruction
+    pub fn finalize_program(program_id: Pubkey, authority: String) -> Self {
+        Instruction::FinalizeProgram { program_id, authority }
+    }
+
+    /// Create a new Invoke instruction
+    pub fn invoke(program_id: Pubkey, accounts: Vec<AccountMeta>, data: Vec<u8>) -> Self {
+        Instruction::Invoke { program_id, accounts, data }
+    }
+
+    /// Create a new CreateNonceAccount instruction
+    pub fn create_nonce_account(funder: String, name: String, authority: String, amount: u64) -> Self {
+        Instruction::CreateNonceAccount { funder, name, authority, amount }
+    }
+
+    /// Create a new AdvanceNonce instruction
+    pub fn advance_nonce(name: String, authority: String) -> Self {
+        Instruction::AdvanceNonce { name, authority }
+    }
+
+    /// Create a new WithdrawNonce instruction
+    pub fn withdraw_nonce(name: String, authority: String, to: String, amount: u64) -> Self {
+        Instruction::WithdrawNonce { name, authority, to, amount }
+    }
+
+    /// Accounts and data of the system program call behind one of the core
+    /// account instructions; `None` for every other instruction. The sender
+    /// of a core instruction signs for the accounts it acts on.
+    pub fn to_system_call(&self) -> Option<(Vec<AccountMeta>, Vec<u8>)> {
+        let (accounts, instruction) = match self {
+            Instruction::CreateAccount { name, balance } => (
+                vec![AccountMeta::new(name.clone(), true)],
+                SystemInstruction::CreateAccount { balance: *balance },
+            ),
+            Instruction::Transfer { from, to, amount } => (
+                vec![AccountMeta::new(from.clone(), true), AccountMeta::new(to.clone(), false)],
+                SystemInstruction::Transfer { amount: *amount },
+            ),
+            Instruction::UpdateBalance { name, amount } => (
+                vec![AccountMeta::new(name.clone(), false)],
+                SystemInstruction::UpdateBalance { amount: *amount },
+            ),
+            Instruction::GetBalance { name } => (
+                vec![AccountMeta::new_readonly(name.clone(), false)],
+                SystemInstruction::GetBalance,
+            ),
+            Instruction::DeleteAccount { name } => (
+                vec![AccountMeta::new(name.clone(), true)],
+                SystemInstruction::DeleteAccount,
+            ),
+            _ => return None,
+        };
+        Some((accounts, instruction.pack()))
+    }
+
+    /// Accounts that must authorize this instruction. A transaction carrying
+    /// it needs a signature from each of them that is a keypair account.
+    pub fn signers(&self) -> Vec<&str> {
+        match self {
+            Instruction::CreateAccount { name, .. } | Instruction::DeleteAccount { name } => vec![name],
+            Instruction::UpdateBalance { .. }
+            | Instruction::GetBalance { .. }
+            | Instruction::CreateMultisig { .. }
+            | Instruction::CreatePool { .. }
+            | Instruction::ResolveName { .. } => Vec::new(),
+            Instruction::Transfer { from, .. }
+            | Instruction::SchedulePayment { from, .. }
+            | Instruction::TransferToken { from, .. } => vec![from],
+            Instruction::Approve { owner, .. }
+            | Instruction::Revoke { owner }
+            | Instruction::RegisterName { owner, .. }
+            | Instruction::TransferName { owner, .. }
+            | Instruction::DeployProgram { owner, .. } => vec![owner],
+            Instruction::TransferFrom { delegate, .. } => vec![delegate],
+            Instruction::FreezeAccount { authority, .. }
+            | Instruction::ThawAccount { authority, .. }
+            | Instruction::CreateProgramAccount { authority, .. }
+            | Instruction::CreateMint { authority, .. }
+            | Instruction::MintTo { authority, .. }
+            | Instruction::UpgradeProgram { authority, .. }
+            | Instruction::SetUpgradeAuthority { authority, .. }
+            | Instruction::FinalizeProgram { authority, .. }
+            | Instruction::AdvanceNonce { authority, .. }
+            | Instruction::WithdrawNonce { authority, .. } => vec![authority],
+            Instruction::SetAuthority { signers, .. } | Instruction::MultisigTransfer { signers, .. } => {
+                signers.iter().map(String::as_str).collect()
+            }
+            Instruction::EscrowCreate { depositor, .. } => vec![depositor],
+            Instruction::EscrowRelease { signer, .. } | Instruction::EscrowCancel { signer, .. } => vec![signer],
+            Instruction::CreateVesting { funder, .. } | Instruction::CreateNonceAccount { funder, .. } => vec![funder],
+            Instruction::ClaimVested { beneficiary, .. } => vec![beneficiary],
+            Instruction::CreateStake { staker, .. }
+            | Instruction::Delegate { staker, .. }
+            | Instruction::Deactivate { staker, .. }
+            | Instruction::Withdraw { staker, .. } => vec![staker],
+            Instruction::AddLiquidity { provider, .. } | Instruction::RemoveLiquidity { provider, .. } => vec![provider],
+            Instruction::Swap { trader, .. } => vec![trader],
+            Instruction::Invoke { accounts, .. } => accounts
+                .iter()
+                .filter(|account| account.is_signer)
+                .map(|account| account.key.as_str())
+                .collect(),
+        }
+    }
+
+    /// Canonical byte encoding, used for hashing and signing. The first byte
+    /// is the variant's position in the enum, so new variants go at the end.
+    pub fn encode(&self) -> Vec<u8> {
+        let encoder = match self {
+            Instruction::CreateAccount { name, balance } => {
+                Encoder::new().put(&0u8).put(name).put(balance)
+            }
+            Instruction::Transfer { from, to, amount } => {
+                Encoder::new().put(&1u8).put(from).put(to).put(amount)
+            }
+            Instruction::UpdateBalance { name, amount } => {
+                Encoder::new().put(&2u8).put(name).put(amount)
+            }
+            Instruction::GetBalance { name } => {
+                Encoder::new().put(&3u8).put(name)
+            }
+            Instruction::DeleteAccount { name } => {
+                Encoder::new().put(&4u8).put(name)
+            }
+            Instruction::Approve { owner, delegate, amount } => {
+                Encoder::new().put(&5u8).put(owner).put(delegate).put(amount)
+            }
+            Instruction::Revoke { owner } => {
+                Encoder::new().put(&6u8).put(owner)
+            }
+            Instruction::TransferFrom { delegate, from, to, amount } => {
+                Encoder::new().put(&7u8).put(delegate).put(from).put(to).put(amount)
+            }
+            Instruction::FreezeAccount { authority, name } => {
+                Encoder::new().put(&8u8).put(authority).put(name)
+            }
+            Instruction::ThawAccount { authority, name } => {
+                Encoder::new().put(&9u8).put(authority).put(name)
+            }
+            Instruction::CreateMultisig { name, signers, threshold } => {
+                Encoder::new().put(&10u8).put(name).put(signers).put(threshold)
+            }
+            Instruction::SetAuthority { name, authority, signers } => {
+                Encoder::new().put(&11u8).put(name).put(authority).put(signers)
+            }
+            Instruction::MultisigTransfer { from, to, amount, signers } => {
+                Encoder::new().put(&12u8).put(from).put(to).put(amount).put(signers)
+            }
+            Instruction::CreateProgramAccount { address, program_id, seeds, bump, balance, authority } => {
+                Encoder::new()
+                    .put(&13u8)
+                    .put(address)
+                    .put(program_id)
+                    .put(seeds)
+                    .put(bump)
+                    .put(balance)
+                    .put(authority)
+            }
+            Instruction::EscrowCreate { name, depositor, recipient, arbiter, amount, expiry_slot } => {
+                Encoder::new()
+                    .put(&14u8)
+                    .put(name)
+                    .put(depositor)
+                    .put(recipient)
+                    .put(arbiter)
+                    .put(amount)
+                    .put(expiry_slot)
+            }
+            Instruction::EscrowRelease { name, signer } => {
+                Encoder::new().put(&15u8).put(name).put(signer)
+            }
+            Instruction::EscrowCancel { name, signer } => {
+                Encoder::new().put(&16u8).put(name).put(signer)
+            }
+            Instruction::CreateVesting { funder, beneficiary, total, start, cliff, duration } => {
+                Encoder::new()
+                    .put(&17u8)
+                    .put(funder)
+                    .put(beneficiary)
+                    .put(total)
+                    .put(start)
+                    .put(cliff)
+                    .put(duration)
+            }
+            Instruction::ClaimVested { beneficiary, amount } => {
+                Encoder::new().put(&18u8).put(beneficiary).put(amount)
+            }
+            Instruction::CreateStake { staker, name, amount } => {
+                Encoder::new().put(&19u8).put(staker).put(name).put(amount)
+            }
+            Instruction::Delegate { name, staker, validator } => {
+                Encoder::new().put(&20u8).put(name).put(staker).put(validator)
+            }
+            Instruction::Deactivate { name, staker } => {
+                Encoder::new().put(&21u8).put(name).put(staker)
+            }
+            Instruction::Withdraw { name, staker, to, amount } => {
+                Encoder::new().put(&22u8).put(name).put(staker).put(to).put(amount)
+            }
+            Instruction::SchedulePayment { from, to, amount, interval, count } => {
+                Encoder::new().put(&23u8).put(from).put(to).put(amount).put(interval).put(count)
+            }
+            Instruction::CreateMint { symbol, authority } => {
+                Encoder::new().put(&24u8).put(symbol).put(authority)
+            }
+            Instruction::MintTo { symbol, authority, to, amount } => {
+                Encoder::new().put(&25u8).put(symbol).put(authority).put(to).put(amount)
+            }
+            Instruction::TransferToken { symbol, from, to, amount } => {
+                Encoder::new().put(&26u8).put(symbol).put(from).put(to).put(amount)
+            }
+            Instruction::CreatePool { name, mint_a, mint_b, fee_bps } => {
+                Encoder::new().put(&27u8).put(name).put(mint_a).put(mint_b).put(fee_bps)
+            }
+            Instruction::AddLiquidity { pool, provider, amount_a, amount_b, min_shares } => {
+                Encoder::new()
+                    .put(&28u8)
+                    .put(pool)
+                    .put(provider)
+                    .put(amount_a)
+                    .put(amount_b)
+                    .put(min_shares)
+            }
+            Instruction::RemoveLiquidity { pool, provider, shares, min_a, min_b } => {
+                Encoder::new().put(&29u8).put(pool).put(provider).put(shares).put(min_a).put(min_b)
+            }
+            Instruction::Swap { pool, trader, mint_in, amount_in, min_out } => {
+                Encoder::new()
+                    .put(&30u8)
+                    .put(pool)
+                    .put(trader)
+                    .put(mint_in)
+                    .put(amount_in)
+                    .put(min_out)
+            }
+            Instruction::RegisterName { name, owner, target, duration } => {
+                Encoder::new().put(&31u8).put(name).put(owner).put(target).put(duration)
+            }
+            Instruction::ResolveName { name } => {
+                Encoder::new().put(&32u8).put(name)
+            }
+            Instruction::TransferName { name, owner, new_owner } => {
+                Encoder::new().put(&33u8).put(name).put(owner).put(new_owner)
+            }
+            Instruction::DeployProgram { owner, name, bytecode } => {
+                Encoder::new().put(&34u8).put(owner).put(name).put(bytecode)
+            }
+            Instruction::UpgradeProgram { program_id, authority, bytecode } => {
+                Encoder::new().put(&35u8).put(program_id).put(authority).put(bytecode)
+            }
+            Instruction::SetUpgradeAuthority { program_id, authority, new_authority } => {
+                Encoder::new().put(&36u8).put(program_id).put(authority).put(new_authority)
+            }
+            Instruction::FinalizeProgram { program_id, authority } => {
+                Encoder::new().put(&37u8).put(program_id).put(authority)
+            }
+            Instruction::Invoke { program_id, accounts, data } => {
+                Encoder::new().put(&38u8).put(program_id).put(accounts).put(data)
+            }
+            Instruction::CreateNonceAccount { funder, name, authority, amount } => {
+                Encoder::new().put(&39u8).put(funder).put(name).put(authority).put(amount)
+            }
+            Instruction::AdvanceNonce { name, authority } => {
+                Encoder::new().put(&40u8).put(name).put(authority)
+            }
+            Instruction::WithdrawNonce { name, authority, to, amount } => {
+                Encoder::new().put(&41u8).put(name).put(authority).put(to).put(amount)
+            }
+        };
+        encoder.finish()
+    }
+
+    /// Decode an instruction written by `encode`
+    pub fn decode(data: &[u8]) -> Result<Self, AppError> {
+        let mut decoder = Decoder::new(data);
+        let instruction = match decoder.get::<u8>()? {
+            0 => Instruction::CreateAccount {
+                name: decoder.get()?,
+                balance: decoder.get()?,
+            },
+            1 => Instruction::Transfer {
+                from: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            2 => Instruction::UpdateBalance {
+                name: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            3 => Instruction::GetBalance {
+                name: decoder.get()?,
+            },
+            4 => Instruction::DeleteAccount {
+                name: decoder.get()?,
+            },
+            5 => Instruction::Approve {
+                owner: decoder.get()?,
+                delegate: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            6 => Instruction::Revoke {
+                owner: decoder.get()?,
+            },
+            7 => Instruction::TransferFrom {
+                delegate: decoder.get()?,
+                from: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            8 => Instruction::FreezeAccount {
+                authority: decoder.get()?,
+                name: decoder.get()?,
+            },
+            9 => Instruction::ThawAccount {
+                authority: decoder.get()?,
+                name: decoder.get()?,
+            },
+            10 => Instruction::CreateMultisig {
+                name: decoder.get()?,
+                signers: decoder.get()?,
+                threshold: decoder.get()?,
+            },
+            11 => Instruction::SetAuthority {
+                name: decoder.get()?,
+                authority: decoder.get()?,
+                signers: decoder.get()?,
+            },
+            12 => Instruction::MultisigTransfer {
+                from: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+                signers: decoder.get()?,
+            },
+            13 => Instruction::CreateProgramAccount {
+                address: decoder.get()?,
+                program_id: decoder.get()?,
+                seeds: decoder.get()?,
+                bump: decoder.get()?,
+                balance: decoder.get()?,
+                authority: decoder.get()?,
+            },
+            14 => Instruction::EscrowCreate {
+                name: decoder.get()?,
+                depositor: decoder.get()?,
+                recipient: decoder.get()?,
+                arbiter: decoder.get()?,
+                amount: decoder.get()?,
+                expiry_slot: decoder.get()?,
+            },
+            15 => Instruction::EscrowRelease {
+                name: decoder.get()?,
+                signer: decoder.get()?,
+            },
+            16 => Instruction::EscrowCancel {
+                name: decoder.get()?,
+                signer: decoder.get()?,
+            },
+            17 => Instruction::CreateVesting {
+                funder: decoder.get()?,
+                beneficiary: decoder.get()?,
+                total: decoder.get()?,
+                start: decoder.get()?,
+                cliff: decoder.get()?,
+                duration: decoder.get()?,
+            },
+            18 => Instruction::ClaimVested {
+                beneficiary: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            19 => Instruction::CreateStake {
+                staker: decoder.get()?,
+                name: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            20 => Instruction::Delegate {
+                name: decoder.get()?,
+                staker: decoder.get()?,
+                validator: decoder.get()?,
+            },
+            21 => Instruction::Deactivate {
+                name: decoder.get()?,
+                staker: decoder.get()?,
+            },
+            22 => Instruction::Withdraw {
+                name: decoder.get()?,
+                staker: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            23 => Instruction::SchedulePayment {
+                from: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+                interval: decoder.get()?,
+                count: decoder.get()?,
+            },
+            24 => Instruction::CreateMint {
+                symbol: decoder.get()?,
+                authority: decoder.get()?,
+            },
+            25 => Instruction::MintTo {
+                symbol: decoder.get()?,
+                authority: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            26 => Instruction::TransferToken {
+                symbol: decoder.get()?,
+                from: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            27 => Instruction::CreatePool {
+                name: decoder.get()?,
+                mint_a: decoder.get()?,
+                mint_b: decoder.get()?,
+                fee_bps: decoder.get()?,
+            },
+            28 => Instruction::AddLiquidity {
+                pool: decoder.get()?,
+                provider: decoder.get()?,
+                amount_a: decoder.get()?,
+                amount_b: decoder.get()?,
+                min_shares: decoder.get()?,
+            },
+            29 => Instruction::RemoveLiquidity {
+                pool: decoder.get()?,
+                provider: decoder.get()?,
+                shares: decoder.get()?,
+                min_a: decoder.get()?,
+                min_b: decoder.get()?,
+            },
+            30 => Instruction::Swap {
+                pool: decoder.get()?,
+                trader: decoder.get()?,
+                mint_in: decoder.get()?,
+                amount_in: decoder.get()?,
+                min_out: decoder.get()?,
+            },
+            31 => Instruction::RegisterName {
+                name: decoder.get()?,
+                owner: decoder.get()?,
+                target: decoder.get()?,
+                duration: decoder.get()?,
+            },
+            32 => Instruction::ResolveName {
+                name: decoder.get()?,
+            },
+            33 => Instruction::TransferName {
+                name: decoder.get()?,
+                owner: decoder.get()?,
+                new_owner: decoder.get()?,
+            },
+            34 => Instruction::DeployProgram {
+                owner: decoder.get()?,
+                name: decoder.get()?,
+                bytecode: decoder.get()?,
+            },
+            35 => Instruction::UpgradeProgram {
+                program_id: decoder.get()?,
+                authority: decoder.get()?,
+                bytecode: decoder.get()?,
+            },
+            36 => Instruction::SetUpgradeAuthority {
+                program_id: decoder.get()?,
+                authority: decoder.get()?,
+                new_authority: decoder.get()?,
+            },
+            37 => Instruction::FinalizeProgram {
+                program_id: decoder.get()?,
+                authority: decoder.get()?,
+            },
+            38 => Instruction::Invoke {
+                program_id: decoder.get()?,
+                accounts: decoder.get()?,
+                data: decoder.get()?,
+            },
+            39 => Instruction::CreateNonceAccount {
+                funder: decoder.get()?,
+                name: decoder.get()?,
+                authority: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            40 => Instruction::AdvanceNonce {
+                name: decoder.get()?,
+                authority: decoder.get()?,
+            },
+            41 => Instruction::WithdrawNonce {
+                name: decoder.get()?,
+                authority: decoder.get()?,
+                to: decoder.get()?,
+                amount: decoder.get()?,
+            },
+            tag => return Err(AppError::InvalidInstructionData(format!("unknown instruction tag {}", tag))),
+        };
+        decoder.finish()?;
+        Ok(instruction)
     }
 
     /// Get a short description of the instruction
@@ -96,6 +1069,228 @@ impl Instruction {
             Instruction::DeleteAccount { name } => {
                 format!("Delete account '{}'", name)
             }
+            Instruction::Approve { owner, delegate, amount } => {
+                format!("Approve '{}' to spend {} from '{}'", delegate, amount, owner)
+            }
+            Instruction::Revoke { owner } => {
+                format!("Revoke delegate of '{}'", owner)
+            }
+            Instruction::TransferFrom { delegate, from, to, amount } => {
+                format!(
+                    "Transfer {} from '{}' to '{}' as delegate '{}'",
+                    amount, from, to, delegate
+                )
+            }
+            Instruction::FreezeAccount { authority, name } => {
+                format!("Freeze account '{}' by '{}'", name, authority)
+            }
+            Instruction::ThawAccount { authority, name } => {
+                format!("Thaw account '{}' by '{}'", name, authority)
+            }
+            Instruction::CreateMultisig { name, signers, threshold } => {
+                format!(
+                    "Create {}-of-{} multisig '{}'",
+                    threshold,
+                    signers.len(),
+                    name
+                )
+            }
+            Instruction::SetAuthority { name, authority, .. } => match authority {
+                Some(authority) => format!("Set authority of '{}' to '{}'", name, authority),
+                None => format!("Remove authority of '{}'", name),
+            },
+            Instruction::MultisigTransfer { from, to, amount, signers } => {
+                format!(
+                    "Transfer {} from '{}' to '{}' signed by {}",
+                    amount,
+                    from,
+                    to,
+                    signers.join(", ")
+                )
+            }
+            Instruction::CreateProgramAccount { address, program_id, balance, .. } => {
+                format!(
+                    "Create program account '{}' owned by '{}' with initial balance of {}",
+                    address, program_id, balance
+                )
+            }
+            Instruction::EscrowCreate { name, depositor, recipient, amount, expiry_slot, .. } => {
+                format!(
+                    "Escrow '{}': lock {} from '{}' for '{}' until slot {}",
+                    name, amount, depositor, recipient, expiry_slot
+                )
+            }
+            Instruction::EscrowRelease { name, signer } => {
+                format!("Release escrow '{}' by '{}'", name, signer)
+            }
+            Instruction::EscrowCancel { name, signer } => {
+                format!("Cancel escrow '{}' by '{}'", name, signer)
+            }
+            Instruction::CreateVesting { funder, beneficiary, total, start, cliff, duration } => {
+                format!(
+                    "Vest {} from '{}' to '{}' starting at slot {} (cliff {}, duration {})",
+                    total, funder, beneficiary, start, cliff, duration
+                )
+            }
+            Instruction::ClaimVested { beneficiary, amount } => {
+                format!("Claim {} vested for '{}'", amount, beneficiary)
+            }
+            Instruction::CreateStake { staker, name, amount } => {
+                format!("Create stake account '{}' for '{}' with {}", name, staker, amount)
+            }
+            Instruction::Delegate { name, validator, .. } => {
+                format!("Delegate stake '{}' to '{}'", name, validator)
+            }
+            Instruction::Deactivate { name, .. } => {
+                format!("Deactivate stake '{}'", name)
+            }
+            Instruction::Withdraw { name, to, amount, .. } => {
+                format!("Withdraw {} from stake '{}' to '{}'", amount, name, to)
+            }
+            Instruction::SchedulePayment { from, to, amount, interval, count } => {
+                format!(
+                    "Pay {} from '{}' to '{}' every {} slots, {} times",
+                    amount, from, to, interval, count
+                )
+            }
+            Instruction::CreateMint { symbol, authority } => {
+                format!("Create mint '{}' with authority '{}'", symbol, authority)
+            }
+            Instruction::MintTo { symbol, to, amount, .. } => {
+                format!("Mint {} {} to '{}'", amount, symbol, to)
+            }
+            Instruction::TransferToken { symbol, from, to, amount } => {
+                format!("Transfer {} {} from '{}' to '{}'", amount, symbol, from, to)
+            }
+            Instruction::CreatePool { name, mint_a, mint_b, fee_bps } => {
+                format!(
+                    "Create pool '{}' for {}/{} with a {} bps fee",
+                    name, mint_a, mint_b, fee_bps
+                )
+            }
+            Instruction::AddLiquidity { pool, provider, amount_a, amount_b, .. } => {
+                format!(
+                    "Add up to {} / {} liquidity to '{}' from '{}'",
+                    amount_a, amount_b, pool, provider
+                )
+            }
+            Instruction::RemoveLiquidity { pool, provider, shares, .. } => {
+                format!("Remove {} shares from '{}' for '{}'", shares, pool, provider)
+            }
+            Instruction::Swap { pool, trader, mint_in, amount_in, min_out } => {
+                format!(
+                    "Swap {} {} in '{}' for '{}' (min out {})",
+                    amount_in, mint_in, pool, trader, min_out
+                )
+            }
+            Instruction::RegisterName { name, owner, target, duration } => {
+                format!(
+                    "Register '{}' for '{}' pointing at '{}' for {} slots",
+                    name, owner, target, duration
+                )
+            }
+            Instruction::ResolveName { name } => {
+                format!("Resolve name '{}'", name)
+            }
+            Instruction::TransferName { name, owner, new_owner } => {
+                format!("Transfer name '{}' from '{}' to '{}'", name, owner, new_owner)
+            }
+            Instruction::DeployProgram { owner, name, bytecode } => {
+                format!(
+                    "Deploy program '{}' for '{}' ({} bytes)",
+                    name,
+                    owner,
+                    bytecode.len()
+                )
+            }
+            Instruction::UpgradeProgram { program_id, authority, bytecode } => {
+                format!(
+                    "Upgrade program {} by '{}' ({} bytes)",
+                    program_id,
+                    authority,
+                    bytecode.len()
+                )
+            }
+            Instruction::SetUpgradeAuthority { program_id, authority, new_authority } => {
+                match new_authority {
+                    Some(new_authority) => format!(
+                        "Move upgrade authority of {} from '{}' to '{}'",
+                        program_id, authority, new_authority
+                    ),
+                    None => format!("Remove upgrade authority of {} by '{}'", program_id, authority),
+                }
+            }
+            Instruction::FinalizeProgram { program_id, authority } => {
+                format!("Finalize program {} by '{}'", program_id, authority)
+            }
+            Instruction::Invoke { program_id, accounts, data } => {
+                format!(
+                    "Invoke program {} with {} accounts and {} bytes of data",
+                    program_id,
+                    accounts.len(),
+                    data.len()
+                )
+            }
+            Instruction::CreateNonceAccount { funder, name, authority, amount } => {
+                format!(
+                    "Create nonce account '{}' for '{}' with {} from '{}'",
+                    name, authority, amount, funder
+                )
+            }
+            Instruction::AdvanceNonce { name, authority } => {
+                format!("Advance nonce '{}' by '{}'", name, authority)
+            }
+            Instruction::WithdrawNonce { name, authority, to, amount } => {
+                format!("Withdraw {} from nonce '{}' to '{}' by '{}'", amount, name, to, authority)
+            }
+        }
+    }
+
+    /// Name of the variant, e.g. "Transfer"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instruction::CreateAccount { .. } => "CreateAccount",
+            Instruction::Transfer { .. } => "Transfer",
+            Instruction::UpdateBalance { .. } => "UpdateBalance",
+            Instruction::GetBalance { .. } => "GetBalance",
+            Instruction::DeleteAccount { .. } => "DeleteAccount",
+            Instruction::Approve { .. } => "Approve",
+            Instruction::Revoke { .. } => "Revoke",
+            Instruction::TransferFrom { .. } => "TransferFrom",
+            Instruction::FreezeAccount { .. } => "FreezeAccount",
+            Instruction::ThawAccount { .. } => "ThawAccount",
+            Instruction::CreateMultisig { .. } => "CreateMultisig",
+            Instruction::SetAuthority { .. } => "SetAuthority",
+            Instruction::MultisigTransfer { .. } => "MultisigTransfer",
+            Instruction::CreateProgramAccount { .. } => "CreateProgramAccount",
+            Instruction::EscrowCreate { .. } => "EscrowCreate",
+            Instruction::EscrowRelease { .. } => "EscrowRelease",
+            Instruction::EscrowCancel { .. } => "EscrowCancel",
+            Instruction::CreateVesting { .. } => "CreateVesting",
+            Instruction::ClaimVested { .. } => "ClaimVested",
+            Instruction::CreateStake { .. } => "CreateStake",
+            Instruction::Delegate { .. } => "Delegate",
+            Instruction::Deactivate { .. } => "Deactivate",
+            Instruction::Withdraw { .. } => "Withdraw",
+            Instruction::SchedulePayment { .. } => "SchedulePayment",
+            Instruction::CreateMint { .. } => "CreateMint",
+            Instruction::MintTo { .. } => "MintTo",
+            Instruction::TransferToken { .. } => "TransferToken",
+            Instruction::CreatePool { .. } => "CreatePool",
+            Instruction::AddLiquidity { .. } => "AddLiquidity",
+            Instruction::RemoveLiquidity { .. } => "RemoveLiquidity",
+            Instruction::Swap { .. } => "Swap",
+            Instruction::RegisterName { .. } => "RegisterName",
+            Instruction::ResolveName { .. } => "ResolveName",
+            Instruction::TransferName { .. } => "TransferName",
+            Instruction::DeployProgram { .. } => "DeployProgram",
+            Instruction::UpgradeProgram { .. } => "UpgradeProgram",
+            Instruction::SetUpgradeAuthority { .. } => "SetUpgradeAuthority",
+            Instruction::FinalizeProgram { .. } => "FinalizeProgram",
+            Instruction::Invoke { .. } => "Invoke",
+            Instruction::CreateNonceAccount { .. } => "CreateNonceAccount",
+            Instruction::AdvanceNonce { .. } => "AdvanceNonce",
+            Instruction::WithdrawNonce { .. } => "WithdrawNonce",
         }
     }
 
@@ -149,6 +1344,314 @@ impl Instruction {
                 }
                 Ok(())
             }
+            Instruction::Approve { owner, delegate, amount } => {
+                if owner.is_empty() || delegate.is_empty() {
+                    return Err("Owner and delegate names can not be empty".to_string());
+                }
+                if owner == delegate {
+                    return Err("Cannot delegate to the same account".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Approved amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::Revoke { owner } => {
+                if owner.is_empty() {
+                    return Err("Account name cannot be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::TransferFrom { delegate, from, to, amount } => {
+                if delegate.is_empty() || from.is_empty() || to.is_empty() {
+                    return Err("Delegate, sender and receiver names can not be empty".to_string());
+                }
+                if from == to {
+                    return Err("Cannot transfer to the same account".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Transfer amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::FreezeAccount { authority, name }
+            | Instruction::ThawAccount { authority, name } => {
+                if authority.is_empty() || name.is_empty() {
+                    return Err("Authority and account names can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::CreateMultisig { name, signers, threshold } => {
+                if name.is_empty() {
+                    return Err("Multisig name cannot be empty".to_string());
+                }
+                if signers.iter().any(|signer| signer.is_empty()) {
+                    return Err("Signer names can not be empty".to_string());
+                }
+                if *threshold == 0 || *threshold as usize > signers.len() {
+                    return Err("Threshold must be between 1 and the number of signers".to_string());
+                }
+                Ok(())
+            }
+            Instruction::SetAuthority { name, authority, .. } => {
+                if name.is_empty() || authority.as_deref() == Some("") {
+                    return Err("Account and authority names can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::MultisigTransfer { from, to, amount, .. } => {
+                if from.is_empty() || to.is_empty() {
+                    return Err("Sender and receive names can not be empty".to_string());
+                }
+                if from == to {
+                    return Err("Cannot transfer to the same account".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Transfer amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::CreateProgramAccount { seeds, balance, authority, .. } => {
+                if authority.is_empty() {
+                    return Err("Authority name cannot be empty".to_string());
+                }
+                if seeds.is_empty() {
+                    return Err("Program account needs at least one seed".to_string());
+                }
+                if *balance == 0 {
+                    return Err("Initial balance must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::EscrowCreate { name, depositor, recipient, arbiter, amount, .. } => {
+                if name.is_empty() || depositor.is_empty() || recipient.is_empty() || arbiter.is_empty() {
+                    return Err("Escrow, depositor, recipient and arbiter names can not be empty".to_string());
+                }
+                if depositor == recipient {
+                    return Err("Cannot transfer to the same account".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Escrow amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::EscrowRelease { name, signer }
+            | Instruction::EscrowCancel { name, signer } => {
+                if name.is_empty() || signer.is_empty() {
+                    return Err("Escrow and signer names can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::CreateVesting { funder, beneficiary, total, cliff, duration, .. } => {
+                if funder.is_empty() || beneficiary.is_empty() {
+                    return Err("Funder and beneficiary names can not be empty".to_string());
+                }
+                if *total == 0 {
+                    return Err("Vesting total must be greater than 0".to_string());
+                }
+                if *duration == 0 || cliff > duration {
+                    return Err("Cliff must not exceed a non-zero duration".to_string());
+                }
+                Ok(())
+            }
+            Instruction::ClaimVested { beneficiary, amount } => {
+                if beneficiary.is_empty() {
+                    return Err("Account name cannot be empty".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Claim amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::CreateStake { staker, name, amount } => {
+                if staker.is_empty() || name.is_empty() {
+                    return Err("Staker and stake account names can not be empty".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Stake amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::Delegate { name, staker, validator } => {
+                if name.is_empty() || staker.is_empty() || validator.is_empty() {
+                    return Err("Stake, staker and validator names can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::Deactivate { name, staker } => {
+                if name.is_empty() || staker.is_empty() {
+                    return Err("Stake and staker names can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::Withdraw { name, staker, to, amount } => {
+                if name.is_empty() || staker.is_empty() || to.is_empty() {
+                    return Err("Stake, staker and receiver names can not be empty".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Withdraw amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::SchedulePayment { from, to, amount, interval, count } => {
+                if from.is_empty() || to.is_empty() {
+                    return Err("Sender and receive names can not be empty".to_string());
+                }
+                if from == to {
+                    return Err("Cannot transfer to the same account".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Transfer amount must be greater than 0".to_string());
+                }
+                if *interval == 0 || *count == 0 {
+                    return Err("Interval and count must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::CreateMint { symbol, authority } => {
+                if symbol.is_empty() || authority.is_empty() {
+                    return Err("Mint symbol and authority can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::MintTo { symbol, authority, to, amount } => {
+                if symbol.is_empty() || authority.is_empty() || to.is_empty() {
+                    return Err("Mint symbol, authority and receiver can not be empty".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Mint amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::TransferToken { symbol, from, to, amount } => {
+                if symbol.is_empty() || from.is_empty() || to.is_empty() {
+                    return Err("Mint symbol, sender and receiver can not be empty".to_string());
+                }
+                if from == to {
+                    return Err("Cannot transfer to the same account".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Transfer amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::CreatePool { name, mint_a, mint_b, .. } => {
+                if name.is_empty() || mint_a.is_empty() || mint_b.is_empty() {
+                    return Err("Pool name and mints can not be empty".to_string());
+                }
+                if mint_a == mint_b {
+                    return Err("Pool mints must be different".to_string());
+                }
+                Ok(())
+            }
+            Instruction::AddLiquidity { pool, provider, amount_a, amount_b, .. } => {
+                if pool.is_empty() || provider.is_empty() {
+                    return Err("Pool and provider names can not be empty".to_string());
+                }
+                if *amount_a == 0 || *amount_b == 0 {
+                    return Err("Deposit amounts must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::RemoveLiquidity { pool, provider, shares, .. } => {
+                if pool.is_empty() || provider.is_empty() {
+                    return Err("Pool and provider names can not be empty".to_string());
+                }
+                if *shares == 0 {
+                    return Err("Shares must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::Swap { pool, trader, mint_in, amount_in, .. } => {
+                if pool.is_empty() || trader.is_empty() || mint_in.is_empty() {
+                    return Err("Pool, trader and mint can not be empty".to_string());
+                }
+                if *amount_in == 0 {
+                    return Err("Swap amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::RegisterName { name, owner, target, duration } => {
+                if name.is_empty() || owner.is_empty() || target.is_empty() {
+                    return Err("Name, owner and target can not be empty".to_string());
+                }
+                if *duration == 0 {
+                    return Err("Registration duration must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::ResolveName { name } => {
+                if name.is_empty() {
+                    return Err("Name cannot be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::TransferName { name, owner, new_owner } => {
+                if name.is_empty() || owner.is_empty() || new_owner.is_empty() {
+                    return Err("Name, owner and new owner can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::DeployProgram { owner, name, bytecode } => {
+                if owner.is_empty() || name.is_empty() {
+                    return Err("Owner and program name can not be empty".to_string());
+                }
+                if bytecode.is_empty() {
+                    return Err("Bytecode cannot be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::UpgradeProgram { authority, bytecode, .. } => {
+                if authority.is_empty() {
+                    return Err("Upgrade authority cannot be empty".to_string());
+                }
+                if bytecode.is_empty() {
+                    return Err("Bytecode cannot be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::SetUpgradeAuthority { authority, new_authority, .. } => {
+                if authority.is_empty() || new_authority.as_ref().is_some_and(|name| name.is_empty()) {
+                    return Err("Upgrade authority cannot be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::FinalizeProgram { authority, .. } => {
+                if authority.is_empty() {
+                    return Err("Upgrade authority cannot be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::Invoke { accounts, .. } => {
+                if accounts.iter().any(|account| account.key.is_empty()) {
+                    return Err("Invoked accounts can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::CreateNonceAccount { funder, name, authority, amount } => {
+                if funder.is_empty() || name.is_empty() || authority.is_empty() {
+                    return Err("Funder, nonce and authority names can not be empty".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Nonce account funding must be greater than 0".to_string());
+                }
+                Ok(())
+            }
+            Instruction::AdvanceNonce { name, authority } => {
+                if name.is_empty() || authority.is_empty() {
+                    return Err("Nonce and authority names can not be empty".to_string());
+                }
+                Ok(())
+            }
+            Instruction::WithdrawNonce { name, authority, to, amount } => {
+                if name.is_empty() || authority.is_empty() || to.is_empty() {
+                    return Err("Nonce, authority and receiver names can not be empty".to_string());
+                }
+                if *amount == 0 {
+                    return Err("Withdraw amount must be greater than 0".to_string());
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -165,6 +1668,8 @@ impl std::fmt::Display for Instruction {
 #[cfg(test)]    
 mod tests {
     use crate::instruction::Instruction;
+    use crate::program::AccountMeta;
+    use crate::pubkey::Pubkey;
 
     #[test]
     fn test_create_account_instruction() {
@@ -232,5 +1737,35 @@ mod tests {
 
         let self_transfer = Instruction::transfer("Alice".to_string(), "Alice".to_string(), 100);
         assert!(self_transfer.validate().is_err());
+
+        let self_approve = Instruction::approve("Alice".to_string(), "Alice".to_string(), 100);
+        assert_eq!(
+            self_approve.validate().unwrap_err(),
+            "Cannot delegate to the same account"
+        );
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let instructions = [
+            Instruction::transfer("Alice".into(), "Bob".into(), 100),
+            Instruction::set_upgrade_authority(Pubkey::new([7; 32]), "Dev".into(), None),
+            Instruction::invoke(
+                Pubkey::new([1; 32]),
+                vec![AccountMeta::new("Alice".into(), true), AccountMeta::new_readonly("Bob".into(), false)],
+                vec![1, 2, 3],
+            ),
+            Instruction::withdraw_nonce("Cold".into(), "Alice".into(), "Bob".into(), 5),
+        ];
+        for instruction in instructions {
+            let bytes = instruction.encode();
+            assert_eq!(Instruction::decode(&bytes).unwrap().encode(), bytes);
+        }
+
+        assert!(Instruction::decode(&[]).is_err());
+        assert!(Instruction::decode(&[255]).is_err());
+        let mut trailing = Instruction::advance_nonce("Cold".into(), "Alice".into()).encode();
+        trailing.push(0);
+        assert!(Instruction::decode(&trailing).is_err());
     }
 }
@@ -0,0 +1,148 @@
+// ============================================
+// JOURNALED MAPS
+// ============================================
+// A map that remembers what each key held before its first write since a
+// checkpoint, so the state can undo a failed program call or transaction by
+// putting those entries back, at a cost of the keys written rather than a
+// copy of everything. Reads go straight to the map; every write goes through
+// the journal.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Deref;
+
+/// A `HashMap` whose writes can be undone back to a checkpoint
+#[derive(Debug, Clone)]
+pub struct JournaledMap<K, V> {
+    map: HashMap<K, V>,
+    checkpoints: Vec<HashMap<K, Option<V>>>, // Entries before their first write, innermost checkpoint last
+}
+
+impl<K, V> Default for JournaledMap<K, V> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> JournaledMap<K, V> {
+    /// Start keeping what written keys held. Checkpoints nest; each is
+    /// closed by `commit` or `rollback`.
+    pub fn checkpoint(&mut self) {
+        self.checkpoints.push(HashMap::new());
+    }
+
+    /// Keep the writes made since the innermost checkpoint
+    pub fn commit(&mut self) {
+        let Some(entries) = self.checkpoints.pop() else {
+            return;
+        };
+        // An enclosing checkpoint needs what a key held when it was taken,
+        // which is what it held here unless that checkpoint saw it already
+        if let Some(outer) = self.checkpoints.last_mut() {
+            for (key, value) in entries {
+                outer.entry(key).or_insert(value);
+            }
+        }
+    }
+
+    /// Undo the writes made since the innermost checkpoint; returns the keys
+    /// put back
+    pub fn rollback(&mut self) -> Vec<K> {
+        let Some(entries) = self.checkpoints.pop() else {
+            return Vec::new();
+        };
+        let mut keys = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            match value {
+                Some(value) => self.map.insert(key.clone(), value),
+                None => self.map.remove(&key),
+            };
+            keys.push(key);
+        }
+        keys
+    }
+
+    /// Keep what `key` holds, if a checkpoint is open and has not seen it
+    fn note<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        if let Some(entries) = self.checkpoints.last_mut()
+            && !entries.contains_key(key)
+        {
+            entries.insert(key.to_owned(), self.map.get(key).cloned());
+        }
+    }
+
+    /// Insert or replace the value under `key`
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.note(&key);
+        self.map.insert(key, value)
+    }
+
+    /// Get the value under `key` to change it
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        self.note(key);
+        self.map.get_mut(key)
+    }
+
+    /// Remove the value under `key`
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        self.note(key);
+        self.map.remove(key)
+    }
+}
+
+impl<K, V> Deref for JournaledMap<K, V> {
+    type Target = HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_rollback_and_commit() {
+        let mut map: JournaledMap<String, u64> = JournaledMap::default();
+        map.insert("a".to_string(), 1);
+        map.insert("b".to_string(), 2);
+
+        map.checkpoint();
+        map.insert("a".to_string(), 10);
+        map.checkpoint();
+        *map.get_mut("b").unwrap() = 20;
+        map.insert("c".to_string(), 30);
+        map.rollback();
+        assert_eq!((map.get("a"), map.get("b"), map.get("c")), (Some(&10), Some(&2), None));
+
+        // Committing hands the inner entries to the outer checkpoint
+        map.checkpoint();
+        map.remove("b");
+        map.insert("a".to_string(), 100);
+        map.commit();
+        map.rollback();
+        assert_eq!((map.get("a"), map.get("b"), map.len()), (Some(&1), Some(&2), 2));
+
+        // Without a checkpoint nothing is kept
+        map.insert("a".to_string(), 5);
+        map.rollback();
+        assert_eq!(map.get("a"), Some(&5));
+    }
+}
@@ -0,0 +1,224 @@
+// ============================================
+// KEYPAIRS AND SIGNATURES
+// ============================================
+// Ed25519 (RFC 8032) keypairs. An account named after a public key (its
+// base58 string) is a keypair account: transactions acting for it must carry
+// a signature from that key. Accounts with plain names like "Alice" predate
+// keypairs and are not checked.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher as _};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::codec::{Decode, Decoder, Encode};
+use crate::curve::{self, Point};
+use crate::error::{AppError, Result};
+use crate::hash;
+use crate::pubkey::{self, Pubkey};
+
+/// Length of a signature in bytes
+pub const SIGNATURE_BYTES: usize = 64;
+
+/// An ed25519 signature: R (a compressed point) followed by S (a scalar)
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Signature([u8; SIGNATURE_BYTES]);
+
+impl Signature {
+    pub const fn new(bytes: [u8; SIGNATURE_BYTES]) -> Self {
+        Signature(bytes)
+    }
+
+    pub fn to_bytes(self) -> [u8; SIGNATURE_BYTES] {
+        self.0
+    }
+
+    /// Check that `pubkey`'s owner signed `message`
+    pub fn verify(&self, pubkey: &Pubkey, message: &[u8]) -> bool {
+        let (r, s) = self.0.split_at(32);
+        let (r, s): ([u8; 32], [u8; 32]) = (r.try_into().expect("32 bytes"), s.try_into().expect("32 bytes"));
+        let (Some(public), Some(r_point)) = (Point::decompress(&pubkey.to_bytes()), Point::decompress(&r)) else {
+            return false;
+        };
+        if !curve::is_canonical_scalar(&s) {
+            return false;
+        }
+
+        // S B = R + H(R, A, M) A
+        let k = curve::reduce_scalar(&hash::sha512v(&[&r, pubkey.as_ref(), message]));
+        Point::base().mul(&s) == r_point.add(&public.mul(&k))
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", pubkey::base58_encode(&self.0))
+    }
+}
+
+impl fmt::Debug for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", pubkey::base58_encode(&self.0))
+    }
+}
+
+impl FromStr for Signature {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        pubkey::base58_decode(s)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(Signature)
+            .ok_or_else(|| AppError::InvalidSignature(s.to_string()))
+    }
+}
+
+impl Encode for Signature {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+    }
+}
+
+impl Decode for Signature {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Signature(decoder.get()?))
+    }
+}
+
+/// An ed25519 signing key and its public key
+#[derive(Clone)]
+pub struct Keypair {
+    seed: [u8; 32],
+    scalar: [u8; 32], // Clamped secret scalar a
+    prefix: [u8; 32], // Second half of the expanded key, for deterministic nonces
+    pubkey: Pubkey,
+}
+
+impl Keypair {
+    /// Derive the keypair for a 32-byte secret seed
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let expanded = hash::sha512v(&[&seed]);
+        let mut scalar: [u8; 32] = expanded[..32].try_into().expect("32 bytes");
+        scalar[0] &= 248;
+        scalar[31] &= 127;
+        scalar[31] |= 64;
+        Self {
+            seed,
+            scalar,
+            prefix: expanded[32..].try_into().expect("32 bytes"),
+            pubkey: Pubkey::new(Point::base().mul(&scalar).compress()),
+        }
+    }
+
+    /// A fresh keypair. The seed comes from the standard library's randomly
+    /// keyed hasher and the clock: fine for a devnet, not for real funds.
+    pub fn generate() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos());
+        let mut entropy = Vec::new();
+        for index in 0..4u8 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(nanos);
+            hasher.write_u8(index);
+            entropy.extend_from_slice(&hasher.finish().to_le_bytes());
+        }
+        Self::from_seed(hash::sha256(&entropy))
+    }
+
+    /// Secret seed, enough to recreate the keypair with `from_seed`
+    pub fn seed(&self) -> [u8; 32] {
+        self.seed
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    /// Name of the account this keypair controls
+    pub fn account_name(&self) -> String {
+        self.pubkey.to_string()
+    }
+
+    /// Sign `message`; the same message always gives the same signature
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        let r = curve::reduce_scalar(&hash::sha512v(&[&self.prefix, message]));
+        let r_point = Point::base().mul(&r).compress();
+        let k = curve::reduce_scalar(&hash::sha512v(&[&r_point, self.pubkey.as_ref(), message]));
+        let s = curve::scalar_mul_add(&k, &self.scalar, &r);
+
+        let mut signature = [0; SIGNATURE_BYTES];
+        signature[..32].copy_from_slice(&r_point);
+        signature[32..].copy_from_slice(&s);
+        Signature(signature)
+    }
+}
+
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the secret
+        write!(f, "Keypair({})", self.pubkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex<const N: usize>(hex: &str) -> [u8; N] {
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        bytes.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_rfc8032_vectors() {
+        // Test 1: empty message
+        let keypair = Keypair::from_seed(from_hex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"));
+        assert_eq!(
+            keypair.pubkey().to_bytes(),
+            from_hex::<32>("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+        );
+        let signature = keypair.sign(b"");
+        assert_eq!(
+            signature.to_bytes(),
+            from_hex::<64>(
+                "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+            )
+        );
+        assert!(signature.verify(&keypair.pubkey(), b""));
+
+        // Test 2: one byte
+        let keypair = Keypair::from_seed(from_hex("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb"));
+        let signature = keypair.sign(&[0x72]);
+        assert_eq!(
+            signature.to_bytes(),
+            from_hex::<64>(
+                "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00"
+            )
+        );
+        assert!(signature.verify(&keypair.pubkey(), &[0x72]));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let keypair = Keypair::generate();
+        let other = Keypair::generate();
+        assert_ne!(keypair.pubkey(), other.pubkey());
+
+        let signature = keypair.sign(b"pay Bob 10");
+        assert!(signature.verify(&keypair.pubkey(), b"pay Bob 10"));
+        assert!(!signature.verify(&keypair.pubkey(), b"pay Bob 99"));
+        assert!(!signature.verify(&other.pubkey(), b"pay Bob 10"));
+
+        let mut bytes = signature.to_bytes();
+        bytes[40] ^= 1;
+        assert!(!Signature::new(bytes).verify(&keypair.pubkey(), b"pay Bob 10"));
+
+        // Round trips through text and the seed
+        assert_eq!(signature.to_string().parse::<Signature>().unwrap(), signature);
+        assert_eq!(Keypair::from_seed(keypair.seed()).pubkey(), keypair.pubkey());
+        assert!(!format!("{:?}", keypair).contains(&hash::to_hex(&keypair.seed())));
+    }
+}
@@ -0,0 +1,54 @@
+// Rust Basic and Stateless Instruction Exercise, as a library.
+//
+// The public API is the account state, the instructions and the processor
+// that applies them, their errors, and the ledger, transactions, keypairs,
+// servers and client built on top. The built-in programs, encodings and
+// cryptography behind them are internal; the records they keep are exported
+// from `state`. The demo in main.rs is one consumer.
+
+pub mod block;
+pub mod client;
+pub mod error;
+pub mod hash;
+pub mod instruction;
+pub mod json;
+pub mod keypair;
+pub mod processor;
+pub mod program;
+pub mod pubkey;
+pub mod pubsub;
+pub mod rpc;
+pub mod state;
+pub mod transaction;
+pub mod vm;
+
+mod amm;
+mod blockhash;
+mod codec;
+mod curve;
+mod escrow;
+mod history;
+mod index;
+mod journal;
+mod loader;
+mod mempool;
+mod merkle;
+mod multisig;
+mod names;
+mod nonce;
+mod schedule;
+mod stake;
+mod system_program;
+mod token;
+mod vesting;
+mod websocket;
+
+pub use block::{Block, BlockHeader, Ledger};
+pub use client::RpcClient;
+pub use error::{AppError, Result};
+pub use instruction::Instruction;
+pub use keypair::{Keypair, Signature};
+pub use processor::{Processor, process_instruction, process_transaction};
+pub use pubkey::Pubkey;
+pub use state::{Account, AppState};
+pub use transaction::Transaction;
@@ -0,0 +1,135 @@
+// ============================================
+// UPGRADEABLE LOADER
+// ============================================
+// Deployed programs live in two loader-owned accounts, as with Solana's
+// upgradeable loader: the executable program account, whose data is the
+// address of its program data account, and the program data account, which
+// holds the upgrade authority, version and bytecode. Upgrades replace the
+// program data so the program ID never changes; dropping the authority makes
+// the program immutable.
+
+use crate::error::{AppError, Result};
+use crate::hash;
+use crate::pubkey::{Pubkey, PUBKEY_BYTES};
+
+/// Program that owns every deployed program and its program data
+pub const LOADER_PROGRAM_ID: Pubkey = Pubkey::new(*b"BytecodeLoader111111111111111111");
+
+/// Contents of a program data account
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramData {
+    pub upgrade_authority: Option<String>, // None once finalized
+    pub version: u32,                      // 1 on deploy, +1 per upgrade
+    pub deployed_slot: u64,                // Slot of the latest deploy or upgrade
+    pub bytecode: Vec<u8>,
+}
+
+impl ProgramData {
+    /// Program data for a fresh deploy
+    pub fn new(upgrade_authority: String, slot: u64, bytecode: Vec<u8>) -> Self {
+        Self {
+            upgrade_authority: Some(upgrade_authority),
+            version: 1,
+            deployed_slot: slot,
+            bytecode,
+        }
+    }
+
+    /// Address of the program data account of `program_id`
+    pub fn address(program_id: &Pubkey) -> Result<Pubkey> {
+        Pubkey::find_program_address(&[program_id.as_ref()], &LOADER_PROGRAM_ID).map(|(address, _)| address)
+    }
+
+    /// Check that `signer` may upgrade the program
+    pub fn check_authority(&self, program_id: &Pubkey, signer: &str) -> Result<()> {
+        match &self.upgrade_authority {
+            None => Err(AppError::ProgramImmutable(program_id.to_string())),
+            Some(authority) if authority != signer => Err(AppError::Unauthorized(signer.to_string())),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Replace the bytecode and bump the version
+    pub fn upgrade(&mut self, bytecode: Vec<u8>, slot: u64) {
+        self.bytecode = bytecode;
+        self.version += 1;
+        self.deployed_slot = slot;
+    }
+
+    /// Encode as account data: version, slot, authority length and bytes, then bytecode
+    pub fn pack(&self) -> Vec<u8> {
+        let authority = self.upgrade_authority.as_deref().unwrap_or_default();
+        let mut data = Vec::with_capacity(13 + authority.len() + self.bytecode.len());
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data.extend_from_slice(&self.deployed_slot.to_le_bytes());
+        data.push(authority.len() as u8);
+        data.extend_from_slice(authority.as_bytes());
+        data.extend_from_slice(&self.bytecode);
+        data
+    }
+
+    /// Decode account data written by `pack`
+    pub fn unpack(data: &[u8]) -> Result<Self> {
+        let corrupt = || AppError::InvalidInstructionData("corrupt program data".to_string());
+        if data.len() < 13 {
+            return Err(corrupt());
+        }
+        let version = u32::from_le_bytes(data[0..4].try_into().map_err(|_| corrupt())?);
+        let deployed_slot = u64::from_le_bytes(data[4..12].try_into().map_err(|_| corrupt())?);
+        let authority_end = 13 + data[12] as usize;
+        let authority = data.get(13..authority_end).ok_or_else(corrupt)?;
+        let authority = String::from_utf8(authority.to_vec()).map_err(|_| corrupt())?;
+
+        Ok(Self {
+            upgrade_authority: (!authority.is_empty()).then_some(authority),
+            version,
+            deployed_slot,
+            bytecode: data[authority_end..].to_vec(),
+        })
+    }
+}
+
+/// ID of the program `owner` deploys as `name`. Both names are hashed, so
+/// names longer than a seed still fit.
+pub fn program_id(owner: &str, name: &str) -> Result<Pubkey> {
+    let (owner, name) = (hash::sha256(owner.as_bytes()), hash::sha256(name.as_bytes()));
+    Pubkey::find_program_address(&[b"program", &owner, &name], &LOADER_PROGRAM_ID).map(|(id, _)| id)
+}
+
+/// Read the program data address stored in a program account
+pub fn program_data_address(program_account_data: &[u8]) -> Result<Pubkey> {
+    let bytes: [u8; PUBKEY_BYTES] = program_account_data
+        .try_into()
+        .map_err(|_| AppError::InvalidInstructionData("corrupt program account".to_string()))?;
+    Ok(Pubkey::new(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_roundtrip() {
+        let mut data = ProgramData::new("Dev".into(), 42, vec![1, 2, 3]);
+        assert_eq!(ProgramData::unpack(&data.pack()).unwrap(), data);
+
+        data.upgrade(vec![4; 16], 50);
+        data.upgrade_authority = None;
+        let unpacked = ProgramData::unpack(&data.pack()).unwrap();
+        assert_eq!(unpacked.version, 2);
+        assert_eq!(unpacked, data);
+
+        assert!(ProgramData::unpack(&[0; 12]).is_err());
+        assert!(ProgramData::unpack(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, b'a']).is_err());
+    }
+
+    #[test]
+    fn test_check_authority() {
+        let id = Pubkey::new([1; 32]);
+        let mut data = ProgramData::new("Dev".into(), 0, vec![]);
+        assert!(data.check_authority(&id, "Dev").is_ok());
+        assert!(matches!(data.check_authority(&id, "Eve"), Err(AppError::Unauthorized(_))));
+        data.upgrade_authority = None;
+        assert!(matches!(data.check_authority(&id, "Dev"), Err(AppError::ProgramImmutable(_))));
+    }
+}
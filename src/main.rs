@@ -1,19 +1,89 @@
 // Rust Basic and Stateless Instruction Exercise
-// Module declarations
-mod instruction;
-mod processor;
-mod state;
-mod error;
+// Demo of the library: runs a few instructions through a ledger, then
+// optionally keeps serving it over JSON-RPC and WebSocket.
 
-use instruction::Instruction;
-use processor::process_instruction;
-use state::AppState;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use solana101::block::Ledger;
+use solana101::hash;
+use solana101::instruction::Instruction;
+use solana101::keypair::Keypair;
+use solana101::pubsub::{self, PubSubServer};
+use solana101::rpc::{self, RpcServer};
+use solana101::state::AppState;
+use solana101::transaction::Transaction;
+
+/// Time between blocks while serving RPC
+const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+/// Value following `flag` on the command line
+fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == flag)?;
+    args.get(index + 1).cloned()
+}
+
+/// Read `--slots N` from the command line: extra empty slots to produce after the demo
+fn slots_from_args() -> u64 {
+    arg_value("--slots").and_then(|value| value.parse().ok()).unwrap_or(0)
+}
+
+/// Read `--allow-origins A,B` from the command line: browser origins that may call the RPC server
+fn allowed_origins_from_args() -> Vec<String> {
+    arg_value("--allow-origins")
+        .map(|value| value.split(',').map(|origin| origin.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Serve JSON-RPC and/or WebSocket subscriptions, producing a block every
+/// SLOT_DURATION and notifying subscribers after each one
+fn serve(ledger: Ledger, rpc_addr: Option<String>, ws_addr: Option<String>) {
+    let ledger = Arc::new(Mutex::new(ledger));
+    if let Some(addr) = rpc_addr {
+        match RpcServer::bind(&addr, Arc::clone(&ledger)) {
+            Ok(server) => {
+                let server = server.with_allowed_origins(allowed_origins_from_args());
+                if let Ok(addr) = server.local_addr() {
+                    println!("\n=== JSON-RPC listening on http://{} ===", addr);
+                }
+                server.spawn();
+            }
+            Err(e) => println!("✗ Cannot listen on {}: {}", addr, e),
+        }
+    }
+    let mut subscriptions = None;
+    if let Some(addr) = ws_addr {
+        match PubSubServer::bind(&addr, Arc::clone(&ledger)) {
+            Ok(server) => {
+                if let Ok(addr) = server.local_addr() {
+                    println!("=== WebSocket subscriptions on ws://{} ===", addr);
+                }
+                subscriptions = Some(server.subscriptions());
+                server.spawn();
+            }
+            Err(e) => println!("✗ Cannot listen on {}: {}", addr, e),
+        }
+    }
+
+    loop {
+        std::thread::sleep(SLOT_DURATION);
+        let mut ledger = rpc::lock(&ledger);
+        let block = ledger.produce_block();
+        if !block.transactions.is_empty() {
+            println!("Slot {:>3}  {} transactions", block.header.slot, block.transactions.len());
+        }
+        if let Some(subscriptions) = &subscriptions {
+            pubsub::lock(subscriptions).notify(&ledger);
+        }
+    }
+}
 
 fn main() {
     println!("=== RUST BASIC AND STATELESS INSTRUCTION ===\n");
 
     // Initialize initial state
-    let mut app_state = AppState::new();
+    let app_state = AppState::new();
 
     println!("Initial state:");
     app_state.display();
@@ -45,20 +115,86 @@ fn main() {
         },
     ];
 
-    // Process each instruction
+    // Submit each instruction as its own transaction and pack them into a block
     println!("\n=== PROCESSING INSTRUCTIONS ===\n");
-    for (index, instruction) in instructions.iter().enumerate() {
-        println!("Instruction #{}: {:?}", index + 1, instruction);
+    let mut ledger = Ledger::new(app_state);
+    let transactions: Vec<_> = instructions
+        .into_iter()
+        .map(|instruction| Transaction::new(vec![instruction], ledger.recent_blockhash()))
+        .collect();
+    for transaction in &transactions {
+        if let Err(e) = ledger.submit(transaction.clone()) {
+            println!("✗ Rejected: {}", e);
+        }
+    }
+    // Submitting the transfer again is caught as a replay
+    if let Err(e) = ledger.submit(transactions[2].clone()) {
+        println!("Replaying instruction #3: ✗ Rejected: {}\n", e);
+    }
+    let block = ledger.produce_block();
+
+    for (index, (transaction, result)) in block.transactions.iter().zip(&block.results).enumerate() {
+        println!("Instruction #{}: {:?}", index + 1, transaction.instructions[0]);
 
-        match process_instruction(instruction, &mut app_state) {
-            Ok(message) => println!("✓ Success: {}", message),
+        match result {
+            Ok(messages) => println!("✓ Success: {}", messages.join("; ")),
             Err(e) => println!("✗ Error: {}", e),
         }
 
         println!();
     }
 
-    // Display final state
-    println!("=== FINAL STATE ===");
-    app_state.display();
+    // Accounts named after a public key only act with that key's signature
+    println!("=== SIGNED TRANSACTIONS ===\n");
+    let carol = Keypair::from_seed(hash::sha256(b"carol"));
+    let open = Transaction::new(
+        vec![Instruction::create_account(carol.account_name(), 100)],
+        ledger.recent_blockhash(),
+    );
+    let pay = Transaction::new(
+        vec![Instruction::transfer(carol.account_name(), String::from("Bob"), 40)],
+        ledger.recent_blockhash(),
+    );
+    println!("Carol's account: {}", carol.account_name());
+    if let Err(e) = ledger.submit(pay.clone()) {
+        println!("Unsigned transfer: ✗ Rejected: {}", e);
+    }
+    for transaction in [open.sign(&[&carol]), pay.sign(&[&carol])] {
+        if let Err(e) = ledger.submit(transaction) {
+            println!("✗ Rejected: {}", e);
+        }
+    }
+    let block = ledger.produce_block();
+    for result in &block.results {
+        match result {
+            Ok(messages) => println!("Signed: ✓ Success: {}", messages.join("; ")),
+            Err(e) => println!("Signed: ✗ Error: {}", e),
+        }
+    }
+    println!();
+
+    ledger.advance_slots(slots_from_args());
+
+    // Display the chain and final state
+    println!("=== BLOCKS ===");
+    for block in ledger.blocks() {
+        println!(
+            "Slot {:>3}  hash {}  parent {}  txs {}",
+            block.header.slot,
+            &hash::to_hex(&block.hash)[..16],
+            &hash::to_hex(&block.header.parent_hash)[..16],
+            block.transactions.len()
+        );
+    }
+
+    println!("\n=== FINAL STATE ===");
+    ledger.state().display();
+
+    // `--rpc 127.0.0.1:8899` keeps the chain running behind a JSON-RPC
+    // endpoint, `--ws 127.0.0.1:8900` behind WebSocket subscriptions;
+    // `--allow-origins http://localhost:3000` lets that page call the RPC
+    let (rpc_addr, ws_addr) = (arg_value("--rpc"), arg_value("--ws"));
+    if rpc_addr.is_some() || ws_addr.is_some() {
+        serve(ledger, rpc_addr, ws_addr);
+    }
 }
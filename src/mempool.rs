@@ -0,0 +1,195 @@
+// ============================================
+// MEMPOOL
+// ============================================
+// Pending transactions wait here until the block producer takes them, highest
+// priority fee first and in arrival order among equal fees. Duplicates are
+// refused, and once the pool is full a new transaction evicts the cheapest
+// pending one only if it bids more. The pool also remembers what became of
+// recent transactions so clients can poll their status.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::transaction::Transaction;
+
+/// Pending transactions kept before the cheapest are evicted
+pub const MAX_MEMPOOL_SIZE: usize = 10_000;
+/// Finished (processed or dropped) transactions whose status is remembered
+pub const MAX_TRACKED_STATUSES: usize = 100_000;
+
+/// What became of a submitted transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionStatus {
+    Pending,
+    Processed { slot: u64, result: std::result::Result<(), AppError> },
+    Dropped(AppError), // Never executed: expired, replayed or evicted
+}
+
+/// Scheduling order: highest fee first, then earliest arrival
+type Priority = (Reverse<u64>, u64, Hash);
+
+/// Pool of pending transactions ordered by priority
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    pending: HashMap<Hash, (Priority, Transaction)>,
+    queue: BTreeSet<Priority>,
+    statuses: HashMap<Hash, TransactionStatus>,
+    finished: VecDeque<Hash>, // Finished transactions, oldest first, for forgetting statuses
+    next_arrival: u64,
+    capacity: usize,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(MAX_MEMPOOL_SIZE)
+    }
+}
+
+impl Mempool {
+    /// Pool holding at most `capacity` pending transactions
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pending: HashMap::new(),
+            queue: BTreeSet::new(),
+            statuses: HashMap::new(),
+            finished: VecDeque::new(),
+            next_arrival: 0,
+            capacity,
+        }
+    }
+
+    /// Number of pending transactions
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Status of transaction `hash`, if it is pending or finished recently
+    pub fn status(&self, hash: &Hash) -> Option<&TransactionStatus> {
+        self.statuses.get(hash)
+    }
+
+    /// Add a transaction; returns its hash. A transaction that is pending or
+    /// was processed is refused; a dropped one may be submitted again.
+    pub fn insert(&mut self, transaction: Transaction) -> Result<Hash> {
+        let hash = transaction.hash();
+        match self.statuses.get(&hash) {
+            Some(TransactionStatus::Pending | TransactionStatus::Processed { .. }) => {
+                return Err(AppError::AlreadyProcessed(hash::to_hex(&hash)));
+            }
+            // Resubmitted after being dropped: it is no longer finished
+            Some(TransactionStatus::Dropped(_)) => self.finished.retain(|finished| *finished != hash),
+            None => {}
+        }
+
+        if self.pending.len() >= self.capacity {
+            // The last entry is the cheapest, and the newest among equals
+            let cheapest = match self.queue.last() {
+                Some(&(Reverse(fee), _, cheapest)) if fee < transaction.priority_fee => cheapest,
+                _ => return Err(AppError::MempoolFull(self.capacity)),
+            };
+            self.remove(&cheapest);
+            self.finish(cheapest, TransactionStatus::Dropped(AppError::MempoolFull(self.capacity)));
+        }
+
+        let priority = (Reverse(transaction.priority_fee), self.next_arrival, hash);
+        self.next_arrival += 1;
+        self.queue.insert(priority);
+        self.pending.insert(hash, (priority, transaction));
+        self.statuses.insert(hash, TransactionStatus::Pending);
+        Ok(hash)
+    }
+
+    /// Take up to `max` transactions in priority order. They stay `Pending`
+    /// until marked processed or dropped.
+    pub fn take_batch(&mut self, max: usize) -> Vec<Transaction> {
+        let mut batch = Vec::new();
+        while batch.len() < max {
+            let Some((_, _, hash)) = self.queue.pop_first() else {
+                break;
+            };
+            if let Some((_, transaction)) = self.pending.remove(&hash) {
+                batch.push(transaction);
+            }
+        }
+        batch
+    }
+
+    /// Record that `hash` ran in the block at `slot`
+    pub fn mark_processed(&mut self, hash: Hash, slot: u64, result: std::result::Result<(), AppError>) {
+        self.finish(hash, TransactionStatus::Processed { slot, result });
+    }
+
+    /// Record that `hash` was discarded without running
+    pub fn mark_dropped(&mut self, hash: Hash, reason: AppError) {
+        self.remove(&hash);
+        self.finish(hash, TransactionStatus::Dropped(reason));
+    }
+
+    fn remove(&mut self, hash: &Hash) {
+        if let Some((priority, _)) = self.pending.remove(hash) {
+            self.queue.remove(&priority);
+        }
+    }
+
+    /// Store a final status, forgetting the oldest ones past MAX_TRACKED_STATUSES
+    fn finish(&mut self, hash: Hash, status: TransactionStatus) {
+        self.statuses.insert(hash, status);
+        self.finished.push_back(hash);
+        while self.finished.len() > MAX_TRACKED_STATUSES {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.statuses.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction;
+
+    fn transfer(amount: u64, fee: u64) -> Transaction {
+        Transaction::new(vec![Instruction::transfer("Alice".into(), "Bob".into(), amount)], [0; 32])
+            .with_priority_fee(fee)
+    }
+
+    #[test]
+    fn test_priority_order_and_dedup() {
+        let mut pool = Mempool::new(10);
+        let low = pool.insert(transfer(1, 0)).unwrap();
+        let high = pool.insert(transfer(2, 50)).unwrap();
+        let also_low = pool.insert(transfer(3, 0)).unwrap();
+        assert!(matches!(pool.insert(transfer(2, 50)), Err(AppError::AlreadyProcessed(_))));
+        assert_eq!(pool.len(), 3);
+
+        let batch: Vec<_> = pool.take_batch(2).iter().map(Transaction::hash).collect();
+        assert_eq!(batch, [high, low]);
+        assert_eq!(pool.status(&high), Some(&TransactionStatus::Pending));
+        pool.mark_processed(high, 1, Ok(()));
+        pool.mark_dropped(low, AppError::BlockhashNotFound("".into()));
+        assert!(matches!(pool.status(&high), Some(TransactionStatus::Processed { slot: 1, .. })));
+
+        // Processed transactions stay refused; dropped ones may come back
+        assert!(pool.insert(transfer(2, 50)).is_err());
+        assert_eq!(pool.insert(transfer(1, 0)).unwrap(), low);
+        let batch: Vec<_> = pool.take_batch(10).iter().map(Transaction::hash).collect();
+        assert_eq!(batch, [also_low, low]);
+        assert_eq!(pool.len(), 0);
+    }
+
+    #[test]
+    fn test_eviction_when_full() {
+        let mut pool = Mempool::new(2);
+        let cheap = pool.insert(transfer(1, 1)).unwrap();
+        pool.insert(transfer(2, 5)).unwrap();
+        // Not enough to evict anything
+        assert!(matches!(pool.insert(transfer(3, 1)), Err(AppError::MempoolFull(2))));
+
+        let rich = pool.insert(transfer(4, 9)).unwrap();
+        assert_eq!(pool.len(), 2);
+        assert!(matches!(pool.status(&cheap), Some(TransactionStatus::Dropped(AppError::MempoolFull(_)))));
+        assert_eq!(pool.take_batch(1)[0].hash(), rich);
+    }
+}
@@ -0,0 +1,213 @@
+// ============================================
+// SPARSE MERKLE TREE
+// ============================================
+// A binary tree of depth 256 addressed by the bits of a 32-byte key. Only
+// non-empty nodes are stored; an empty subtree hashes to all zeros, so an
+// update rehashes just the 256 nodes on its path and proofs only need the
+// siblings that are not empty. Absent keys can be proven absent.
+
+use std::collections::HashMap;
+
+use crate::hash::{self, Hash, HASH_BYTES};
+
+/// Levels between a leaf and the root
+pub const TREE_DEPTH: usize = HASH_BYTES * 8;
+/// Hash of an empty subtree of any height
+pub const EMPTY: Hash = [0; HASH_BYTES];
+
+/// Hash of a leaf holding `value` under `key`
+fn leaf_hash(key: &Hash, value: &Hash) -> Hash {
+    hash::hashv(&[&[0], key, value])
+}
+
+/// Hash of an inner node; two empty children make an empty node
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    if *left == EMPTY && *right == EMPTY {
+        return EMPTY;
+    }
+    hash::hashv(&[&[1], left, right])
+}
+
+/// Bit `index` of `key`, most significant first
+fn bit(key: &Hash, index: usize) -> bool {
+    key[index / 8] >> (7 - index % 8) & 1 == 1
+}
+
+/// `key` with the bits below the node at `height` cleared
+fn prefix(key: &Hash, height: usize) -> Hash {
+    let keep = TREE_DEPTH - height;
+    let mut prefix = [0; HASH_BYTES];
+    prefix[..keep / 8].copy_from_slice(&key[..keep / 8]);
+    if !keep.is_multiple_of(8) {
+        prefix[keep / 8] = key[keep / 8] & (0xff << (8 - keep % 8));
+    }
+    prefix
+}
+
+/// Key of the sibling of the node at `height` on the path of `key`
+fn sibling(key: &Hash, height: usize) -> Hash {
+    let mut sibling = prefix(key, height);
+    let index = TREE_DEPTH - 1 - height;
+    sibling[index / 8] ^= 1 << (7 - index % 8);
+    sibling
+}
+
+/// Sparse Merkle tree of 32-byte keys to 32-byte value hashes
+#[derive(Debug, Clone, Default)]
+pub struct SparseMerkleTree {
+    nodes: HashMap<(usize, Hash), Hash>, // (height, prefix) -> hash; empty nodes are not stored
+}
+
+impl SparseMerkleTree {
+    /// Current root
+    pub fn root(&self) -> Hash {
+        self.node(TREE_DEPTH, &EMPTY)
+    }
+
+    fn node(&self, height: usize, prefix: &Hash) -> Hash {
+        self.nodes.get(&(height, *prefix)).copied().unwrap_or(EMPTY)
+    }
+
+    fn set_node(&mut self, height: usize, prefix: Hash, node: Hash) {
+        if node == EMPTY {
+            self.nodes.remove(&(height, prefix));
+        } else {
+            self.nodes.insert((height, prefix), node);
+        }
+    }
+
+    /// Set the value under `key`, or clear it with `None`. Rehashes the
+    /// `TREE_DEPTH` nodes on the key's path.
+    pub fn update(&mut self, key: &Hash, value: Option<&Hash>) {
+        let mut node = value.map_or(EMPTY, |value| leaf_hash(key, value));
+        self.set_node(0, *key, node);
+        for height in 0..TREE_DEPTH {
+            let sibling = self.node(height, &sibling(key, height));
+            node = if bit(key, TREE_DEPTH - 1 - height) {
+                node_hash(&sibling, &node)
+            } else {
+                node_hash(&node, &sibling)
+            };
+            self.set_node(height + 1, prefix(key, height + 1), node);
+        }
+    }
+
+    /// Proof for whatever is stored under `key`, present or not
+    pub fn prove(&self, key: &Hash) -> MerkleProof {
+        let mut proof = MerkleProof {
+            bitmap: [0; HASH_BYTES],
+            siblings: Vec::new(),
+        };
+        for height in 0..TREE_DEPTH {
+            let sibling = self.node(height, &sibling(key, height));
+            if sibling != EMPTY {
+                proof.bitmap[height / 8] |= 1 << (height % 8);
+                proof.siblings.push(sibling);
+            }
+        }
+        proof
+    }
+}
+
+/// Siblings along a key's path, leaf first. Empty siblings are left out and
+/// marked by a clear bit in `bitmap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub bitmap: [u8; HASH_BYTES],
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Root implied by `value` being stored under `key` (`None` for absent)
+    pub fn compute_root(&self, key: &Hash, value: Option<&Hash>) -> Option<Hash> {
+        let mut siblings = self.siblings.iter();
+        let mut node = value.map_or(EMPTY, |value| leaf_hash(key, value));
+        for height in 0..TREE_DEPTH {
+            let sibling = if self.bitmap[height / 8] >> (height % 8) & 1 == 1 {
+                *siblings.next()?
+            } else {
+                EMPTY
+            };
+            node = if bit(key, TREE_DEPTH - 1 - height) {
+                node_hash(&sibling, &node)
+            } else {
+                node_hash(&node, &sibling)
+            };
+        }
+        // Every sibling must be used exactly once
+        siblings.next().is_none().then_some(node)
+    }
+
+    /// Check the proof against a trusted root
+    pub fn verify(&self, root: &Hash, key: &Hash, value: Option<&Hash>) -> bool {
+        self.compute_root(key, value) == Some(*root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u32) -> Hash {
+        hash::sha256(&n.to_le_bytes())
+    }
+
+    /// Root computed from scratch, for checking the incremental updates
+    fn naive_root(entries: &[(Hash, Hash)]) -> Hash {
+        let mut tree = SparseMerkleTree::default();
+        for (key, value) in entries {
+            tree.update(key, Some(value));
+        }
+        tree.root()
+    }
+
+    #[test]
+    fn test_empty_and_order_independent() {
+        assert_eq!(SparseMerkleTree::default().root(), EMPTY);
+
+        let entries: Vec<_> = (0..20).map(|n| (key(n), key(n + 100))).collect();
+        let mut reversed = entries.clone();
+        reversed.reverse();
+        assert_eq!(naive_root(&entries), naive_root(&reversed));
+
+        // Deleting everything returns to the empty tree and frees every node
+        let mut tree = SparseMerkleTree::default();
+        for (key, value) in &entries {
+            tree.update(key, Some(value));
+        }
+        for (key, _) in &entries {
+            tree.update(key, None);
+        }
+        assert_eq!(tree.root(), EMPTY);
+        assert!(tree.nodes.is_empty());
+    }
+
+    #[test]
+    fn test_inclusion_and_exclusion_proofs() {
+        let mut tree = SparseMerkleTree::default();
+        for n in 0..50 {
+            tree.update(&key(n), Some(&key(n + 1000)));
+        }
+        let root = tree.root();
+
+        let proof = tree.prove(&key(7));
+        assert!(proof.verify(&root, &key(7), Some(&key(1007))));
+        assert!(!proof.verify(&root, &key(7), Some(&key(1008))));
+        assert!(!proof.verify(&root, &key(7), None));
+        // Proofs stay small: only non-empty siblings are included
+        assert!(proof.siblings.len() < 20);
+
+        let absent = tree.prove(&key(99));
+        assert!(absent.verify(&root, &key(99), None));
+        assert!(!absent.verify(&root, &key(99), Some(&key(1099))));
+
+        // A stale proof does not verify against the new root
+        tree.update(&key(7), Some(&key(1)));
+        assert!(!proof.verify(&tree.root(), &key(7), Some(&key(1007))));
+        assert!(tree.prove(&key(7)).verify(&tree.root(), &key(7), Some(&key(1))));
+
+        let mut tampered = tree.prove(&key(8));
+        tampered.siblings.push(EMPTY);
+        assert!(tampered.compute_root(&key(8), Some(&key(1008))).is_none());
+    }
+}
@@ -0,0 +1,103 @@
+// ============================================
+// MULTISIG AUTHORITY
+// ============================================
+// An M-of-N set of signer keys that can act as the authority of an account
+
+use crate::error::{AppError, Result};
+
+/// Maximum number of signer keys in one multisig (same limit as SPL Token)
+pub const MAX_SIGNERS: usize = 11;
+
+/// Struct representing an M-of-N multisig authority
+#[derive(Debug, Clone, PartialEq)]
+pub struct Multisig {
+    pub name: String,
+    pub signers: Vec<String>, // The N keys allowed to sign
+    pub threshold: u8,        // M, the number of signatures required
+}
+
+impl Multisig {
+    /// Create a new multisig, checking that 1 <= M <= N <= MAX_SIGNERS
+    pub fn new(name: String, signers: Vec<String>, threshold: u8) -> Result<Self> {
+        AppError::validate_account_name(&name)?;
+        for signer in &signers {
+            AppError::validate_account_name(signer)?;
+        }
+
+        let mut unique = signers.clone();
+        unique.sort();
+        unique.dedup();
+        if unique.len() != signers.len()
+            || signers.len() > MAX_SIGNERS
+            || threshold == 0
+            || threshold as usize > signers.len()
+        {
+            return Err(AppError::InvalidThreshold {
+                threshold,
+                signers: signers.len(),
+            });
+        }
+
+        Ok(Self {
+            name,
+            signers,
+            threshold,
+        })
+    }
+
+    /// Count how many distinct keys of this multisig appear in `signed`
+    pub fn count_signatures(&self, signed: &[String]) -> usize {
+        self.signers
+            .iter()
+            .filter(|signer| signed.contains(signer))
+            .count()
+    }
+
+    /// Check that at least M of the N keys signed
+    pub fn check_signatures(&self, signed: &[String]) -> Result<()> {
+        let provided = self.count_signatures(signed);
+        if provided < self.threshold as usize {
+            return Err(AppError::NotEnoughSignatures {
+                required: self.threshold as usize,
+                provided,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_create_multisig() {
+        let multisig = Multisig::new("Council".into(), keys(&["Alice", "Bob", "Carol"]), 2).unwrap();
+        assert_eq!(multisig.threshold, 2);
+        assert_eq!(multisig.signers.len(), 3);
+
+        // M must be between 1 and N
+        assert!(Multisig::new("Council".into(), keys(&["Alice", "Bob"]), 3).is_err());
+        assert!(Multisig::new("Council".into(), keys(&["Alice", "Bob"]), 0).is_err());
+        // Duplicate keys would let one signer count twice
+        let result = Multisig::new("Council".into(), keys(&["Alice", "Alice"]), 2);
+        assert!(matches!(result, Err(AppError::InvalidThreshold { .. })));
+    }
+
+    #[test]
+    fn test_check_signatures() {
+        let multisig = Multisig::new("Council".into(), keys(&["Alice", "Bob", "Carol"]), 2).unwrap();
+        assert!(multisig.check_signatures(&keys(&["Alice", "Carol"])).is_ok());
+
+        // Outsiders and repeated signatures do not count
+        let result = multisig.check_signatures(&keys(&["Alice", "Alice", "Mallory"]));
+        assert_eq!(
+            result,
+            Err(AppError::NotEnoughSignatures { required: 2, provided: 1 })
+        );
+    }
+}
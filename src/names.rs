@@ -0,0 +1,92 @@
+// ============================================
+// NAME SERVICE
+// ============================================
+// Human-readable names ending in ".sol" that point at an account. Each
+// registration has an owner and expires at a slot unless renewed.
+
+use crate::error::{AppError, Result};
+
+/// Suffix every registered name must carry
+pub const NAME_SUFFIX: &str = ".sol";
+
+/// Struct representing one name registration
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameRecord {
+    pub name: String,
+    pub owner: String,  // Account allowed to renew or transfer the name
+    pub target: String, // Account the name resolves to
+    pub expires_at: u64,
+}
+
+impl NameRecord {
+    /// Create a registration valid for `duration` slots from `slot`
+    pub fn new(name: String, owner: String, target: String, slot: u64, duration: u64) -> Result<Self> {
+        validate_registered_name(&name)?;
+        AppError::validate_amount(duration)?;
+
+        Ok(Self {
+            name,
+            owner,
+            target,
+            expires_at: slot.saturating_add(duration),
+        })
+    }
+
+    /// Check whether the registration has lapsed at `slot`
+    pub fn is_expired(&self, slot: u64) -> bool {
+        slot >= self.expires_at
+    }
+
+    /// Check that `signer` owns the name and it has not lapsed
+    pub fn check_owner(&self, signer: &str, slot: u64) -> Result<()> {
+        if self.is_expired(slot) {
+            return Err(AppError::NameExpired(self.name.clone()));
+        }
+        if signer != self.owner {
+            return Err(AppError::Unauthorized(signer.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Check whether `key` is a registered-name style key rather than an account key
+pub fn is_registered_name(key: &str) -> bool {
+    key.ends_with(NAME_SUFFIX)
+}
+
+/// Check if a registered name is valid: a lowercase account name followed by ".sol"
+pub fn validate_registered_name(name: &str) -> Result<()> {
+    let label = name
+        .strip_suffix(NAME_SUFFIX)
+        .ok_or_else(|| AppError::InvalidAccountName(name.to_string()))?;
+    AppError::validate_account_name(label)?;
+    if label.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(AppError::InvalidAccountName(name.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_registered_name() {
+        assert!(validate_registered_name("alice.sol").is_ok());
+        assert!(validate_registered_name("my-shop_2.sol").is_ok());
+        assert!(validate_registered_name("alice").is_err());
+        assert!(validate_registered_name("Alice.sol").is_err());
+        assert!(validate_registered_name("a.sol").is_err());
+        assert!(validate_registered_name("root.sol").is_err());
+        assert!(validate_registered_name("al.ice.sol").is_err());
+    }
+
+    #[test]
+    fn test_expiry_and_ownership() {
+        let record = NameRecord::new("alice.sol".into(), "Alice".into(), "Alice".into(), 100, 50).unwrap();
+        assert_eq!(record.expires_at, 150);
+        assert!(record.check_owner("Alice", 149).is_ok());
+        assert!(matches!(record.check_owner("Bob", 149), Err(AppError::Unauthorized(_))));
+        assert!(matches!(record.check_owner("Alice", 150), Err(AppError::NameExpired(_))));
+    }
+}
@@ -0,0 +1,96 @@
+// ============================================
+// DURABLE NONCES
+// ============================================
+// A nonce account stores a value that a transaction can use in place of a
+// recent block hash, as with Solana's durable nonces. Such a transaction must
+// start with AdvanceNonce, which replaces the stored value, so it stays valid
+// for as long as the nonce is left alone and can never run twice.
+
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash, HASH_BYTES};
+use crate::pubkey::Pubkey;
+
+/// Program that owns every nonce account
+pub const NONCE_PROGRAM_ID: Pubkey = Pubkey::new(*b"NonceProgram11111111111111111111");
+
+/// Contents of a nonce account
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonceState {
+    pub authority: String, // Only the authority may advance or withdraw
+    pub nonce: Hash,       // Value the next transaction must reference
+}
+
+impl NonceState {
+    /// Fresh nonce for the account at `address`, opened at `slot`
+    pub fn new(authority: String, address: &Pubkey, slot: u64) -> Self {
+        Self {
+            authority,
+            nonce: hash::hashv(&[b"durable_nonce", address.as_ref(), &slot.to_le_bytes()]),
+        }
+    }
+
+    /// Address of nonce account `name`
+    pub fn address(name: &str) -> Result<Pubkey> {
+        AppError::validate_account_name(name)?;
+        Pubkey::find_program_address(&NonceState::seeds(&hash::sha256(name.as_bytes())), &NONCE_PROGRAM_ID).map(|(address, _)| address)
+    }
+
+    /// Seeds of the nonce account PDA, from the hash of its name so that
+    /// every valid name fits in one seed
+    pub fn seeds(name_hash: &Hash) -> [&[u8]; 2] {
+        [b"nonce", name_hash]
+    }
+
+    /// Check that `signer` is the nonce authority
+    pub fn check_authority(&self, signer: &str) -> Result<()> {
+        if signer != self.authority {
+            return Err(AppError::Unauthorized(signer.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Replace the nonce; every value is derived from the previous one, so
+    /// none repeats
+    pub fn advance(&mut self) {
+        self.nonce = hash::hashv(&[b"durable_nonce", &self.nonce]);
+    }
+
+    /// Encode as account data: nonce, then authority
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = self.nonce.to_vec();
+        data.extend_from_slice(self.authority.as_bytes());
+        data
+    }
+
+    /// Decode account data written by `pack`
+    pub fn unpack(data: &[u8]) -> Result<Self> {
+        let corrupt = || AppError::InvalidInstructionData("corrupt nonce account".to_string());
+        if data.len() < HASH_BYTES {
+            return Err(corrupt());
+        }
+        let nonce = data[..HASH_BYTES].try_into().map_err(|_| corrupt())?;
+        let authority = String::from_utf8(data[HASH_BYTES..].to_vec()).map_err(|_| corrupt())?;
+        Ok(Self { authority, nonce })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_and_pack() {
+        let address = NonceState::address("ColdNonce").unwrap();
+        let mut state = NonceState::new("Alice".into(), &address, 7);
+        assert_eq!(NonceState::unpack(&state.pack()).unwrap(), state);
+
+        let before = state.nonce;
+        state.advance();
+        assert_ne!(state.nonce, before);
+        assert_eq!(NonceState::unpack(&state.pack()).unwrap(), state);
+
+        assert!(state.check_authority("Alice").is_ok());
+        assert!(matches!(state.check_authority("Eve"), Err(AppError::Unauthorized(_))));
+        assert!(NonceState::unpack(&[0; 8]).is_err());
+    }
+}
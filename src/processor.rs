@@ -6,75 +6,606 @@
 use crate::instruction::Instruction;
 use crate::state::AppState;
 use crate::error::{AppError, Result};
+use crate::hash;
+use crate::history;
+use crate::pubkey::{Pubkey, SYSTEM_PROGRAM_ID};
+use crate::program::{Program, ProgramRegistry};
+use std::sync::OnceLock;
+
+/// Processor holding the registry of programs instructions are routed to
+pub struct Processor {
+    programs: ProgramRegistry,
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Processor {
+    /// Create a processor with only the built-in programs registered
+    pub fn new() -> Self {
+        Self {
+            programs: ProgramRegistry::with_builtins(),
+        }
+    }
+
+    /// Shared processor used by the free functions below
+    pub fn builtin() -> &'static Processor {
+        static BUILTIN: OnceLock<Processor> = OnceLock::new();
+        BUILTIN.get_or_init(Processor::new)
+    }
+
+    /// Register a custom program; `Invoke` instructions for its ID are routed to it
+    pub fn register_program(&mut self, program: Box<dyn Program>) -> Result<()> {
+        self.programs.register(program)
+    }
+
+    /// Registered programs
+    pub fn programs(&self) -> &ProgramRegistry {
+        &self.programs
+    }
+
+    /// Main function to process instruction
+    pub fn process_instruction(&self, instruction: &Instruction, state: &mut AppState) -> Result<String> {
+        // Keep writes made outside instructions apart from this one's
+        state.record_history(history::DIRECT);
+        let result = self.execute(instruction, state);
+        // A failed instruction may still have moved funds before failing
+        state.record_history(instruction.name());
+        result
+    }
+
+    fn execute(&self, instruction: &Instruction, state: &mut AppState) -> Result<String> {
+        // TODO 4.1: Validate instruction before processing
+        // Map the String error from Instruction::validate to AppError
+        instruction.validate().map_err(AppError::InvalidAccountName)?;
+
+        // The core account instructions are served by the system program
+        if let Some((accounts, data)) = instruction.to_system_call() {
+            return self.programs.invoke(&SYSTEM_PROGRAM_ID, &accounts, &data, state);
+        }
+
+        match instruction {
+            Instruction::Invoke { program_id, accounts, data } => {
+                self.programs.invoke(program_id, accounts, data, state)
+            }
+            _ => process_builtin(instruction, state),
+        }
+    }
+
+    /// Batch processing: Process multiple instructions at once
+    pub fn process_batch(&self, instructions: &[Instruction], state: &mut AppState) -> Vec<Result<String>> {
+        instructions
+            .iter()
+            .map(|instruction| self.process_instruction(instruction, state))
+            .collect()
+    }
+
+    /// Transaction: Process multiple instructions, rollback if error
+    pub fn process_transaction(&self, instructions: &[Instruction], state: &mut AppState) -> Result<Vec<String>> {
+        // The checkpoint keeps only what the transaction overwrites
+        state.checkpoint();
+        let mut results = Vec::new();
+
+        for instruction in instructions {
+            match self.process_instruction(instruction, state) {
+                Ok(msg) => results.push(msg),
+                Err(e) => {
+                    state.rollback(); // Rollback!
+                    return Err(e);
+                }
+            }
+        }
+
+        state.commit();
+        Ok(results)
+    }
+
+    /// Run a transaction against a copy of `state`, leaving the real one untouched
+    pub fn dry_run(&self, instructions: &[Instruction], state: &AppState) -> Result<Vec<String>> {
+        let mut state_clone = state.clone();
+        self.process_transaction(instructions, &mut state_clone)
+    }
+}
 
 /// Main function to process instruction
 pub fn process_instruction(instruction: &Instruction, state: &mut AppState) -> Result<String> {
-    // TODO 4.1: Validate instruction before processing
-    // Map the String error from Instruction::validate to AppError
-    instruction.validate().map_err(AppError::InvalidAccountName)?;
+    Processor::builtin().process_instruction(instruction, state)
+}
 
+/// Handle the built-in instructions that are not routed to a program
+fn process_builtin(instruction: &Instruction, state: &mut AppState) -> Result<String> {
     // TODO 4.2: Pattern match on instruction to handle each type
     match instruction {
-        Instruction::CreateAccount { name, balance } => {
-            process_create_account(name, *balance, state)
+        Instruction::Approve { owner, delegate, amount } => {
+            process_approve(owner, delegate, *amount, state)
         }
-        Instruction::Transfer { from, to, amount } => {
-            process_transfer(from, to, *amount, state)
+        Instruction::Revoke { owner } => {
+            process_revoke(owner, state)
         }
-        Instruction::UpdateBalance { name, amount } => {
-            process_update_balance(name, *amount, state)
+        Instruction::TransferFrom { delegate, from, to, amount } => {
+            process_transfer_from(delegate, from, to, *amount, state)
         }
-        Instruction::GetBalance { name } => {
-            process_get_balance(name, state)
+        Instruction::FreezeAccount { authority, name } => {
+            process_freeze_account(authority, name, state)
         }
-        Instruction::DeleteAccount { name } => {
-            process_delete_account(name, state)
+        Instruction::ThawAccount { authority, name } => {
+            process_thaw_account(authority, name, state)
         }
+        Instruction::CreateMultisig { name, signers, threshold } => {
+            process_create_multisig(name, signers, *threshold, state)
+        }
+        Instruction::SetAuthority { name, authority, signers } => {
+            process_set_authority(name, authority.as_deref(), signers, state)
+        }
+        Instruction::MultisigTransfer { from, to, amount, signers } => {
+            process_multisig_transfer(from, to, *amount, signers, state)
+        }
+        Instruction::CreateProgramAccount { address, program_id, seeds, bump, balance, authority } => {
+            process_create_program_account(address, program_id, seeds, *bump, *balance, authority, state)
+        }
+        Instruction::EscrowCreate { name, depositor, recipient, arbiter, amount, expiry_slot } => {
+            process_escrow_create(name, depositor, recipient, arbiter, *amount, *expiry_slot, state)
+        }
+        Instruction::EscrowRelease { name, signer } => {
+            process_escrow_release(name, signer, state)
+        }
+        Instruction::EscrowCancel { name, signer } => {
+            process_escrow_cancel(name, signer, state)
+        }
+        Instruction::CreateVesting { funder, beneficiary, total, start, cliff, duration } => {
+            process_create_vesting(funder, beneficiary, *total, *start, *cliff, *duration, state)
+        }
+        Instruction::ClaimVested { beneficiary, amount } => {
+            process_claim_vested(beneficiary, *amount, state)
+        }
+        Instruction::CreateStake { staker, name, amount } => {
+            process_create_stake(staker, name, *amount, state)
+        }
+        Instruction::Delegate { name, staker, validator } => {
+            process_delegate(name, staker, validator, state)
+        }
+        Instruction::Deactivate { name, staker } => {
+            process_deactivate(name, staker, state)
+        }
+        Instruction::Withdraw { name, staker, to, amount } => {
+            process_withdraw(name, staker, to, *amount, state)
+        }
+        Instruction::SchedulePayment { from, to, amount, interval, count } => {
+            process_schedule_payment(from, to, *amount, *interval, *count, state)
+        }
+        Instruction::CreateMint { symbol, authority } => {
+            process_create_mint(symbol, authority, state)
+        }
+        Instruction::MintTo { symbol, authority, to, amount } => {
+            process_mint_to(symbol, authority, to, *amount, state)
+        }
+        Instruction::TransferToken { symbol, from, to, amount } => {
+            process_transfer_token(symbol, from, to, *amount, state)
+        }
+        Instruction::CreatePool { name, mint_a, mint_b, fee_bps } => {
+            process_create_pool(name, mint_a, mint_b, *fee_bps, state)
+        }
+        Instruction::AddLiquidity { pool, provider, amount_a, amount_b, min_shares } => {
+            process_add_liquidity(pool, provider, *amount_a, *amount_b, *min_shares, state)
+        }
+        Instruction::RemoveLiquidity { pool, provider, shares, min_a, min_b } => {
+            process_remove_liquidity(pool, provider, *shares, *min_a, *min_b, state)
+        }
+        Instruction::Swap { pool, trader, mint_in, amount_in, min_out } => {
+            process_swap(pool, trader, mint_in, *amount_in, *min_out, state)
+        }
+        Instruction::RegisterName { name, owner, target, duration } => {
+            process_register_name(name, owner, target, *duration, state)
+        }
+        Instruction::ResolveName { name } => {
+            process_resolve_name(name, state)
+        }
+        Instruction::TransferName { name, owner, new_owner } => {
+            process_transfer_name(name, owner, new_owner, state)
+        }
+        Instruction::DeployProgram { owner, name, bytecode } => {
+            process_deploy_program(owner, name, bytecode, state)
+        }
+        Instruction::UpgradeProgram { program_id, authority, bytecode } => {
+            process_upgrade_program(program_id, authority, bytecode, state)
+        }
+        Instruction::SetUpgradeAuthority { program_id, authority, new_authority } => {
+            process_set_upgrade_authority(program_id, authority, new_authority.clone(), state)
+        }
+        Instruction::FinalizeProgram { program_id, authority } => {
+            process_set_upgrade_authority(program_id, authority, None, state)
+        }
+        Instruction::CreateNonceAccount { funder, name, authority, amount } => {
+            process_create_nonce_account(funder, name, authority, *amount, state)
+        }
+        Instruction::AdvanceNonce { name, authority } => {
+            process_advance_nonce(name, authority, state)
+        }
+        Instruction::WithdrawNonce { name, authority, to, amount } => {
+            process_withdraw_nonce(name, authority, to, *amount, state)
+        }
+        Instruction::CreateAccount { .. }
+        | Instruction::Transfer { .. }
+        | Instruction::UpdateBalance { .. }
+        | Instruction::GetBalance { .. }
+        | Instruction::DeleteAccount { .. }
+        | Instruction::Invoke { .. } => unreachable!("routed to a program by the processor"),
+    }
+}
+
+/// Process approve instruction
+fn process_approve(owner: &str, delegate: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    state.approve(owner, delegate, amount)?;
+    Ok(format!("Approved '{}' to spend {} from '{}'", delegate, amount, owner))
+}
+
+/// Process revoke instruction
+fn process_revoke(owner: &str, state: &mut AppState) -> Result<String> {
+    state.revoke(owner)?;
+    Ok(format!("Revoked delegate of '{}'", owner))
+}
+
+/// Process delegated transfer instruction
+fn process_transfer_from(
+    delegate: &str,
+    from: &str,
+    to: &str,
+    amount: u64,
+    state: &mut AppState,
+) -> Result<String> {
+    state.transfer_from(delegate, from, to, amount)?;
+    Ok(format!(
+        "Transferred {} from '{}' to '{}' as delegate '{}'",
+        amount, from, to, delegate
+    ))
+}
+
+/// Process freeze account instruction
+fn process_freeze_account(authority: &str, name: &str, state: &mut AppState) -> Result<String> {
+    state.freeze_account(authority, name)?;
+    Ok(format!("Froze account '{}'", name))
+}
+
+/// Process thaw account instruction
+fn process_thaw_account(authority: &str, name: &str, state: &mut AppState) -> Result<String> {
+    state.thaw_account(authority, name)?;
+    Ok(format!("Thawed account '{}'", name))
+}
+
+/// Process create multisig instruction
+fn process_create_multisig(
+    name: &str,
+    signers: &[String],
+    threshold: u8,
+    state: &mut AppState,
+) -> Result<String> {
+    state.create_multisig(name.to_string(), signers.to_vec(), threshold)?;
+    Ok(format!(
+        "Created {}-of-{} multisig '{}'",
+        threshold,
+        signers.len(),
+        name
+    ))
+}
+
+/// Process set authority instruction
+fn process_set_authority(
+    name: &str,
+    authority: Option<&str>,
+    signers: &[String],
+    state: &mut AppState,
+) -> Result<String> {
+    state.set_authority(name, authority.map(String::from), signers)?;
+    match authority {
+        Some(authority) => Ok(format!("Set authority of '{}' to '{}'", name, authority)),
+        None => Ok(format!("Removed authority of '{}'", name)),
     }
 }
 
-/// Process create account instruction
-fn process_create_account(name: &str, balance: u64, state: &mut AppState) -> Result<String> {
-    // TODO 4.3: Implement create account logic
-    state.create_account(name.to_string(), balance)?;
-    Ok(format!("Created account '{}' with balance {}", name, balance))
+/// Process multisig transfer instruction
+fn process_multisig_transfer(
+    from: &str,
+    to: &str,
+    amount: u64,
+    signers: &[String],
+    state: &mut AppState,
+) -> Result<String> {
+    state.multisig_transfer(from, to, amount, signers)?;
+    Ok(format!(
+        "Transferred {} from '{}' to '{}' with multisig approval",
+        amount, from, to
+    ))
 }
 
-/// Process transfer instruction
-fn process_transfer(from: &str, to: &str, amount: u64, state: &mut AppState) -> Result<String> {
-    // TODO 4.4: Implement transfer logic
-    if from == to {
-        return Err(AppError::TransferToSelf(from.to_string()));
+/// Process create program account instruction
+fn process_create_program_account(
+    address: &Pubkey,
+    program_id: &Pubkey,
+    seeds: &[Vec<u8>],
+    bump: u8,
+    balance: u64,
+    authority: &str,
+    state: &mut AppState,
+) -> Result<String> {
+    // The claimed address must really be derived from these seeds
+    let seed_refs: Vec<&[u8]> = seeds.iter().map(|seed| seed.as_slice()).collect();
+    address.verify_program_address(&seed_refs, bump, program_id)?;
+
+    // Otherwise anyone could take an address before its program does. Built-in
+    // programs open their own accounts; a deployed one through its authority.
+    if state.program_data(program_id)?.upgrade_authority.as_deref() != Some(authority) {
+        return Err(AppError::Unauthorized(authority.to_string()));
     }
-    state.transfer(from, to, amount)?;
-    Ok(format!("Transferred {} from '{}' to '{}'", amount, from, to))
+
+    state.create_program_account(*address, *program_id, balance)?;
+    Ok(format!(
+        "Created program account '{}' with balance {}",
+        address, balance
+    ))
+}
+
+/// Process escrow create instruction
+fn process_escrow_create(
+    name: &str,
+    depositor: &str,
+    recipient: &str,
+    arbiter: &str,
+    amount: u64,
+    expiry_slot: u64,
+    state: &mut AppState,
+) -> Result<String> {
+    state.create_escrow(name.to_string(), depositor, recipient, arbiter, amount, expiry_slot)?;
+    Ok(format!(
+        "Locked {} from '{}' in escrow '{}' until slot {}",
+        amount, depositor, name, expiry_slot
+    ))
+}
+
+/// Process escrow release instruction
+fn process_escrow_release(name: &str, signer: &str, state: &mut AppState) -> Result<String> {
+    state.release_escrow(name, signer)?;
+    let escrow = state.get_escrow(name)?;
+    Ok(format!(
+        "Released escrow '{}': {} paid to '{}'",
+        name, escrow.amount, escrow.recipient
+    ))
+}
+
+/// Process escrow cancel instruction
+fn process_escrow_cancel(name: &str, signer: &str, state: &mut AppState) -> Result<String> {
+    state.cancel_escrow(name, signer)?;
+    let escrow = state.get_escrow(name)?;
+    Ok(format!(
+        "Cancelled escrow '{}': {} refunded to '{}'",
+        name, escrow.amount, escrow.depositor
+    ))
+}
+
+/// Process create vesting instruction
+fn process_create_vesting(
+    funder: &str,
+    beneficiary: &str,
+    total: u64,
+    start: u64,
+    cliff: u64,
+    duration: u64,
+    state: &mut AppState,
+) -> Result<String> {
+    state.create_vesting(funder, beneficiary, total, start, cliff, duration)?;
+    Ok(format!(
+        "Vesting {} from '{}' to '{}' over {} slots",
+        total, funder, beneficiary, duration
+    ))
+}
+
+/// Process claim vested instruction
+fn process_claim_vested(beneficiary: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    let schedule = state.claim_vested(beneficiary, amount)?;
+    Ok(format!(
+        "Claimed {} for '{}' ({} of {} claimed)",
+        amount, beneficiary, schedule.claimed, schedule.total
+    ))
+}
+
+/// Process create stake instruction
+fn process_create_stake(staker: &str, name: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    state.create_stake(staker, name, amount)?;
+    Ok(format!("Created stake account '{}' with {}", name, amount))
+}
+
+/// Process delegate instruction
+fn process_delegate(name: &str, staker: &str, validator: &str, state: &mut AppState) -> Result<String> {
+    state.delegate_stake(name, staker, validator)?;
+    Ok(format!(
+        "Delegated stake '{}' to '{}' from epoch {}",
+        name,
+        validator,
+        state.current_epoch()
+    ))
+}
+
+/// Process deactivate instruction
+fn process_deactivate(name: &str, staker: &str, state: &mut AppState) -> Result<String> {
+    state.deactivate_stake(name, staker)?;
+    Ok(format!(
+        "Deactivated stake '{}' at epoch {}",
+        name,
+        state.current_epoch()
+    ))
+}
+
+/// Process withdraw instruction
+fn process_withdraw(name: &str, staker: &str, to: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    state.withdraw_stake(name, staker, to, amount)?;
+    Ok(format!("Withdrew {} from stake '{}' to '{}'", amount, name, to))
+}
+
+/// Process schedule payment instruction
+fn process_schedule_payment(
+    from: &str,
+    to: &str,
+    amount: u64,
+    interval: u64,
+    count: u32,
+    state: &mut AppState,
+) -> Result<String> {
+    let id = state.schedule_payment(from, to, amount, interval, count)?;
+    Ok(format!(
+        "Scheduled payment #{}: {} from '{}' to '{}' every {} slots, {} times",
+        id, amount, from, to, interval, count
+    ))
+}
+
+/// Process create mint instruction
+fn process_create_mint(symbol: &str, authority: &str, state: &mut AppState) -> Result<String> {
+    state.create_mint(symbol, authority)?;
+    Ok(format!("Created mint '{}'", symbol))
+}
+
+/// Process mint to instruction
+fn process_mint_to(symbol: &str, authority: &str, to: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    state.mint_to(symbol, authority, to, amount)?;
+    Ok(format!("Minted {} {} to '{}'", amount, symbol, to))
+}
+
+/// Process token transfer instruction
+fn process_transfer_token(symbol: &str, from: &str, to: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    state.transfer_tokens(symbol, from, to, amount)?;
+    Ok(format!("Transferred {} {} from '{}' to '{}'", amount, symbol, from, to))
+}
+
+/// Process create pool instruction
+fn process_create_pool(name: &str, mint_a: &str, mint_b: &str, fee_bps: u16, state: &mut AppState) -> Result<String> {
+    state.create_pool(name, mint_a, mint_b, fee_bps)?;
+    Ok(format!("Created pool '{}' for {}/{}", name, mint_a, mint_b))
+}
+
+/// Process add liquidity instruction
+fn process_add_liquidity(
+    pool: &str,
+    provider: &str,
+    amount_a: u64,
+    amount_b: u64,
+    min_shares: u64,
+    state: &mut AppState,
+) -> Result<String> {
+    let deposit = state.add_liquidity(pool, provider, amount_a, amount_b, min_shares)?;
+    Ok(format!(
+        "Deposited {} / {} into '{}' for {} shares",
+        deposit.amount_a, deposit.amount_b, pool, deposit.shares
+    ))
+}
+
+/// Process remove liquidity instruction
+fn process_remove_liquidity(
+    pool: &str,
+    provider: &str,
+    shares: u64,
+    min_a: u64,
+    min_b: u64,
+    state: &mut AppState,
+) -> Result<String> {
+    let (amount_a, amount_b) = state.remove_liquidity(pool, provider, shares, min_a, min_b)?;
+    Ok(format!(
+        "Burned {} shares of '{}' for {} / {}",
+        shares, pool, amount_a, amount_b
+    ))
+}
+
+/// Process swap instruction
+fn process_swap(
+    pool: &str,
+    trader: &str,
+    mint_in: &str,
+    amount_in: u64,
+    min_out: u64,
+    state: &mut AppState,
+) -> Result<String> {
+    let amount_out = state.swap(pool, trader, mint_in, amount_in, min_out)?;
+    Ok(format!(
+        "Swapped {} {} for {} in '{}'",
+        amount_in, mint_in, amount_out, pool
+    ))
+}
+
+/// Process register name instruction
+fn process_register_name(name: &str, owner: &str, target: &str, duration: u64, state: &mut AppState) -> Result<String> {
+    state.register_name(name, owner, target, duration)?;
+    let record = state.get_name_record(name)?;
+    Ok(format!(
+        "Registered '{}' -> '{}' until slot {}",
+        name, target, record.expires_at
+    ))
+}
+
+/// Process resolve name instruction
+fn process_resolve_name(name: &str, state: &AppState) -> Result<String> {
+    let target = state.resolve_name(name)?;
+    Ok(format!("Name '{}' resolves to '{}'", name, target))
 }
 
-/// Process update balance instruction
-fn process_update_balance(name: &str, amount: u64, state: &mut AppState) -> Result<String> {
-    // TODO 4.5: Implement update balance logic
-    let account = state.get_account_mut(name)?;
-    account.add_balance(amount)?;
-    Ok(format!("Added {} to account '{}'", amount, name))
+/// Process transfer name instruction
+fn process_transfer_name(name: &str, owner: &str, new_owner: &str, state: &mut AppState) -> Result<String> {
+    state.transfer_name(name, owner, new_owner)?;
+    Ok(format!("Transferred name '{}' to '{}'", name, new_owner))
 }
 
-/// Process get balance instruction
-fn process_get_balance(name: &str, state: &AppState) -> Result<String> {
-    // TODO 4.6: Implement get balance logic
-    let account = state.get_account(name)?;
-    let balance = account.get_balance();
-    Ok(format!("Account '{}' balance: {}", name, balance))
+/// Process deploy program instruction
+fn process_deploy_program(owner: &str, name: &str, bytecode: &[u8], state: &mut AppState) -> Result<String> {
+    let program_id = state.deploy_program(owner, name, bytecode.to_vec())?;
+    Ok(format!(
+        "Deployed program '{}' at {} ({} bytes)",
+        name,
+        program_id,
+        bytecode.len()
+    ))
 }
 
-/// Process delete account instruction
-fn process_delete_account(name: &str, state: &mut AppState) -> Result<String> {
-    // TODO 4.7: Implement delete account logic
-    let account = state.get_account(name)?;
-    if account.get_balance() > 0 {
-        return Err(AppError::InvalidAmount(account.get_balance()));
+/// Process upgrade program instruction
+fn process_upgrade_program(program_id: &Pubkey, authority: &str, bytecode: &[u8], state: &mut AppState) -> Result<String> {
+    let version = state.upgrade_program(program_id, authority, bytecode.to_vec())?;
+    Ok(format!("Upgraded program {} to version {}", program_id, version))
+}
+
+/// Process set upgrade authority and finalize program instructions
+fn process_set_upgrade_authority(
+    program_id: &Pubkey,
+    authority: &str,
+    new_authority: Option<String>,
+    state: &mut AppState,
+) -> Result<String> {
+    state.set_upgrade_authority(program_id, authority, new_authority.clone())?;
+    match new_authority {
+        Some(new_authority) => Ok(format!(
+            "Upgrade authority of {} set to '{}'",
+            program_id, new_authority
+        )),
+        None => Ok(format!("Program {} is now immutable", program_id)),
     }
-    let deleted_account = state.delete_account(name)?;
-    Ok(format!("Deleted account '{}'", deleted_account.name))
+}
+
+/// Process create nonce account instruction
+fn process_create_nonce_account(funder: &str, name: &str, authority: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    let address = state.create_nonce_account(funder, name, authority, amount)?;
+    Ok(format!(
+        "Created nonce account '{}' at {} with {}",
+        name, address, amount
+    ))
+}
+
+/// Process advance nonce instruction
+fn process_advance_nonce(name: &str, authority: &str, state: &mut AppState) -> Result<String> {
+    let nonce = state.advance_nonce(name, authority)?;
+    Ok(format!("Advanced nonce '{}' to {}", name, hash::to_hex(&nonce)))
+}
+
+/// Process withdraw nonce instruction
+fn process_withdraw_nonce(name: &str, authority: &str, to: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    state.withdraw_nonce(name, authority, to, amount)?;
+    Ok(format!("Withdrew {} from nonce '{}' to '{}'", amount, name, to))
 }
 
 // ============================================
@@ -84,10 +615,7 @@ fn process_delete_account(name: &str, state: &mut AppState) -> Result<String> {
 /// Batch processing: Process multiple instructions at once
 pub fn process_batch(instructions: &[Instruction], state: &mut AppState) -> Vec<Result<String>> {
     // TODO 4.8: ADVANCED - Implement batch processing
-    instructions
-        .iter()
-        .map(|instruction| process_instruction(instruction, state))
-        .collect()
+    Processor::builtin().process_batch(instructions, state)
 }
 
 /// Transaction: Process multiple instructions, rollback if error
@@ -97,20 +625,7 @@ pub fn process_transaction(
     state: &mut AppState,
 ) -> Result<Vec<String>> {
     // TODO 4.9: ADVANCED - Implement transaction with rollback
-    let original_state = state.clone(); 
-    let mut results = Vec::new();
-
-    for instruction in instructions {
-        match process_instruction(instruction, state) {
-            Ok(msg) => results.push(msg),
-            Err(e) => {
-                *state = original_state; // Rollback!
-                return Err(e);
-            }
-        }
-    }
-
-    Ok(results)
+    Processor::builtin().process_transaction(instructions, state)
 }
 
 /// Logging wrapper: Log every instruction before processing
@@ -139,6 +654,7 @@ pub fn dry_run(instruction: &Instruction, state: &AppState) -> Result<String> {
 mod tests {
     use super::*;
     use crate::instruction::Instruction;
+    use crate::program::{AccountMeta, InvokeContext};
 
     #[test]
     fn test_process_create_account() {
@@ -215,4 +731,388 @@ mod tests {
         // Verify Alice's balance rolled back to 1000, not 1500
         assert_eq!(state.get_account("Alice").unwrap().balance, 1000);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_transaction_rollback_undoes_records_and_caches() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+        let root = state.state_root();
+        let largest: Vec<String> = state.largest_accounts(10).iter().map(|account| account.name.clone()).collect();
+
+        let insts = vec![
+            Instruction::create_account("Carol".into(), 50),
+            Instruction::create_mint("GOLD".into(), "Alice".into()),
+            Instruction::schedule_payment("Alice".into(), "Bob".into(), 10, 5, 2),
+            Instruction::transfer("Alice".into(), "Bob".into(), 900),
+            Instruction::transfer("Bob".into(), "Alice".into(), 10_000), // Fails
+        ];
+        assert!(process_transaction(&insts, &mut state).is_err());
+
+        assert_eq!(state.state_root(), root);
+        let after: Vec<String> = state.largest_accounts(10).iter().map(|account| account.name.clone()).collect();
+        assert_eq!(after, largest);
+        assert!(!state.account_exists("Carol"));
+        assert!(state.get_mint("GOLD").is_err());
+        assert!(state.get_scheduled_payment(1).is_err());
+        assert!(state.history("Alice", .., 0, 10).changes.iter().all(|change| change.instruction == "Direct"));
+
+        // IDs handed out inside the failed transaction are handed out again
+        assert_eq!(state.schedule_payment("Alice", "Bob", 10, 5, 2).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_process_delegated_transfer() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+
+        let insts = vec![
+            Instruction::approve("Alice".into(), "Bob".into(), 250),
+            Instruction::transfer_from("Bob".into(), "Alice".into(), "Bob".into(), 200),
+        ];
+        let results = process_batch(&insts, &mut state);
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(state.get_account("Bob").unwrap().balance, 201);
+
+        let over = Instruction::transfer_from("Bob".into(), "Alice".into(), "Bob".into(), 100);
+        let result = process_instruction(&over, &mut state);
+        assert!(matches!(result, Err(AppError::InsufficientAllowance { .. })));
+    }
+
+    #[test]
+    fn test_process_freeze_blocks_transfer() {
+        let mut state = AppState::with_freeze_authority("Compliance".into());
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 500).unwrap();
+
+        let freeze = Instruction::freeze_account("Compliance".into(), "Alice".into());
+        assert!(process_instruction(&freeze, &mut state).is_ok());
+
+        let inst = Instruction::transfer("Alice".into(), "Bob".into(), 100);
+        let result = process_instruction(&inst, &mut state);
+        assert!(matches!(result, Err(AppError::AccountFrozen(_))));
+
+        let thaw = Instruction::thaw_account("Compliance".into(), "Alice".into());
+        assert!(process_instruction(&thaw, &mut state).is_ok());
+        assert!(process_instruction(&inst, &mut state).is_ok());
+    }
+
+    #[test]
+    fn test_process_multisig_treasury() {
+        let mut state = AppState::new();
+        state.create_account("Treasury".into(), 1000).unwrap();
+        state.create_account("Vendor".into(), 10).unwrap();
+        let keys = vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()];
+
+        let setup = vec![
+            Instruction::create_multisig("Council".into(), keys, 2),
+            Instruction::set_authority("Treasury".into(), Some("Council".into()), vec!["Treasury".into()]),
+        ];
+        assert!(process_transaction(&setup, &mut state).is_ok());
+
+        let plain = Instruction::transfer("Treasury".into(), "Vendor".into(), 100);
+        assert!(matches!(process_instruction(&plain, &mut state), Err(AppError::Unauthorized(_))));
+
+        let signed = Instruction::multisig_transfer(
+            "Treasury".into(),
+            "Vendor".into(),
+            100,
+            vec!["Bob".into(), "Carol".into()],
+        );
+        assert!(process_instruction(&signed, &mut state).is_ok());
+        assert_eq!(state.get_account("Vendor").unwrap().balance, 110);
+    }
+
+    #[test]
+    fn test_process_program_account() {
+        use crate::vm::{assemble, Insn, Opcode};
+
+        let mut state = AppState::new();
+        state.create_account("Dev".into(), 1).unwrap();
+        let bytecode = assemble(&[Insn::new(Opcode::MovImm, 0, 0, 0), Insn::new(Opcode::Exit, 0, 0, 0)]);
+        let program_id = state.deploy_program("Dev", "vaults", bytecode).unwrap();
+        let seeds = vec![b"vault".to_vec(), b"Alice".to_vec()];
+
+        // Only the program's upgrade authority may open its accounts
+        let squat = Instruction::create_program_account(program_id, "Mallory".into(), seeds.clone(), 100).unwrap();
+        assert!(matches!(process_instruction(&squat, &mut state), Err(AppError::Unauthorized(_))));
+        let inst = Instruction::create_program_account(program_id, "Dev".into(), seeds.clone(), 100).unwrap();
+        assert!(process_instruction(&inst, &mut state).is_ok());
+
+        // Built-in programs open their own accounts
+        let escrow_vault = Instruction::create_program_account(
+            crate::state::ESCROW_PROGRAM_ID,
+            "Mallory".into(),
+            vec![b"escrow".to_vec(), b"Order1".to_vec()],
+            100,
+        )
+        .unwrap();
+        assert!(matches!(process_instruction(&escrow_vault, &mut state), Err(AppError::ProgramNotFound(_))));
+
+        // Claiming an address that was not derived from the seeds fails
+        if let Instruction::CreateProgramAccount { address, bump, .. } = inst {
+            let forged = Instruction::CreateProgramAccount {
+                address,
+                program_id,
+                seeds: vec![b"vault".to_vec(), b"Mallory".to_vec()],
+                bump,
+                balance: 100,
+                authority: "Dev".into(),
+            };
+            let result = process_instruction(&forged, &mut state);
+            assert!(matches!(result, Err(AppError::InvalidProgramAddress(_)) | Err(AppError::InvalidSeeds(_))));
+        }
+    }
+
+    #[test]
+    fn test_process_escrow_flow() {
+        let mut state = AppState::new();
+        state.create_account("Buyer".into(), 1000).unwrap();
+        state.create_account("Seller".into(), 10).unwrap();
+
+        let create = Instruction::escrow_create(
+            "Order1".into(),
+            "Buyer".into(),
+            "Seller".into(),
+            "Market".into(),
+            400,
+            20,
+        );
+        assert!(process_instruction(&create, &mut state).is_ok());
+
+        let wrong_signer = Instruction::escrow_release("Order1".into(), "Seller".into());
+        assert!(matches!(process_instruction(&wrong_signer, &mut state), Err(AppError::Unauthorized(_))));
+
+        let release = Instruction::escrow_release("Order1".into(), "Market".into());
+        assert!(process_instruction(&release, &mut state).is_ok());
+        assert_eq!(state.get_account("Seller").unwrap().balance, 410);
+        assert_eq!(state.get_account("Buyer").unwrap().balance, 600);
+    }
+
+    #[test]
+    fn test_process_amm_swap() {
+        let mut state = AppState::new();
+        state.create_account("Lp".into(), 1).unwrap();
+        let setup = vec![
+            Instruction::create_mint("SOL".into(), "Issuer".into()),
+            Instruction::create_mint("USDC".into(), "Issuer".into()),
+            Instruction::mint_to("SOL".into(), "Issuer".into(), "Lp".into(), 2_000),
+            Instruction::mint_to("USDC".into(), "Issuer".into(), "Lp".into(), 100_000),
+            Instruction::create_pool("Pool1".into(), "SOL".into(), "USDC".into(), 25),
+            Instruction::add_liquidity("Pool1".into(), "Lp".into(), 1_000, 100_000, 1),
+        ];
+        assert!(process_transaction(&setup, &mut state).is_ok());
+
+        let swap = Instruction::swap("Pool1".into(), "Lp".into(), "SOL".into(), 100, 9_000);
+        assert!(process_instruction(&swap, &mut state).is_ok());
+        let greedy = Instruction::swap("Pool1".into(), "Lp".into(), "SOL".into(), 100, 9_000);
+        assert!(matches!(process_instruction(&greedy, &mut state), Err(AppError::SlippageExceeded { .. })));
+    }
+
+    #[test]
+    fn test_process_transfer_by_name() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 500).unwrap();
+
+        let register = Instruction::register_name("bob.sol".into(), "Bob".into(), "Bob".into(), 100);
+        assert!(process_instruction(&register, &mut state).is_ok());
+
+        let inst = Instruction::transfer("Alice".into(), "bob.sol".into(), 300);
+        assert!(process_instruction(&inst, &mut state).is_ok());
+        assert_eq!(state.get_account("Bob").unwrap().balance, 800);
+
+        let to_self = Instruction::transfer("Bob".into(), "bob.sol".into(), 1);
+        assert!(matches!(process_instruction(&to_self, &mut state), Err(AppError::TransferToSelf(_))));
+
+        let unknown = Instruction::transfer("Alice".into(), "carol.sol".into(), 1);
+        assert!(matches!(process_instruction(&unknown, &mut state), Err(AppError::NameNotFound(_))));
+    }
+
+    /// Toy program that credits its only account with the byte sum of its data
+    struct Faucet;
+
+    impl Program for Faucet {
+        fn id(&self) -> Pubkey {
+            Pubkey::new(*b"FaucetProgram1111111111111111111")
+        }
+
+        fn process(&self, ctx: &mut InvokeContext, accounts: &[AccountMeta], data: &[u8]) -> Result<String> {
+            let account = crate::program::account_at(accounts, 0)?;
+            account.check_writable()?;
+            let amount: u64 = data.iter().map(|byte| *byte as u64).sum();
+            ctx.account_mut(&account.key)?.add_balance(amount)?;
+            Ok(format!("Dripped {}", amount))
+        }
+    }
+
+    #[test]
+    fn test_custom_program_routing() {
+        let mut processor = Processor::new();
+        processor.register_program(Box::new(Faucet)).unwrap();
+        let mut state = AppState::new();
+
+        let insts = vec![
+            Instruction::create_account("Alice".into(), 10),
+            Instruction::invoke(Faucet.id(), vec![AccountMeta::new("Alice".into(), false)], vec![5, 5]),
+        ];
+        assert!(processor.process_transaction(&insts, &mut state).is_ok());
+        assert_eq!(state.get_account("Alice").unwrap().balance, 20);
+
+        // The shared processor only knows the built-in programs
+        let result = process_instruction(&insts[1], &mut state);
+        assert!(matches!(result, Err(AppError::ProgramNotFound(_))));
+
+        // Core instructions can also be sent to the system program directly
+        let (accounts, data) = Instruction::GetBalance { name: "Alice".into() }.to_system_call().unwrap();
+        let raw = Instruction::invoke(SYSTEM_PROGRAM_ID, accounts, data);
+        assert_eq!(process_instruction(&raw, &mut state).unwrap(), "Account 'Alice' balance: 20");
+    }
+
+    #[test]
+    fn test_deploy_and_invoke_bytecode() {
+        use crate::vm::{assemble, Insn, Opcode};
+
+        let mut state = AppState::new();
+        state.create_account("Dev".into(), 1).unwrap();
+        let bytecode = assemble(&[Insn::new(Opcode::MovImm, 0, 0, 0), Insn::new(Opcode::Exit, 0, 0, 0)]);
+
+        let deploy = Instruction::deploy_program("Dev".into(), "noop".into(), bytecode.clone());
+        assert!(process_instruction(&deploy, &mut state).is_ok());
+        assert!(matches!(process_instruction(&deploy, &mut state), Err(AppError::AccountAlreadyExists(_))));
+
+        let program_id = crate::loader::program_id("Dev", "noop").unwrap();
+        let invoke = Instruction::invoke(program_id, vec![], vec![]);
+        assert!(process_instruction(&invoke, &mut state).unwrap().starts_with("Program succeeded"));
+
+        let bad = Instruction::deploy_program("Dev".into(), "bad".into(), vec![0xff; 8]);
+        assert!(matches!(process_instruction(&bad, &mut state), Err(AppError::InvalidBytecode(_))));
+
+        // Same ID, new behaviour; then lock it
+        let failing = assemble(&[Insn::new(Opcode::MovImm, 0, 0, 3), Insn::new(Opcode::Exit, 0, 0, 0)]);
+        let upgrade = Instruction::upgrade_program(program_id, "Dev".into(), failing);
+        assert!(process_instruction(&upgrade, &mut state).is_ok());
+        assert!(matches!(process_instruction(&invoke, &mut state), Err(AppError::ProgramFailed(3))));
+
+        let finalize = Instruction::finalize_program(program_id, "Dev".into());
+        assert!(process_instruction(&finalize, &mut state).is_ok());
+        let restore = Instruction::upgrade_program(program_id, "Dev".into(), bytecode);
+        assert!(matches!(process_instruction(&restore, &mut state), Err(AppError::ProgramImmutable(_))));
+    }
+
+    #[test]
+    fn test_balance_history() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+        state.tick();
+
+        let transfer = Instruction::transfer("Alice".into(), "Bob".into(), 300);
+        assert!(process_instruction(&transfer, &mut state).is_ok());
+        state.schedule_payment("Bob", "Alice", 50, 2, 1).unwrap();
+        // A rolled back transaction leaves no trace
+        let failing = [transfer.clone(), Instruction::transfer("Bob".into(), "Alice".into(), 10_000)];
+        assert!(process_transaction(&failing, &mut state).is_err());
+        state.advance_slots(2);
+
+        let page = state.history("Alice", .., 0, 10);
+        let summary: Vec<_> = page
+            .changes
+            .iter()
+            .map(|change| (change.slot, change.delta(), change.counterparty.as_deref(), change.instruction))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 1000, None, "Direct"),
+                (1, -300, Some("Bob"), "Transfer"),
+                (3, 50, Some("Bob"), "ScheduledPayment"),
+            ]
+        );
+        assert_eq!(page.changes[2].balance, 750);
+        assert_eq!(page.next, None);
+
+        // Paged and sliced by slot
+        let first = state.history("Bob", .., 0, 1);
+        assert_eq!(first.changes[0].delta(), 1);
+        let rest = state.history("Bob", .., first.next.unwrap(), 10);
+        assert_eq!(rest.changes.len(), 2);
+        assert_eq!(state.history("Bob", 2.., 0, 10).changes.len(), 1);
+
+        assert_eq!(state.balance_at("Alice", 0), Ok(1000));
+        assert_eq!(state.balance_at("Alice", 2), Ok(700));
+        assert_eq!(state.balance_at("Alice", 3), Ok(750));
+        assert!(state.balance_at("Nobody", 3).is_err());
+
+        // Deleted accounts keep their history
+        state.create_account("Temp".into(), 5).unwrap();
+        let close = [
+            Instruction::transfer("Temp".into(), "Alice".into(), 5),
+            Instruction::delete_account("Temp".into()),
+        ];
+        assert!(process_transaction(&close, &mut state).is_ok());
+        assert_eq!(state.history("Temp", .., 0, 10).changes.len(), 2);
+        assert_eq!(state.balance_at("Temp", 3), Ok(0));
+    }
+
+    #[test]
+    fn test_indexes_follow_any_mix_of_instructions() {
+        let names = ["A", "B", "C", "D", "E", "F"];
+        let mut state = AppState::new();
+        state.create_mint("GOLD", "Mint").unwrap();
+        state.create_account("Dev".into(), 1).unwrap();
+        let bytecode = crate::vm::assemble(&[crate::vm::Insn::new(crate::vm::Opcode::Exit, 0, 0, 0)]);
+        let program_id = state.deploy_program("Dev", "records", bytecode).unwrap();
+
+        // A fixed pseudo-random sequence of instructions, many of them failing
+        let mut seed = 42u64;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        for step in 0..400 {
+            let pick = |index: u64| names[index as usize].to_string();
+            let (a, b, amount) = (pick(next(6)), pick(next(6)), next(200));
+            let instruction = match next(7) {
+                0 => Instruction::create_account(a, amount),
+                1 | 2 => Instruction::transfer(a, b, amount),
+                3 => Instruction::delete_account(a),
+                4 => Instruction::mint_to("GOLD".into(), "Mint".into(), a, amount),
+                5 => Instruction::transfer_token("GOLD".into(), a, b, amount),
+                _ => {
+                    Instruction::create_program_account(program_id, "Dev".into(), vec![vec![step as u8]], amount).unwrap()
+                }
+            };
+            if next(4) == 0 {
+                // Rolled back with the failing second half
+                let _ = process_transaction(&[instruction, Instruction::transfer("X".into(), "Y".into(), 1)], &mut state);
+            } else {
+                let _ = process_instruction(&instruction, &mut state);
+            }
+
+            if step % 20 == 0 {
+                let mut all = state.accounts_by_balance(..);
+                assert_eq!(state.total_balance(), all.iter().map(|account| account.balance).sum::<u64>());
+                assert_eq!(all.len(), state.account_count());
+                all.sort_by(|a, b| b.balance.cmp(&a.balance).then(b.name.cmp(&a.name)));
+                let largest: Vec<_> = state.largest_accounts(3).iter().map(|account| &account.name).collect();
+                assert_eq!(largest, all.iter().take(3).map(|account| &account.name).collect::<Vec<_>>());
+
+                let middle = state.accounts_by_balance(50..150);
+                assert_eq!(middle.len(), all.iter().filter(|account| (50..150).contains(&account.balance)).count());
+                let holders: Vec<_> = state.token_holders("GOLD").iter().map(|account| account.name.clone()).collect();
+                let expected: Vec<_> = state
+                    .list_accounts()
+                    .into_iter()
+                    .filter(|name| state.get_account(name).unwrap().token_balance("GOLD") > 0)
+                    .collect();
+                assert_eq!(holders, expected);
+                let owned = state.program_accounts(&program_id).len();
+                assert_eq!(owned, all.iter().filter(|account| account.owner == program_id).count());
+                assert_eq!(state.accounts_created(..).len(), state.account_count());
+            }
+        }
+        assert!(state.account_count() > 3);
+    }
+}
@@ -0,0 +1,471 @@
+// ============================================
+// PROGRAM RUNTIME
+// ============================================
+// Programs are handlers identified by a program ID. The processor keeps a
+// registry of them and routes `Invoke` instructions by ID, so new programs
+// can be added without touching the built-in instruction set. IDs that are not
+// registered fall back to bytecode deployed at that address.
+//
+// A running program may call another one through its `InvokeContext`. The
+// callee only gets the signer and writable privileges the caller holds (plus
+// signatures for the caller's own derived addresses), calls nest at most
+// `MAX_CALL_DEPTH` deep, and a failed call leaves the state as it was.
+// Programs reach the state only through the accounts they were passed, with
+// the privileges they were passed with.
+
+use std::collections::HashMap;
+
+use crate::codec::{Decode, Decoder, Encode};
+use crate::error::{AppError, Result};
+use crate::pubkey::Pubkey;
+use crate::state::{Account, AppState};
+use crate::system_program::SystemProgram;
+use crate::vm;
+
+/// Deepest nesting of program calls, counting the top-level one
+pub const MAX_CALL_DEPTH: usize = 5;
+
+/// An account passed to a program, with the privileges it is passed with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountMeta {
+    pub key: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountMeta {
+    /// A writable account
+    pub fn new(key: String, is_signer: bool) -> Self {
+        Self {
+            key,
+            is_signer,
+            is_writable: true,
+        }
+    }
+
+    /// A read-only account
+    pub fn new_readonly(key: String, is_signer: bool) -> Self {
+        Self {
+            key,
+            is_signer,
+            is_writable: false,
+        }
+    }
+
+    /// Fail unless the account signed
+    pub fn check_signer(&self) -> Result<()> {
+        if !self.is_signer {
+            return Err(AppError::Unauthorized(self.key.clone()));
+        }
+        Ok(())
+    }
+
+    /// Fail unless the account was passed writable
+    pub fn check_writable(&self) -> Result<()> {
+        if !self.is_writable {
+            return Err(AppError::AccountNotWritable(self.key.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl Encode for AccountMeta {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.key.encode_to(out);
+        self.is_signer.encode_to(out);
+        self.is_writable.encode_to(out);
+    }
+}
+
+impl Decode for AccountMeta {
+    fn decode_from(decoder: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Self {
+            key: decoder.get()?,
+            is_signer: decoder.get()?,
+            is_writable: decoder.get()?,
+        })
+    }
+}
+
+/// A program that can be invoked with a list of accounts and opaque data
+pub trait Program: Send + Sync {
+    /// Address the program is registered under
+    fn id(&self) -> Pubkey;
+
+    /// Execute one instruction; `accounts` are the account keys it may touch
+    fn process(&self, ctx: &mut InvokeContext, accounts: &[AccountMeta], data: &[u8]) -> Result<String>;
+}
+
+/// Registry of programs keyed by program ID
+#[derive(Default)]
+pub struct ProgramRegistry {
+    programs: HashMap<Pubkey, Box<dyn Program>>,
+}
+
+impl ProgramRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry holding the built-in programs
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.programs.insert(SystemProgram.id(), Box::new(SystemProgram));
+        registry
+    }
+
+    /// Register a program; fails if its ID is already taken
+    pub fn register(&mut self, program: Box<dyn Program>) -> Result<()> {
+        let id = program.id();
+        if self.programs.contains_key(&id) {
+            return Err(AppError::ProgramAlreadyRegistered(id.to_string()));
+        }
+        self.programs.insert(id, program);
+        Ok(())
+    }
+
+    /// Get a registered program
+    pub fn get(&self, id: &Pubkey) -> Result<&dyn Program> {
+        self.programs
+            .get(id)
+            .map(|program| program.as_ref())
+            .ok_or_else(|| AppError::ProgramNotFound(id.to_string()))
+    }
+
+    /// Check whether a program is registered under `id`
+    pub fn contains(&self, id: &Pubkey) -> bool {
+        self.programs.contains_key(id)
+    }
+
+    /// Route a top-level instruction to the program registered under `id`
+    pub fn invoke(&self, id: &Pubkey, accounts: &[AccountMeta], data: &[u8], state: &mut AppState) -> Result<String> {
+        let mut root = InvokeContext {
+            registry: self,
+            state,
+            program_id: None,
+            accounts: accounts.to_vec(),
+            depth: 0,
+        };
+        root.invoke(id, accounts, data)
+    }
+}
+
+/// Execution context of the running program
+pub struct InvokeContext<'a> {
+    registry: &'a ProgramRegistry,
+    state: &'a mut AppState,
+    program_id: Option<Pubkey>, // None for the top-level caller
+    accounts: Vec<AccountMeta>, // Privileges the running program was given
+    depth: usize,
+}
+
+impl InvokeContext<'_> {
+    /// ID of the running program
+    pub fn program_id(&self) -> Option<Pubkey> {
+        self.program_id
+    }
+
+    /// Number of program calls on the stack, including the running one
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// An account passed to the running program
+    pub fn account(&self, key: &str) -> Result<&Account> {
+        self.granted(key)?;
+        self.state.get_account(key)
+    }
+
+    /// An account passed writable to the running program, to change it
+    pub fn account_mut(&mut self, key: &str) -> Result<&mut Account> {
+        self.granted(key)?.check_writable()?;
+        self.state.get_account_mut(key)
+    }
+
+    /// The whole state, for built-in programs that check the privileges of
+    /// their accounts themselves
+    pub(crate) fn state_mut(&mut self) -> &mut AppState {
+        self.state
+    }
+
+    /// Privileges the running program holds on `key`
+    fn granted(&self, key: &str) -> Result<&AccountMeta> {
+        self.accounts
+            .iter()
+            .find(|held| held.key == key)
+            .ok_or_else(|| AppError::PrivilegeEscalation(format!("{} was not passed to the program", key)))
+    }
+
+    /// Call another program with a subset of the running program's privileges
+    pub fn invoke(&mut self, id: &Pubkey, accounts: &[AccountMeta], data: &[u8]) -> Result<String> {
+        self.invoke_signed(id, accounts, data, &[])
+    }
+
+    /// Call another program, additionally signing for every address derived
+    /// from the running program's ID with one of `signer_seeds`
+    pub fn invoke_signed(
+        &mut self,
+        id: &Pubkey,
+        accounts: &[AccountMeta],
+        data: &[u8],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<String> {
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(AppError::CallDepthExceeded(MAX_CALL_DEPTH));
+        }
+        // Built-in programs first, then bytecode deployed at runtime
+        let bytecode = match self.registry.get(id) {
+            Ok(_) => None,
+            Err(_) => Some(self.state.program_data(id)?.bytecode),
+        };
+        self.check_privileges(accounts, signer_seeds)?;
+
+        self.state.checkpoint();
+        let mut callee = InvokeContext {
+            registry: self.registry,
+            state: &mut *self.state,
+            program_id: Some(*id),
+            accounts: accounts.to_vec(),
+            depth: self.depth + 1,
+        };
+        let result = match &bytecode {
+            Some(bytecode) => vm::execute(&mut callee, bytecode, accounts, data),
+            None => self.registry.get(id)?.process(&mut callee, accounts, data),
+        };
+        // Undo everything the call stack did, touching only what it wrote
+        match result {
+            Ok(_) => self.state.commit(),
+            Err(_) => self.state.rollback(),
+        }
+        result
+    }
+
+    /// Check that `accounts` asks for no privilege the running program lacks
+    fn check_privileges(&self, accounts: &[AccountMeta], signer_seeds: &[&[&[u8]]]) -> Result<()> {
+        let mut pda_signers = Vec::new();
+        if !signer_seeds.is_empty() {
+            let program_id = self.program_id.ok_or_else(|| {
+                AppError::PrivilegeEscalation("only programs can sign for derived addresses".to_string())
+            })?;
+            for seeds in signer_seeds {
+                pda_signers.push(Pubkey::create_program_address(seeds, &program_id)?.to_string());
+            }
+        }
+
+        for meta in accounts {
+            let granted = self
+                .accounts
+                .iter()
+                .find(|held| held.key == meta.key)
+                .ok_or_else(|| AppError::PrivilegeEscalation(format!("{} was not passed to the caller", meta.key)))?;
+            if meta.is_writable && !granted.is_writable {
+                return Err(AppError::PrivilegeEscalation(format!("{} is read-only", meta.key)));
+            }
+            if meta.is_signer && !granted.is_signer && !pda_signers.contains(&meta.key) {
+                return Err(AppError::PrivilegeEscalation(format!("{} did not sign", meta.key)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the account at `index`, failing if the caller passed too few
+pub fn account_at(accounts: &[AccountMeta], index: usize) -> Result<&AccountMeta> {
+    accounts
+        .get(index)
+        .ok_or_else(|| AppError::InvalidInstructionData(format!("missing account #{}", index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubkey::SYSTEM_PROGRAM_ID;
+    use crate::system_program::SystemInstruction;
+
+    struct Echo;
+
+    impl Program for Echo {
+        fn id(&self) -> Pubkey {
+            Pubkey::new([7; 32])
+        }
+
+        fn process(&self, _ctx: &mut InvokeContext, accounts: &[AccountMeta], data: &[u8]) -> Result<String> {
+            Ok(format!("{} accounts, {} bytes", accounts.len(), data.len()))
+        }
+    }
+
+    /// Pays out of its vault PDA through the system program. Data is the
+    /// amount followed by a mode byte: 0 signs with the vault seeds, 1 does
+    /// not sign, 2 pays and then fails, 3 calls itself until the depth limit.
+    struct Vault;
+
+    impl Vault {
+        fn address() -> Pubkey {
+            Pubkey::find_program_address(&[b"vault"], &Vault.id()).unwrap().0
+        }
+    }
+
+    impl Program for Vault {
+        fn id(&self) -> Pubkey {
+            Pubkey::new(*b"VaultProgram11111111111111111111")
+        }
+
+        fn process(&self, ctx: &mut InvokeContext, accounts: &[AccountMeta], data: &[u8]) -> Result<String> {
+            let amount = u64::from_le_bytes(data[..8].try_into().unwrap());
+            let (_, bump) = Pubkey::find_program_address(&[b"vault"], &self.id())?;
+            let bump_seed = [bump];
+            let seeds: &[&[u8]] = &[b"vault", &bump_seed];
+
+            let vault = account_at(accounts, 0)?;
+            let to = account_at(accounts, 1)?;
+            let metas = [AccountMeta::new(vault.key.clone(), true), AccountMeta::new(to.key.clone(), false)];
+            let transfer = SystemInstruction::Transfer { amount }.pack();
+
+            match data[8] {
+                0 => ctx.invoke_signed(&SYSTEM_PROGRAM_ID, &metas, &transfer, &[seeds]),
+                1 => ctx.invoke(&SYSTEM_PROGRAM_ID, &metas, &transfer),
+                2 => {
+                    ctx.invoke_signed(&SYSTEM_PROGRAM_ID, &metas, &transfer, &[seeds])?;
+                    Err(AppError::InvalidAmount(amount))
+                }
+                _ => ctx.invoke(&self.id(), accounts, data),
+            }
+        }
+    }
+
+    fn vault_setup() -> (ProgramRegistry, AppState, Vec<AccountMeta>) {
+        let mut registry = ProgramRegistry::with_builtins();
+        registry.register(Box::new(Vault)).unwrap();
+        let mut state = AppState::new();
+        state.create_account(Vault::address().to_string(), 100).unwrap();
+        state.create_account("Alice".into(), 1).unwrap();
+        let accounts = vec![
+            AccountMeta::new(Vault::address().to_string(), false),
+            AccountMeta::new("Alice".into(), false),
+        ];
+        (registry, state, accounts)
+    }
+
+    fn vault_data(amount: u64, mode: u8) -> Vec<u8> {
+        let mut data = amount.to_le_bytes().to_vec();
+        data.push(mode);
+        data
+    }
+
+    #[test]
+    fn test_register_and_invoke() {
+        let mut registry = ProgramRegistry::with_builtins();
+        assert!(registry.contains(&SYSTEM_PROGRAM_ID));
+        registry.register(Box::new(Echo)).unwrap();
+        assert!(matches!(
+            registry.register(Box::new(Echo)),
+            Err(AppError::ProgramAlreadyRegistered(_))
+        ));
+
+        let mut state = AppState::new();
+        let accounts = [AccountMeta::new_readonly("Alice".into(), false)];
+        let result = registry.invoke(&Pubkey::new([7; 32]), &accounts, &[1, 2], &mut state);
+        assert_eq!(result.unwrap(), "1 accounts, 2 bytes");
+        assert!(matches!(
+            registry.invoke(&Pubkey::new([8; 32]), &[], &[], &mut state),
+            Err(AppError::ProgramNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_cpi_signs_for_program_address() {
+        let (registry, mut state, accounts) = vault_setup();
+        registry.invoke(&Vault.id(), &accounts, &vault_data(30, 0), &mut state).unwrap();
+        assert_eq!(state.get_account("Alice").unwrap().balance, 31);
+
+        // Without the seeds the vault's signature is an escalation
+        let result = registry.invoke(&Vault.id(), &accounts, &vault_data(30, 1), &mut state);
+        assert!(matches!(result, Err(AppError::PrivilegeEscalation(_))));
+
+        // Writable privileges cannot be upgraded either
+        let mut readonly = accounts.clone();
+        readonly[1].is_writable = false;
+        let result = registry.invoke(&Vault.id(), &readonly, &vault_data(30, 0), &mut state);
+        assert!(matches!(result, Err(AppError::PrivilegeEscalation(_))));
+        assert_eq!(state.get_account("Alice").unwrap().balance, 31);
+    }
+
+    #[test]
+    fn test_cpi_failure_rolls_back() {
+        let (registry, mut state, accounts) = vault_setup();
+        let result = registry.invoke(&Vault.id(), &accounts, &vault_data(30, 2), &mut state);
+        assert!(matches!(result, Err(AppError::InvalidAmount(30))));
+        assert_eq!(state.get_account("Alice").unwrap().balance, 1);
+        assert_eq!(state.get_account(&Vault::address().to_string()).unwrap().balance, 100);
+    }
+
+    /// Opens an escrow from its first account without asking the caller, then
+    /// fails if its data says so
+    struct Opener;
+
+    impl Program for Opener {
+        fn id(&self) -> Pubkey {
+            Pubkey::new(*b"OpenerProgram1111111111111111111")
+        }
+
+        fn process(&self, ctx: &mut InvokeContext, accounts: &[AccountMeta], data: &[u8]) -> Result<String> {
+            let depositor = account_at(accounts, 0)?.key.clone();
+            let state = ctx.state_mut();
+            state.create_account("Bob".into(), 1)?;
+            state.create_escrow("deal".into(), &depositor, "Bob", "Bob", 10, 100)?;
+            match data.first() {
+                Some(1) => Err(AppError::InvalidInstructionData("fail after writing".to_string())),
+                _ => Ok("opened".to_string()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cpi_failure_rolls_back_records() {
+        let mut registry = ProgramRegistry::with_builtins();
+        registry.register(Box::new(Opener)).unwrap();
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 100).unwrap();
+        let accounts = [AccountMeta::new("Alice".into(), true)];
+
+        let result = registry.invoke(&Opener.id(), &accounts, &[1], &mut state);
+        assert!(matches!(result, Err(AppError::InvalidInstructionData(_))));
+        assert!(matches!(state.get_escrow("deal"), Err(AppError::EscrowNotFound(_))));
+        assert!(!state.account_exists("Bob"));
+        assert_eq!(state.get_account("Alice").unwrap().balance, 100);
+
+        // The same call succeeding keeps the escrow and can release it
+        registry.invoke(&Opener.id(), &accounts, &[0], &mut state).unwrap();
+        state.release_escrow("deal", "Bob").unwrap();
+        assert_eq!(state.get_account("Bob").unwrap().balance, 11);
+    }
+
+    #[test]
+    fn test_programs_reach_only_passed_accounts() {
+        let registry = ProgramRegistry::with_builtins();
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 100).unwrap();
+        state.create_account("Bob".into(), 100).unwrap();
+        let accounts = [AccountMeta::new_readonly("Alice".into(), false)];
+        let mut ctx = InvokeContext {
+            registry: &registry,
+            state: &mut state,
+            program_id: Some(Echo.id()),
+            accounts: accounts.to_vec(),
+            depth: 1,
+        };
+
+        assert_eq!(ctx.account("Alice").unwrap().balance, 100);
+        assert!(matches!(ctx.account("Bob"), Err(AppError::PrivilegeEscalation(_))));
+        assert!(matches!(ctx.account_mut("Bob"), Err(AppError::PrivilegeEscalation(_))));
+        assert!(matches!(ctx.account_mut("Alice"), Err(AppError::AccountNotWritable(_))));
+    }
+
+    #[test]
+    fn test_cpi_depth_limit() {
+        let (registry, mut state, accounts) = vault_setup();
+        let result = registry.invoke(&Vault.id(), &accounts, &vault_data(30, 3), &mut state);
+        assert!(matches!(result, Err(AppError::CallDepthExceeded(MAX_CALL_DEPTH))));
+    }
+}
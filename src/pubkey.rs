@@ -0,0 +1,256 @@
+// ============================================
+// PUBLIC KEYS AND PROGRAM DERIVED ADDRESSES
+// ============================================
+// 32-byte addresses, base58 encoding, and PDA derivation with bump search
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::curve;
+use crate::error::{AppError, Result};
+use crate::hash;
+
+/// Length of a public key in bytes
+pub const PUBKEY_BYTES: usize = 32;
+/// Maximum number of seeds in one derivation (bump included)
+pub const MAX_SEEDS: usize = 16;
+/// Maximum length of a single seed
+pub const MAX_SEED_LEN: usize = 32;
+/// Marker appended to every PDA hash input
+const PDA_MARKER: &[u8] = b"ProgramDerivedAddress";
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// A 32-byte account address
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pubkey([u8; PUBKEY_BYTES]);
+
+/// Owner of every ordinary account (all zero bytes, as on Solana)
+pub const SYSTEM_PROGRAM_ID: Pubkey = Pubkey([0; PUBKEY_BYTES]);
+
+impl Pubkey {
+    /// Create a pubkey from raw bytes
+    pub const fn new(bytes: [u8; PUBKEY_BYTES]) -> Self {
+        Self(bytes)
+    }
+
+    /// Get the raw bytes
+    pub fn to_bytes(self) -> [u8; PUBKEY_BYTES] {
+        self.0
+    }
+
+    /// Check whether this key is a valid ed25519 point
+    pub fn is_on_curve(&self) -> bool {
+        curve::is_on_curve(&self.0)
+    }
+
+    /// Derive an address from `seeds` (bump included) and `program_id`.
+    /// Fails if the seeds are too long or the result lands on the curve.
+    pub fn create_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Result<Pubkey> {
+        let address = Pubkey::hash_seeds(seeds, program_id)?;
+        if address.is_on_curve() {
+            return Err(AppError::InvalidSeeds(
+                "derived address is on the ed25519 curve".to_string(),
+            ));
+        }
+        Ok(address)
+    }
+
+    /// Find the first off-curve address, trying bump seeds from 255 down to 0
+    pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Result<(Pubkey, u8)> {
+        for bump in (0..=u8::MAX).rev() {
+            let bump_seed = [bump];
+            let mut with_bump = seeds.to_vec();
+            with_bump.push(&bump_seed);
+            let address = Pubkey::hash_seeds(&with_bump, program_id)?;
+
+            if !address.is_on_curve() {
+                return Ok((address, bump));
+            }
+        }
+        Err(AppError::InvalidSeeds(
+            "no off-curve bump seed found".to_string(),
+        ))
+    }
+
+    /// Hash seeds, program ID and the PDA marker, without the curve check
+    fn hash_seeds(seeds: &[&[u8]], program_id: &Pubkey) -> Result<Pubkey> {
+        if seeds.len() > MAX_SEEDS {
+            return Err(AppError::InvalidSeeds(format!(
+                "{} seeds given, at most {} allowed",
+                seeds.len(),
+                MAX_SEEDS
+            )));
+        }
+        if let Some(seed) = seeds.iter().find(|seed| seed.len() > MAX_SEED_LEN) {
+            return Err(AppError::InvalidSeeds(format!(
+                "seed of {} bytes, at most {} allowed",
+                seed.len(),
+                MAX_SEED_LEN
+            )));
+        }
+
+        let mut hasher = hash::Hasher::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update(program_id.as_ref());
+        hasher.update(PDA_MARKER);
+        Ok(Pubkey(hasher.finalize()))
+    }
+
+    /// Check that `self` was derived from `seeds`, `bump` and `program_id`
+    pub fn verify_program_address(&self, seeds: &[&[u8]], bump: u8, program_id: &Pubkey) -> Result<()> {
+        let bump_seed = [bump];
+        let mut with_bump = seeds.to_vec();
+        with_bump.push(&bump_seed);
+
+        let derived = Pubkey::create_program_address(&with_bump, program_id)?;
+        if derived != *self {
+            return Err(AppError::InvalidProgramAddress(self.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl AsRef<[u8]> for Pubkey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<[u8; PUBKEY_BYTES]> for Pubkey {
+    fn from(bytes: [u8; PUBKEY_BYTES]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base58_encode(&self.0))
+    }
+}
+
+impl fmt::Debug for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base58_encode(&self.0))
+    }
+}
+
+impl FromStr for Pubkey {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = base58_decode(s).ok_or_else(|| AppError::InvalidPubkey(s.to_string()))?;
+        let bytes: [u8; PUBKEY_BYTES] = bytes
+            .try_into()
+            .map_err(|_| AppError::InvalidPubkey(s.to_string()))?;
+        Ok(Pubkey(bytes))
+    }
+}
+
+/// Encode bytes with the Bitcoin base58 alphabet
+pub fn base58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+
+    // Repeated division of the big-endian number by 58
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &bytes[zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut out = String::with_capacity(zeros + digits.len());
+    out.extend(std::iter::repeat_n('1', zeros));
+    out.extend(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize] as char));
+    out
+}
+
+/// Decode a base58 string, returning `None` on invalid characters
+pub fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    let zeros = s.chars().take_while(|&c| c == '1').count();
+
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.chars().skip(zeros) {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a as char == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut out = vec![0u8; zeros];
+    out.extend(bytes.iter().rev());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base58_roundtrip() {
+        assert_eq!(SYSTEM_PROGRAM_ID.to_string(), "11111111111111111111111111111111");
+        assert_eq!(base58_encode(b"hello world"), "StV1DL6CwTryKyV");
+
+        let key = Pubkey::new([7; PUBKEY_BYTES]);
+        assert_eq!(key.to_string().parse::<Pubkey>().unwrap(), key);
+        assert!("not-base58!".parse::<Pubkey>().is_err());
+        assert!("abc".parse::<Pubkey>().is_err());
+    }
+
+    #[test]
+    fn test_create_program_address_vectors() {
+        // Vectors from the Solana SDK
+        let program_id: Pubkey = "BPFLoaderUpgradeab1e11111111111111111111111".parse().unwrap();
+        let public_key: Pubkey = "SeedPubey1111111111111111111111111111111111".parse().unwrap();
+
+        assert_eq!(
+            Pubkey::create_program_address(&[b"", &[1]], &program_id).unwrap().to_string(),
+            "BwqrghZA2htAcqq8dzP1WDAhTXYTYWj7CHxF5j7TDBAe"
+        );
+        assert_eq!(
+            Pubkey::create_program_address(&["☉".as_ref(), &[0]], &program_id).unwrap().to_string(),
+            "13yWmRpaTR4r5nAktwLqMpRNr28tnVUZw26rTvPSSB19"
+        );
+        assert_eq!(
+            Pubkey::create_program_address(&[b"Talking", b"Squirrels"], &program_id).unwrap().to_string(),
+            "2fnQrngrQT4SeLcdToJAD96phoEjNL2man2kfRLCASVk"
+        );
+        assert_eq!(
+            Pubkey::create_program_address(&[public_key.as_ref(), &[1]], &program_id).unwrap().to_string(),
+            "976ymqVnfE32QFe6NfGDctSvVa36LWnvYxhU6G2232YL"
+        );
+
+        let too_long = [0u8; MAX_SEED_LEN + 1];
+        assert!(Pubkey::create_program_address(&[&too_long], &program_id).is_err());
+    }
+
+    #[test]
+    fn test_find_and_verify_program_address() {
+        let program_id = Pubkey::new([9; PUBKEY_BYTES]);
+        for i in 0..50u8 {
+            let seeds: [&[u8]; 2] = [b"vault", &[i]];
+            let (address, bump) = Pubkey::find_program_address(&seeds, &program_id).unwrap();
+            assert!(!address.is_on_curve());
+            assert!(address.verify_program_address(&seeds, bump, &program_id).is_ok());
+
+            // A different program cannot claim the same address
+            let other = Pubkey::new([8; PUBKEY_BYTES]);
+            assert!(address.verify_program_address(&seeds, bump, &other).is_err());
+        }
+    }
+}
@@ -0,0 +1,481 @@
+// ============================================
+// PUBSUB
+// ============================================
+// Solana-style subscriptions over WebSocket. Clients subscribe to an account,
+// a transaction signature or new slots, and are pushed a notification whenever
+// a block changes what they watch: the account's new contents, the final
+// status of the transaction, or the new slot. Subscription state lives apart
+// from the ledger; the block producer calls `Subscriptions::notify` after each
+// block. Locks are always taken ledger first, then subscriptions.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::block::Ledger;
+use crate::hash::{self, Hash};
+use crate::json::Json;
+use crate::mempool::TransactionStatus;
+use crate::rpc::{self, METHOD_NOT_FOUND, RpcError};
+use crate::websocket::{self, Message, opcode};
+
+/// How long a client may take to send its upgrade request
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Frame queued for a client's writer thread: (opcode, payload)
+type Frame = (u8, Vec<u8>);
+
+/// What a subscription watches
+#[derive(Debug, Clone, PartialEq)]
+enum Subscription {
+    Account { name: String, last: Json }, // Contents last sent (or seen at subscribe time)
+    Signature(Hash),
+    Slot { last: u64 },
+}
+
+impl Subscription {
+    /// `accountSubscribe` and so on
+    fn method(&self) -> &'static str {
+        match self {
+            Subscription::Account { .. } => "accountSubscribe",
+            Subscription::Signature(_) => "signatureSubscribe",
+            Subscription::Slot { .. } => "slotSubscribe",
+        }
+    }
+}
+
+/// Connected clients and what each is subscribed to
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    next_id: u64,
+    clients: HashMap<u64, Sender<Frame>>,
+    entries: BTreeMap<u64, (u64, Subscription)>, // Subscription ID -> (client, subscription)
+}
+
+/// Account contents as notified; null once the account is gone
+fn account_value(ledger: &Ledger, name: &str) -> Json {
+    ledger.state().get_account(name).map_or(Json::Null, rpc::account_json)
+}
+
+/// Signature notification value once `hash` is final
+fn signature_value(ledger: &Ledger, hash: &Hash) -> Option<Json> {
+    let err = match ledger.transaction_status(hash)? {
+        TransactionStatus::Pending => return None,
+        TransactionStatus::Processed { result: Ok(()), .. } => Json::Null,
+        TransactionStatus::Processed { result: Err(e), .. } | TransactionStatus::Dropped(e) => rpc::error_json(e),
+    };
+    Some(Json::object([("err", err)]))
+}
+
+fn slot_value(ledger: &Ledger, slot: u64) -> Json {
+    let parent = ledger.block(slot - 1).map(|block| Json::from(block.header.slot));
+    Json::object([("slot", Json::from(slot)), ("parent", parent.unwrap_or(Json::Null))])
+}
+
+fn notification(method: &str, subscription: u64, result: Json) -> Json {
+    Json::object([
+        ("jsonrpc", Json::from("2.0")),
+        ("method", Json::from(method)),
+        ("params", Json::object([("result", result), ("subscription", Json::from(subscription))])),
+    ])
+}
+
+impl Subscriptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of active subscriptions
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Register a client whose frames go to `sender`; returns its ID
+    fn connect(&mut self, sender: Sender<Frame>) -> u64 {
+        self.next_id += 1;
+        self.clients.insert(self.next_id, sender);
+        self.next_id
+    }
+
+    /// Forget a client and everything it subscribed to
+    fn disconnect(&mut self, client: u64) {
+        self.clients.remove(&client);
+        self.entries.retain(|_, (owner, _)| *owner != client);
+    }
+
+    /// Queue a text message for `client`. A client whose writer is gone is
+    /// disconnected.
+    fn send(&mut self, client: u64, message: &Json) {
+        let delivered = self
+            .clients
+            .get(&client)
+            .is_some_and(|sender| sender.send((opcode::TEXT, message.to_string().into_bytes())).is_ok());
+        if !delivered {
+            self.disconnect(client);
+        }
+    }
+
+    /// Answer one request from `client`, then send anything already due
+    fn handle(&mut self, client: u64, ledger: &Ledger, text: &str) {
+        let call = match Json::parse(text) {
+            Ok(call) => call,
+            Err(e) => {
+                let response = rpc::error_response(Json::Null, RpcError::new(rpc::PARSE_ERROR, e));
+                return self.send(client, &response);
+            }
+        };
+        let id = call.get("id").cloned().unwrap_or(Json::Null);
+        let response = match self.call(client, ledger, &call) {
+            Ok(result) => Json::object([("jsonrpc", Json::from("2.0")), ("result", result), ("id", id)]),
+            Err(error) => rpc::error_response(id, error),
+        };
+        self.send(client, &response);
+        // A signature may already be final when subscribed to
+        self.notify(ledger);
+    }
+
+    fn call(&mut self, client: u64, ledger: &Ledger, call: &Json) -> Result<Json, RpcError> {
+        let method = match (call.get("jsonrpc").and_then(Json::as_str), call.get("method").and_then(Json::as_str)) {
+            (Some("2.0"), Some(method)) => method,
+            _ => return Err(RpcError::new(rpc::INVALID_REQUEST, "expected a JSON-RPC 2.0 call")),
+        };
+        let params = match call.get("params") {
+            None => &[][..],
+            Some(params) => params.as_array().ok_or_else(|| RpcError::invalid_params("params must be an array"))?,
+        };
+
+        let subscription = match method {
+            "accountSubscribe" => {
+                let name = rpc::str_param(params, 0, "account")?;
+                let last = account_value(ledger, &name);
+                Subscription::Account { name, last }
+            }
+            "signatureSubscribe" => {
+                let hash = hash::from_hex(&rpc::str_param(params, 0, "transaction hash")?)
+                    .ok_or_else(|| RpcError::invalid_params("transaction hash must be 64 hex digits"))?;
+                Subscription::Signature(hash)
+            }
+            "slotSubscribe" => Subscription::Slot {
+                last: ledger.latest_block().header.slot,
+            },
+            "accountUnsubscribe" | "signatureUnsubscribe" | "slotUnsubscribe" => {
+                let id = params
+                    .first()
+                    .and_then(Json::as_u64)
+                    .ok_or_else(|| RpcError::invalid_params("expected subscription ID as parameter 0"))?;
+                return Ok(Json::from(self.unsubscribe(client, id, &method.replace("Unsubscribe", "Subscribe"))));
+            }
+            _ => return Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        };
+
+        self.next_id += 1;
+        self.entries.insert(self.next_id, (client, subscription));
+        Ok(Json::from(self.next_id))
+    }
+
+    /// Remove `client`'s subscription `id` made with `method`; false if there is none
+    fn unsubscribe(&mut self, client: u64, id: u64, method: &str) -> bool {
+        match self.entries.get(&id) {
+            Some((owner, subscription)) if *owner == client && subscription.method() == method => {
+                self.entries.remove(&id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Push notifications for everything that changed since the last call.
+    /// Signature subscriptions end once their notification is sent.
+    pub fn notify(&mut self, ledger: &Ledger) {
+        let latest = ledger.latest_block().header.slot;
+        let mut outgoing = Vec::new();
+        let mut finished = Vec::new();
+
+        for (&id, (client, subscription)) in self.entries.iter_mut() {
+            match subscription {
+                Subscription::Account { name, last } => {
+                    let value = account_value(ledger, name);
+                    if value != *last {
+                        *last = value.clone();
+                        outgoing.push((*client, notification("accountNotification", id, rpc::with_context(ledger, value))));
+                    }
+                }
+                Subscription::Signature(hash) => {
+                    if let Some(value) = signature_value(ledger, hash) {
+                        outgoing.push((*client, notification("signatureNotification", id, rpc::with_context(ledger, value))));
+                        finished.push(id);
+                    }
+                }
+                Subscription::Slot { last } => {
+                    for slot in *last + 1..=latest {
+                        outgoing.push((*client, notification("slotNotification", id, slot_value(ledger, slot))));
+                    }
+                    *last = latest;
+                }
+            }
+        }
+
+        for id in finished {
+            self.entries.remove(&id);
+        }
+        for (client, message) in outgoing {
+            self.send(client, &message);
+        }
+    }
+}
+
+/// WebSocket server pushing notifications about a shared ledger
+pub struct PubSubServer {
+    listener: TcpListener,
+    ledger: Arc<Mutex<Ledger>>,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+}
+
+impl PubSubServer {
+    /// Listen on `addr`; port 0 picks a free port
+    pub fn bind(addr: impl ToSocketAddrs, ledger: Arc<Mutex<Ledger>>) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            ledger,
+            subscriptions: Arc::new(Mutex::new(Subscriptions::new())),
+        })
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Subscription state; call `notify` on it after producing a block
+    pub fn subscriptions(&self) -> Arc<Mutex<Subscriptions>> {
+        Arc::clone(&self.subscriptions)
+    }
+
+    /// Serve connections forever, two threads each
+    pub fn serve(self) {
+        for stream in self.listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let ledger = Arc::clone(&self.ledger);
+            let subscriptions = Arc::clone(&self.subscriptions);
+            thread::spawn(move || {
+                // A client that hangs up is not the server's problem
+                let _ = handle_connection(stream, &ledger, &subscriptions);
+            });
+        }
+    }
+
+    /// Serve on a background thread
+    pub fn spawn(self) -> JoinHandle<()> {
+        thread::spawn(move || self.serve())
+    }
+}
+
+/// Lock the subscriptions, carrying on if a connection panicked while holding them
+pub fn lock(subscriptions: &Mutex<Subscriptions>) -> MutexGuard<'_, Subscriptions> {
+    subscriptions.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Upgrade the connection, then read requests until the client leaves. A
+/// second thread writes queued frames so notifications never wait on reads.
+fn handle_connection(
+    stream: TcpStream,
+    ledger: &Mutex<Ledger>,
+    subscriptions: &Mutex<Subscriptions>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    if !websocket::handshake(&mut reader, &mut writer)? {
+        return Ok(());
+    }
+    // Subscribers may stay quiet for as long as they like
+    writer.set_read_timeout(None)?;
+
+    let (sender, receiver) = mpsc::channel::<Frame>();
+    thread::spawn(move || {
+        for (opcode, payload) in receiver {
+            if websocket::write_frame(&mut writer, opcode, &payload).is_err() || opcode == opcode::CLOSE {
+                break;
+            }
+        }
+    });
+    let client = lock(subscriptions).connect(sender.clone());
+
+    let result = loop {
+        match websocket::read_message(&mut reader) {
+            Ok(Message::Text(text)) => {
+                let ledger = rpc::lock(ledger);
+                lock(subscriptions).handle(client, &ledger, &text);
+            }
+            Ok(Message::Ping(payload)) => {
+                let _ = sender.send((opcode::PONG, payload));
+            }
+            Ok(Message::Binary(_) | Message::Pong(_)) => {}
+            Ok(Message::Close) => {
+                let _ = sender.send((opcode::CLOSE, Vec::new()));
+                break Ok(());
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    lock(subscriptions).disconnect(client);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction;
+    use crate::state::AppState;
+    use crate::transaction::Transaction;
+    use std::io::Write;
+    use std::sync::mpsc::Receiver;
+
+    fn ledger() -> Ledger {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+        Ledger::new(state)
+    }
+
+    fn subscribe(subscriptions: &mut Subscriptions, client: u64, ledger: &Ledger, method: &str, params: Json) {
+        let call = Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("id", Json::from(1u32)),
+            ("method", Json::from(method)),
+            ("params", params),
+        ]);
+        subscriptions.handle(client, ledger, &call.to_string());
+    }
+
+    fn received(receiver: &Receiver<Frame>) -> Vec<Json> {
+        receiver
+            .try_iter()
+            .map(|(_, payload)| Json::parse(std::str::from_utf8(&payload).unwrap()).unwrap())
+            .collect()
+    }
+
+    fn method(message: &Json) -> Option<&str> {
+        message.get("method").and_then(Json::as_str)
+    }
+
+    #[test]
+    fn test_notifications() {
+        let mut ledger = ledger();
+        let mut subscriptions = Subscriptions::new();
+        let (sender, receiver) = mpsc::channel();
+        let client = subscriptions.connect(sender);
+
+        let transfer = Transaction::new(
+            vec![Instruction::transfer("Alice".into(), "Bob".into(), 100)],
+            ledger.recent_blockhash(),
+        );
+        let signature = ledger.submit(transfer).unwrap();
+        subscribe(&mut subscriptions, client, &ledger, "accountSubscribe", Json::from(vec![Json::from("Bob")]));
+        subscribe(&mut subscriptions, client, &ledger, "signatureSubscribe", Json::from(vec![Json::from(hash::to_hex(&signature))]));
+        subscribe(&mut subscriptions, client, &ledger, "slotSubscribe", Json::Array(Vec::new()));
+        subscribe(&mut subscriptions, client, &ledger, "fooSubscribe", Json::Array(Vec::new()));
+        let responses = received(&receiver);
+        let ids: Vec<_> = responses[..3].iter().map(|r| r.get("result").and_then(Json::as_u64).unwrap()).collect();
+        let error = responses[3].get("error").and_then(|e| e.get("code")).map(Json::to_string);
+        assert_eq!(error.as_deref(), Some("-32601"));
+        assert_eq!(subscriptions.len(), 3);
+
+        ledger.produce_block();
+        subscriptions.notify(&ledger);
+        let messages = received(&receiver);
+        assert_eq!(messages.len(), 3);
+        let bob = messages.iter().find(|m| method(m) == Some("accountNotification")).unwrap();
+        let value = bob.get("params").and_then(|p| p.get("result")).and_then(|r| r.get("value")).unwrap();
+        assert_eq!(value.get("lamports").and_then(Json::as_u64), Some(101));
+        let status = messages.iter().find(|m| method(m) == Some("signatureNotification")).unwrap();
+        assert_eq!(status.get("params").and_then(|p| p.get("subscription")).and_then(Json::as_u64), Some(ids[1]));
+        assert!(messages.iter().any(|m| method(m) == Some("slotNotification")));
+
+        // The signature subscription ended; the account did not change again
+        assert_eq!(subscriptions.len(), 2);
+        ledger.produce_block();
+        subscriptions.notify(&ledger);
+        let messages = received(&receiver);
+        assert_eq!(messages.iter().map(method).collect::<Vec<_>>(), [Some("slotNotification")]);
+
+        // Unsubscribing checks the kind of subscription
+        subscribe(&mut subscriptions, client, &ledger, "slotUnsubscribe", Json::from(vec![Json::from(ids[0])]));
+        subscribe(&mut subscriptions, client, &ledger, "slotUnsubscribe", Json::from(vec![Json::from(ids[2])]));
+        let results: Vec<_> = received(&receiver).iter().map(|r| r.get("result").and_then(Json::as_bool)).collect();
+        assert_eq!(results, [Some(false), Some(true)]);
+
+        // A signature that is already final is notified right away
+        subscribe(&mut subscriptions, client, &ledger, "signatureSubscribe", Json::from(vec![Json::from(hash::to_hex(&signature))]));
+        let messages = received(&receiver);
+        assert_eq!(method(&messages[1]), Some("signatureNotification"));
+
+        subscriptions.disconnect(client);
+        assert!(subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_websocket_over_loopback() {
+        let ledger = Arc::new(Mutex::new(ledger()));
+        let server = PubSubServer::bind("127.0.0.1:0", Arc::clone(&ledger)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let subscriptions = server.subscriptions();
+        server.spawn();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let head = rpc::read_head(&mut reader).unwrap().unwrap();
+        assert_eq!(head.header("sec-websocket-accept"), Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+        // Clients mask their frames
+        let request = br#"{"jsonrpc":"2.0","id":1,"method":"accountSubscribe","params":["Alice"]}"#;
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![0x80 | opcode::TEXT, 0x80 | request.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(request.iter().enumerate().map(|(index, byte)| byte ^ mask[index % 4]));
+        stream.write_all(&frame).unwrap();
+
+        let Message::Text(response) = websocket::read_message(&mut reader).unwrap() else {
+            panic!("expected a text message");
+        };
+        assert!(Json::parse(&response).unwrap().get("result").and_then(Json::as_u64).is_some());
+
+        {
+            let mut ledger = rpc::lock(&ledger);
+            let transfer = Transaction::new(
+                vec![Instruction::transfer("Alice".into(), "Bob".into(), 10)],
+                ledger.recent_blockhash(),
+            );
+            ledger.submit(transfer).unwrap();
+            ledger.produce_block();
+            lock(&subscriptions).notify(&ledger);
+        }
+        let Message::Text(notification) = websocket::read_message(&mut reader).unwrap() else {
+            panic!("expected a text message");
+        };
+        let notification = Json::parse(&notification).unwrap();
+        assert_eq!(method(&notification), Some("accountNotification"));
+        let value = notification.get("params").and_then(|p| p.get("result")).and_then(|r| r.get("value"));
+        assert_eq!(value.and_then(|v| v.get("lamports")).and_then(Json::as_u64), Some(990));
+
+        stream.write_all(&[0x80 | opcode::CLOSE, 0x80, 0, 0, 0, 0]).unwrap();
+        assert_eq!(websocket::read_message(&mut reader).unwrap(), Message::Close);
+    }
+}
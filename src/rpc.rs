@@ -0,0 +1,764 @@
+// ============================================
+// JSON-RPC SERVER
+// ============================================
+// JSON-RPC 2.0 over HTTP in front of the ledger, with methods modeled on
+// Solana's. Each connection carries one POST request (or a batch). Transactions
+// travel as their encoded `Transaction::message`, in base58 by default or
+// base64; hashes are hex. Failures from the ledger carry `AppError::code()` in
+// `error.data.code`. Requests must be `application/json`, and browser pages
+// may only call from origins the server was configured with, so a web page
+// cannot post transactions to a local node behind its user's back.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::block::{Block, Ledger};
+use crate::blockhash::MAX_RECENT_BLOCKHASHES;
+use crate::codec;
+use crate::error::AppError;
+use crate::hash::{self, Hash};
+use crate::json::Json;
+use crate::mempool::TransactionStatus;
+use crate::pubkey::{self, Pubkey};
+use crate::state::{Account, BalanceChange};
+use crate::transaction::Transaction;
+
+/// Invalid JSON was received
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON is not a valid request object
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Missing or malformed parameters
+pub const INVALID_PARAMS: i64 = -32602;
+/// The ledger rejected the call; `data.code` holds the `AppError` code
+pub const LEDGER_ERROR: i64 = -32000;
+
+/// Largest request body accepted
+pub const MAX_REQUEST_BYTES: usize = 1 << 20;
+/// Largest request line plus headers accepted
+const MAX_HEADER_BYTES: usize = 8 * 1024;
+/// How long a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Balance changes per `getAccountHistory` page unless the caller asks otherwise
+const DEFAULT_HISTORY_PAGE: usize = 100;
+/// Most balance changes returned in one `getAccountHistory` page
+pub const MAX_HISTORY_PAGE: usize = 1000;
+/// Accounts returned by `getLargestAccounts` unless the caller asks otherwise
+const DEFAULT_LARGEST_ACCOUNTS: usize = 20;
+/// Most accounts returned by one `getLargestAccounts` call
+pub const MAX_LARGEST_ACCOUNTS: usize = 1000;
+
+/// Error object of a JSON-RPC response
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Json>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        RpcError::new(INVALID_PARAMS, message)
+    }
+
+    fn to_json(&self) -> Json {
+        let mut fields = vec![("code", Json::from(self.code)), ("message", Json::from(self.message.as_str()))];
+        if let Some(data) = &self.data {
+            fields.push(("data", data.clone()));
+        }
+        Json::object(fields)
+    }
+}
+
+impl From<AppError> for RpcError {
+    fn from(e: AppError) -> Self {
+        Self {
+            code: LEDGER_ERROR,
+            message: e.to_string(),
+            data: Some(Json::object([("code", Json::from(e.code()))])),
+        }
+    }
+}
+
+type RpcResult = Result<Json, RpcError>;
+
+/// HTTP server answering JSON-RPC calls against a shared ledger
+pub struct RpcServer {
+    listener: TcpListener,
+    ledger: Arc<Mutex<Ledger>>,
+    allowed_origins: Arc<Vec<String>>,
+}
+
+impl RpcServer {
+    /// Listen on `addr`; port 0 picks a free port
+    pub fn bind(addr: impl ToSocketAddrs, ledger: Arc<Mutex<Ledger>>) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            ledger,
+            allowed_origins: Arc::new(Vec::new()),
+        })
+    }
+
+    /// Let browser pages from `origins` (such as "http://localhost:3000")
+    /// call the server; requests from any other page are refused
+    pub fn with_allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = Arc::new(origins);
+        self
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve connections forever, one thread each
+    pub fn serve(self) {
+        for stream in self.listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let ledger = Arc::clone(&self.ledger);
+            let allowed_origins = Arc::clone(&self.allowed_origins);
+            thread::spawn(move || {
+                // A client that hangs up early is not the server's problem
+                let _ = handle_connection(stream, &ledger, &allowed_origins);
+            });
+        }
+    }
+
+    /// Serve on a background thread
+    pub fn spawn(self) -> JoinHandle<()> {
+        thread::spawn(move || self.serve())
+    }
+}
+
+/// Lock the ledger, carrying on if another request panicked while holding it
+pub fn lock(ledger: &Mutex<Ledger>) -> MutexGuard<'_, Ledger> {
+    ledger.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Method and headers of an HTTP request
+#[derive(Debug, Clone, Default)]
+pub struct RequestHead {
+    pub method: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    /// Value of header `name`, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Read the request line and headers, up to MAX_HEADER_BYTES. `None` if the
+/// client hung up or sent too much.
+pub fn read_head(reader: &mut impl BufRead) -> io::Result<Option<RequestHead>> {
+    let mut head = RequestHead::default();
+    let mut head_bytes = 0;
+    let mut first = true;
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line)?;
+        head_bytes += read;
+        if read == 0 || head_bytes > MAX_HEADER_BYTES {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if first {
+            head.method = line.split_whitespace().next().unwrap_or_default().to_string();
+            first = false;
+        } else if line.is_empty() {
+            return Ok(Some(head));
+        } else if let Some((name, value)) = line.split_once(':') {
+            head.headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+}
+
+/// Check that the request body is declared as JSON
+fn is_json(head: &RequestHead) -> bool {
+    head.header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Read one HTTP request from `stream` and answer it
+fn handle_connection(stream: TcpStream, ledger: &Mutex<Ledger>, allowed_origins: &[String]) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?.take((MAX_HEADER_BYTES + MAX_REQUEST_BYTES) as u64));
+    let mut stream = stream;
+
+    let Some(head) = read_head(&mut reader)? else {
+        return write_response(&mut stream, "431 Request Header Fields Too Large", "", None);
+    };
+    let content_length = head
+        .header("content-length")
+        .map_or(0, |value| value.parse().unwrap_or(usize::MAX));
+
+    // Browsers name the page a request comes from; other clients send none
+    let origin = match head.header("origin") {
+        Some(origin) if allowed_origins.iter().any(|allowed| allowed == origin) => Some(origin),
+        Some(_) => return write_response(&mut stream, "403 Forbidden", "", None),
+        None => None,
+    };
+    let mut respond = |status: &str, body: &str| write_response(&mut stream, status, body, origin);
+
+    match head.method.as_str() {
+        // CORS preflight from browser front-ends
+        "OPTIONS" => respond("204 No Content", ""),
+        "POST" if content_length > MAX_REQUEST_BYTES => respond("413 Payload Too Large", ""),
+        "POST" if !is_json(&head) => respond("415 Unsupported Media Type", ""),
+        "POST" => {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            let response = match String::from_utf8(body) {
+                Ok(body) => handle_request(ledger, &body),
+                Err(_) => error_response(Json::Null, RpcError::new(PARSE_ERROR, "request is not UTF-8")).to_string(),
+            };
+            respond("200 OK", &response)
+        }
+        _ => respond("405 Method Not Allowed", ""),
+    }
+}
+
+/// Write the response; `origin` is an allowed page origin to grant CORS access to
+fn write_response(stream: &mut TcpStream, status: &str, body: &str, origin: Option<&str>) -> io::Result<()> {
+    let cors = match origin {
+        Some(origin) => format!(
+            "Access-Control-Allow-Origin: {}\r\n\
+             Access-Control-Allow-Methods: POST, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Content-Type\r\n\
+             Vary: Origin\r\n",
+            origin
+        ),
+        None => String::new(),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         {}\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        cors,
+        body
+    )?;
+    stream.flush()
+}
+
+/// Answer a JSON-RPC request body (a single call or a batch)
+pub fn handle_request(ledger: &Mutex<Ledger>, body: &str) -> String {
+    let response = match Json::parse(body) {
+        Err(e) => error_response(Json::Null, RpcError::new(PARSE_ERROR, e)),
+        Ok(Json::Array(calls)) if calls.is_empty() => {
+            error_response(Json::Null, RpcError::new(INVALID_REQUEST, "empty batch"))
+        }
+        Ok(Json::Array(calls)) => Json::Array(calls.iter().map(|call| handle_call(ledger, call)).collect()),
+        Ok(call) => handle_call(ledger, &call),
+    };
+    response.to_string()
+}
+
+/// Response object carrying `error` for call `id`
+pub fn error_response(id: Json, error: RpcError) -> Json {
+    Json::object([("jsonrpc", Json::from("2.0")), ("error", error.to_json()), ("id", id)])
+}
+
+/// Answer one call object
+fn handle_call(ledger: &Mutex<Ledger>, call: &Json) -> Json {
+    let id = call.get("id").cloned().unwrap_or(Json::Null);
+    let method = match (call.get("jsonrpc").and_then(Json::as_str), call.get("method").and_then(Json::as_str)) {
+        (Some("2.0"), Some(method)) => method,
+        _ => return error_response(id, RpcError::new(INVALID_REQUEST, "expected a JSON-RPC 2.0 call")),
+    };
+    let params = match call.get("params") {
+        None => &[][..],
+        Some(params) => match params.as_array() {
+            Some(params) => params,
+            None => return error_response(id, RpcError::invalid_params("params must be an array")),
+        },
+    };
+
+    let mut ledger = lock(ledger);
+    let result = match method {
+        "getBalance" => get_balance(&ledger, params),
+        "getAccountInfo" => get_account_info(&ledger, params),
+        "getProgramAccounts" => get_program_accounts(&ledger, params),
+        "getLargestAccounts" => get_largest_accounts(&ledger, params),
+        "getSupply" => Ok(supply(&ledger)),
+        "getAccountHistory" => get_account_history(&ledger, params),
+        "getBalanceAt" => get_balance_at(&ledger, params),
+        "getLatestBlockhash" => Ok(latest_blockhash(&ledger)),
+        "getSlot" => Ok(Json::from(ledger.state().current_slot())),
+        "sendTransaction" => send_transaction(&mut ledger, params),
+        "simulateTransaction" => simulate_transaction(&ledger, params),
+        "getTransaction" => get_transaction(&ledger, params),
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {}", method))),
+    };
+    match result {
+        Ok(result) => Json::object([("jsonrpc", Json::from("2.0")), ("result", result), ("id", id)]),
+        Err(error) => error_response(id, error),
+    }
+}
+
+/// String parameter at `index`
+pub fn str_param(params: &[Json], index: usize, name: &str) -> Result<String, RpcError> {
+    params
+        .get(index)
+        .and_then(Json::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| RpcError::invalid_params(format!("expected {} as parameter {}", name, index)))
+}
+
+/// Solana-style result carrying the slot it was read at
+pub fn with_context(ledger: &Ledger, value: Json) -> Json {
+    Json::object([
+        ("context", Json::object([("slot", Json::from(ledger.state().current_slot()))])),
+        ("value", value),
+    ])
+}
+
+/// Account contents as returned by `getAccountInfo`
+pub fn account_json(account: &Account) -> Json {
+    let tokens = account.tokens.iter().map(|(mint, amount)| (mint.as_str(), Json::from(*amount)));
+    Json::object([
+        ("name", Json::from(account.name.as_str())),
+        ("lamports", Json::from(account.balance)),
+        ("owner", Json::from(account.owner.to_string())),
+        ("executable", Json::from(account.executable)),
+        ("data", Json::from(vec![Json::from(codec::base64_encode(&account.data)), Json::from("base64")])),
+        ("address", Json::from(account.address.map(|address| address.to_string()))),
+        ("frozen", Json::from(account.frozen)),
+        ("tokens", Json::object(tokens)),
+    ])
+}
+
+/// Error inside a successful response (a failed transaction, not a failed call)
+pub fn error_json(e: &AppError) -> Json {
+    Json::object([("code", Json::from(e.code())), ("message", Json::from(e.to_string()))])
+}
+
+/// `getBalance [account]`
+fn get_balance(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let name = str_param(params, 0, "account")?;
+    let balance = ledger.state().get_account(&name)?.balance;
+    Ok(with_context(ledger, Json::from(balance)))
+}
+
+/// `getAccountInfo [account]`; null when the account does not exist
+fn get_account_info(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let name = str_param(params, 0, "account")?;
+    let account = ledger.state().get_account(&name).ok().map(account_json);
+    Ok(with_context(ledger, account.unwrap_or(Json::Null)))
+}
+
+/// `getProgramAccounts [owner program ID]`
+fn get_program_accounts(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let owner = Pubkey::from_str(&str_param(params, 0, "program ID")?)?;
+    let accounts = ledger
+        .state()
+        .program_accounts(&owner)
+        .into_iter()
+        .map(|account| Json::object([("pubkey", Json::from(account.name.as_str())), ("account", account_json(account))]))
+        .collect::<Vec<_>>();
+    Ok(Json::from(accounts))
+}
+
+/// `getSupply []`: lamports across all accounts
+fn supply(ledger: &Ledger) -> Json {
+    with_context(ledger, Json::object([("total", Json::from(ledger.state().total_balance()))]))
+}
+
+/// `getLargestAccounts [{limit}?]`: largest balances first
+fn get_largest_accounts(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let limit = match params.first().and_then(|config| config.get("limit")) {
+        None => DEFAULT_LARGEST_ACCOUNTS,
+        Some(limit) => limit
+            .as_u64()
+            .ok_or_else(|| RpcError::invalid_params("limit must be a non-negative integer"))?
+            .min(MAX_LARGEST_ACCOUNTS as u64) as usize,
+    };
+    let accounts = ledger
+        .state()
+        .largest_accounts(limit)
+        .into_iter()
+        .map(|account| Json::object([("address", Json::from(account.name.as_str())), ("lamports", Json::from(account.balance))]))
+        .collect::<Vec<_>>();
+    Ok(with_context(ledger, Json::from(accounts)))
+}
+
+/// `getAccountHistory [account, {fromSlot, toSlot, cursor, limit}?]`: one
+/// page of balance changes, oldest first, with the cursor of the next
+fn get_account_history(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let name = str_param(params, 0, "account")?;
+    let config = params.get(1).unwrap_or(&Json::Null);
+    let option = |key: &str| match config.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| RpcError::invalid_params(format!("{} must be a non-negative integer", key))),
+    };
+    let from = option("fromSlot")?.unwrap_or(0);
+    let to = option("toSlot")?.unwrap_or(u64::MAX);
+    let cursor = option("cursor")?.unwrap_or(0) as usize;
+    let limit = option("limit")?.unwrap_or(DEFAULT_HISTORY_PAGE as u64).min(MAX_HISTORY_PAGE as u64) as usize;
+
+    let page = ledger.state().history(&name, from..=to, cursor, limit);
+    let changes = page.changes.iter().map(balance_change_json).collect::<Vec<_>>();
+    let value = Json::object([
+        ("changes", Json::from(changes)),
+        ("next", Json::from(page.next)),
+    ]);
+    Ok(with_context(ledger, value))
+}
+
+/// `getBalanceAt [account, slot]`
+fn get_balance_at(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let name = str_param(params, 0, "account")?;
+    let slot = params
+        .get(1)
+        .and_then(Json::as_u64)
+        .ok_or_else(|| RpcError::invalid_params("expected slot as parameter 1"))?;
+    let balance = ledger.state().balance_at(&name, slot)?;
+    Ok(with_context(ledger, Json::from(balance)))
+}
+
+/// One entry of `getAccountHistory`
+fn balance_change_json(change: &BalanceChange) -> Json {
+    Json::object([
+        ("slot", Json::from(change.slot)),
+        ("timestamp", Json::from(change.timestamp)),
+        ("delta", Json::from(change.delta())),
+        ("balance", Json::from(change.balance)),
+        ("counterparty", Json::from(change.counterparty.clone())),
+        ("instruction", Json::from(change.instruction)),
+    ])
+}
+
+/// `getLatestBlockhash []`
+fn latest_blockhash(ledger: &Ledger) -> Json {
+    let slot = ledger.state().current_slot();
+    let value = Json::object([
+        ("blockhash", Json::from(hash::to_hex(&ledger.recent_blockhash()))),
+        ("lastValidSlot", Json::from(slot + MAX_RECENT_BLOCKHASHES as u64 - 1)),
+    ]);
+    with_context(ledger, value)
+}
+
+/// Decode the transaction in parameter 0, encoded as named by `{"encoding"}` in parameter 1
+fn transaction_param(params: &[Json]) -> Result<Transaction, RpcError> {
+    let encoded = str_param(params, 0, "encoded transaction")?;
+    let encoding = params.get(1).and_then(|config| config.get("encoding")).and_then(Json::as_str);
+    let bytes = match encoding.unwrap_or("base58") {
+        "base58" => pubkey::base58_decode(&encoded),
+        "base64" => codec::base64_decode(&encoded),
+        other => return Err(RpcError::invalid_params(format!("unsupported encoding {}", other))),
+    }
+    .ok_or_else(|| RpcError::invalid_params("transaction is not validly encoded"))?;
+    Transaction::decode(&bytes).map_err(|e| RpcError::invalid_params(e.to_string()))
+}
+
+/// `sendTransaction [encoded, {encoding}]`; returns the transaction hash
+fn send_transaction(ledger: &mut Ledger, params: &[Json]) -> RpcResult {
+    let hash = ledger.submit(transaction_param(params)?)?;
+    Ok(Json::from(hash::to_hex(&hash)))
+}
+
+/// `simulateTransaction [encoded, {encoding}]`; runs against a copy of the state
+fn simulate_transaction(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let transaction = transaction_param(params)?;
+    let value = match ledger.simulate(&transaction) {
+        Ok(logs) => Json::object([
+            ("err", Json::Null),
+            ("logs", Json::from(logs.into_iter().map(Json::from).collect::<Vec<_>>())),
+        ]),
+        Err(e) => Json::object([("err", error_json(&e)), ("logs", Json::from(Vec::new()))]),
+    };
+    Ok(with_context(ledger, value))
+}
+
+fn transaction_json(transaction: &Transaction) -> Json {
+    let instructions = transaction.instructions.iter().map(|instruction| Json::from(instruction.description()));
+    let signatures = transaction.signatures.iter().map(|(_, signature)| Json::from(signature.to_string()));
+    Json::object([
+        ("recentBlockhash", Json::from(hash::to_hex(&transaction.recent_blockhash))),
+        ("priorityFee", Json::from(transaction.priority_fee)),
+        ("instructions", Json::from(instructions.collect::<Vec<_>>())),
+        ("signatures", Json::from(signatures.collect::<Vec<_>>())),
+    ])
+}
+
+fn executed_json(block: &Block, index: usize) -> Json {
+    let (err, logs) = match &block.results[index] {
+        Ok(logs) => (Json::Null, logs.iter().map(|log| Json::from(log.as_str())).collect()),
+        Err(e) => (error_json(e), Vec::new()),
+    };
+    Json::object([
+        ("status", Json::from("processed")),
+        ("slot", Json::from(block.header.slot)),
+        ("blockHash", Json::from(hash::to_hex(&block.hash))),
+        ("err", err),
+        ("logs", Json::from(logs)),
+        ("transaction", transaction_json(&block.transactions[index])),
+    ])
+}
+
+/// `getTransaction [hash]`; null for transactions the ledger never saw or forgot
+fn get_transaction(ledger: &Ledger, params: &[Json]) -> RpcResult {
+    let hash: Hash = hash::from_hex(&str_param(params, 0, "transaction hash")?)
+        .ok_or_else(|| RpcError::invalid_params("transaction hash must be 64 hex digits"))?;
+    if let Some((block, index)) = ledger.find_transaction(&hash) {
+        return Ok(executed_json(block, index));
+    }
+    Ok(match ledger.transaction_status(&hash) {
+        Some(TransactionStatus::Pending) => Json::object([("status", Json::from("pending"))]),
+        Some(TransactionStatus::Dropped(e)) => {
+            Json::object([("status", Json::from("dropped")), ("err", error_json(e))])
+        }
+        // Processed transactions are found in their block above
+        Some(TransactionStatus::Processed { .. }) | None => Json::Null,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction;
+    use crate::state::AppState;
+
+    fn ledger() -> Mutex<Ledger> {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+        Mutex::new(Ledger::new(state))
+    }
+
+    fn call(ledger: &Mutex<Ledger>, method: &str, params: Json) -> Json {
+        let request = Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("id", Json::from(7u32)),
+            ("method", Json::from(method)),
+            ("params", params),
+        ]);
+        Json::parse(&handle_request(ledger, &request.to_string())).unwrap()
+    }
+
+    fn encoded_transfer(ledger: &Mutex<Ledger>, amount: u64) -> (Hash, String) {
+        let transaction = Transaction::new(
+            vec![Instruction::transfer("Alice".into(), "Bob".into(), amount)],
+            lock(ledger).recent_blockhash(),
+        );
+        (transaction.hash(), codec::base64_encode(&transaction.encode()))
+    }
+
+    fn base64() -> Json {
+        Json::object([("encoding", Json::from("base64"))])
+    }
+
+    #[test]
+    fn test_account_queries() {
+        let ledger = ledger();
+        let response = call(&ledger, "getBalance", Json::from(vec![Json::from("Alice")]));
+        assert_eq!(response.get("id").and_then(Json::as_u64), Some(7));
+        assert_eq!(response.get("result").and_then(|r| r.get("value")).and_then(Json::as_u64), Some(1000));
+
+        let info = call(&ledger, "getAccountInfo", Json::from(vec![Json::from("Bob")]));
+        let value = info.get("result").and_then(|r| r.get("value")).unwrap();
+        assert_eq!(value.get("lamports").and_then(Json::as_u64), Some(1));
+        assert_eq!(value.get("owner").and_then(Json::as_str), Some(Pubkey::new([0; 32]).to_string().as_str()));
+        let missing = call(&ledger, "getAccountInfo", Json::from(vec![Json::from("Nobody")]));
+        assert!(missing.get("result").and_then(|r| r.get("value")).unwrap().is_null());
+
+        let owned = call(&ledger, "getProgramAccounts", Json::from(vec![Json::from(Pubkey::new([0; 32]).to_string())]));
+        assert_eq!(owned.get("result").and_then(Json::as_array).map(<[Json]>::len), Some(2));
+
+        let supply = call(&ledger, "getSupply", Json::from(Vec::new()));
+        let total = supply.get("result").and_then(|r| r.get("value")).and_then(|v| v.get("total"));
+        assert_eq!(total.and_then(Json::as_u64), Some(1001));
+        let config = Json::object([("limit", Json::from(1u32))]);
+        let largest = call(&ledger, "getLargestAccounts", Json::from(vec![config]));
+        let largest = largest.get("result").and_then(|r| r.get("value")).and_then(Json::as_array).unwrap();
+        assert_eq!(largest.len(), 1);
+        assert_eq!(largest[0].get("address").and_then(Json::as_str), Some("Alice"));
+
+        // Ledger errors carry the AppError code
+        let error = call(&ledger, "getBalance", Json::from(vec![Json::from("Nobody")]));
+        let error = error.get("error").unwrap();
+        assert_eq!(error.get("code").map(Json::to_string), Some(LEDGER_ERROR.to_string()));
+        assert_eq!(error.get("data").and_then(|d| d.get("code")).and_then(Json::as_str), Some("ACC_001"));
+    }
+
+    #[test]
+    fn test_send_simulate_and_get_transaction() {
+        let ledger = ledger();
+        let (hash, encoded) = encoded_transfer(&ledger, 100);
+
+        let simulated = call(&ledger, "simulateTransaction", Json::from(vec![Json::from(encoded.as_str()), base64()]));
+        let value = simulated.get("result").and_then(|r| r.get("value")).unwrap();
+        assert!(value.get("err").unwrap().is_null());
+        assert_eq!(value.get("logs").and_then(Json::as_array).map(<[Json]>::len), Some(1));
+        assert_eq!(lock(&ledger).state().get_account("Bob").unwrap().balance, 1);
+
+        let sent = call(&ledger, "sendTransaction", Json::from(vec![Json::from(encoded.as_str()), base64()]));
+        assert_eq!(sent.get("result").and_then(Json::as_str), Some(hash::to_hex(&hash).as_str()));
+        let params = Json::from(vec![Json::from(hash::to_hex(&hash))]);
+        let pending = call(&ledger, "getTransaction", params.clone());
+        assert_eq!(pending.get("result").and_then(|r| r.get("status")).and_then(Json::as_str), Some("pending"));
+
+        lock(&ledger).produce_block();
+        let processed = call(&ledger, "getTransaction", params);
+        let result = processed.get("result").unwrap();
+        assert_eq!(result.get("status").and_then(Json::as_str), Some("processed"));
+        assert_eq!(result.get("slot").and_then(Json::as_u64), Some(1));
+        assert!(result.get("err").unwrap().is_null());
+
+        // A replay is refused with its error code
+        let replay = call(&ledger, "sendTransaction", Json::from(vec![Json::from(encoded.as_str()), base64()]));
+        let code = replay.get("error").and_then(|e| e.get("data")).and_then(|d| d.get("code"));
+        assert_eq!(code.and_then(Json::as_str), Some("TX_003"));
+
+        // base58 is the default encoding
+        let (_, encoded) = encoded_transfer(&ledger, 5);
+        let bytes = codec::base64_decode(&encoded).unwrap();
+        let sent = call(&ledger, "sendTransaction", Json::from(vec![Json::from(pubkey::base58_encode(&bytes))]));
+        assert!(sent.get("result").is_some());
+    }
+
+    #[test]
+    fn test_account_history() {
+        let ledger = ledger();
+        let (_, encoded) = encoded_transfer(&ledger, 100);
+        call(&ledger, "sendTransaction", Json::from(vec![Json::from(encoded.as_str()), base64()]));
+        lock(&ledger).produce_block();
+
+        let config = Json::object([("fromSlot", Json::from(1u32)), ("limit", Json::from(5u32))]);
+        let history = call(&ledger, "getAccountHistory", Json::from(vec![Json::from("Bob"), config]));
+        let value = history.get("result").and_then(|r| r.get("value")).unwrap();
+        let changes = value.get("changes").and_then(Json::as_array).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].get("delta").and_then(Json::as_u64), Some(100));
+        assert_eq!(changes[0].get("counterparty").and_then(Json::as_str), Some("Alice"));
+        assert_eq!(changes[0].get("instruction").and_then(Json::as_str), Some("Transfer"));
+        assert!(value.get("next").unwrap().is_null());
+
+        let at = |slot: u32| {
+            let response = call(&ledger, "getBalanceAt", Json::from(vec![Json::from("Bob"), Json::from(slot)]));
+            response.get("result").and_then(|r| r.get("value")).and_then(Json::as_u64)
+        };
+        assert_eq!((at(0), at(1)), (Some(1), Some(101)));
+
+        let bad = Json::object([("limit", Json::from("all"))]);
+        let error = call(&ledger, "getAccountHistory", Json::from(vec![Json::from("Bob"), bad]));
+        assert_eq!(error.get("error").and_then(|e| e.get("code")).map(Json::to_string), Some(INVALID_PARAMS.to_string()));
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let ledger = ledger();
+        let code = |response: &str| {
+            let response = Json::parse(response).unwrap();
+            response.get("error").and_then(|e| e.get("code")).map(Json::to_string)
+        };
+        assert_eq!(code(&handle_request(&ledger, "{")), Some(PARSE_ERROR.to_string()));
+        assert_eq!(code(&handle_request(&ledger, "[]")), Some(INVALID_REQUEST.to_string()));
+        assert_eq!(code(&handle_request(&ledger, r#"{"method":"getSlot"}"#)), Some(INVALID_REQUEST.to_string()));
+        let unknown = r#"{"jsonrpc":"2.0","id":1,"method":"getNothing"}"#;
+        assert_eq!(code(&handle_request(&ledger, unknown)), Some(METHOD_NOT_FOUND.to_string()));
+        let bad_params = r#"{"jsonrpc":"2.0","id":1,"method":"getBalance","params":[5]}"#;
+        assert_eq!(code(&handle_request(&ledger, bad_params)), Some(INVALID_PARAMS.to_string()));
+        let bad_tx = r#"{"jsonrpc":"2.0","id":1,"method":"sendTransaction","params":["zz"]}"#;
+        assert_eq!(code(&handle_request(&ledger, bad_tx)), Some(INVALID_PARAMS.to_string()));
+
+        // Batches answer each call in order
+        let batch = r#"[{"jsonrpc":"2.0","id":1,"method":"getSlot"},{"jsonrpc":"2.0","id":2,"method":"nope"}]"#;
+        let responses = Json::parse(&handle_request(&ledger, batch)).unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses[0].get("result").and_then(Json::as_u64), Some(0));
+        assert!(responses[1].get("error").is_some());
+    }
+
+    #[test]
+    fn test_http_over_loopback() {
+        let server = RpcServer::bind("127.0.0.1:0", Arc::new(ledger())).unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"getBalance","params":["Alice"]}"#;
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let body = Json::parse(body).unwrap();
+        assert_eq!(body.get("result").and_then(|r| r.get("value")).and_then(Json::as_u64), Some(1000));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 405"));
+    }
+
+    #[test]
+    fn test_browser_requests_need_an_allowed_origin_and_json() {
+        let server = RpcServer::bind("127.0.0.1:0", Arc::new(ledger()))
+            .unwrap()
+            .with_allowed_origins(vec!["http://localhost:3000".into()]);
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+        let post = |headers: &str| {
+            let body = r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#;
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "POST / HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}", headers, body.len(), body).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        // A cross-origin "simple" POST from any web page is refused
+        let response = post("Origin: http://evil.example\r\nContent-Type: text/plain\r\n");
+        assert!(response.starts_with("HTTP/1.1 403"));
+        let response = post("Origin: http://evil.example\r\nContent-Type: application/json\r\n");
+        assert!(response.starts_with("HTTP/1.1 403"));
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+
+        // Bodies must be declared as JSON, even without an origin
+        assert!(post("Content-Type: text/plain\r\n").starts_with("HTTP/1.1 415"));
+        assert!(post("").starts_with("HTTP/1.1 415"));
+        let response = post("Content-Type: application/json; charset=utf-8\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+
+        // The configured origin gets access, and only to itself
+        let response = post("Origin: http://localhost:3000\r\nContent-Type: application/json\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("Access-Control-Allow-Origin: http://localhost:3000\r\n"));
+    }
+}
@@ -0,0 +1,112 @@
+// ============================================
+// SCHEDULED PAYMENTS
+// ============================================
+// Standing orders that pay a fixed amount every `interval` slots, `count`
+// times. They are executed as the clock advances.
+
+use crate::error::{AppError, Result};
+
+/// A payment that could not be made when it fell due
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentFailure {
+    pub slot: u64,
+    pub error: AppError,
+}
+
+/// Struct representing one standing order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledPayment {
+    pub id: u64,
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+    pub interval: u64,  // Slots between payments
+    pub remaining: u32, // Payments still to attempt
+    pub next_slot: u64, // Slot the next payment falls due
+    pub executed: u32,  // Payments that went through
+    pub failures: Vec<PaymentFailure>,
+}
+
+impl ScheduledPayment {
+    /// Create a standing order whose first payment falls due one interval after `slot`
+    pub fn new(id: u64, from: String, to: String, amount: u64, interval: u64, count: u32, slot: u64) -> Result<Self> {
+        if from == to {
+            return Err(AppError::TransferToSelf(from));
+        }
+        AppError::validate_amount(amount)?;
+        if interval == 0 || count == 0 {
+            return Err(AppError::InvalidSchedule(format!(
+                "interval {} and count {} must both be greater than 0",
+                interval, count
+            )));
+        }
+        let next_slot = slot.checked_add(interval).ok_or_else(|| {
+            AppError::InvalidSchedule(format!("interval {} is too large", interval))
+        })?;
+
+        Ok(Self {
+            id,
+            from,
+            to,
+            amount,
+            interval,
+            remaining: count,
+            next_slot,
+            executed: 0,
+            failures: Vec::new(),
+        })
+    }
+
+    /// Check whether there are payments left to attempt
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Check whether a payment falls due at or before `slot`
+    pub fn is_due(&self, slot: u64) -> bool {
+        self.is_active() && self.next_slot <= slot
+    }
+
+    /// Record the outcome of the payment due at `next_slot` and move on
+    pub fn record(&mut self, outcome: Result<()>) {
+        match outcome {
+            Ok(()) => self.executed += 1,
+            Err(error) => self.failures.push(PaymentFailure {
+                slot: self.next_slot,
+                error,
+            }),
+        }
+        self.remaining -= 1;
+        self.next_slot = self.next_slot.saturating_add(self.interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_schedule() {
+        let payment = ScheduledPayment::new(1, "Alice".into(), "Netflix".into(), 15, 30, 12, 100).unwrap();
+        assert_eq!(payment.next_slot, 130);
+        assert!(!payment.is_due(129));
+        assert!(payment.is_due(130));
+
+        assert!(ScheduledPayment::new(1, "Alice".into(), "Netflix".into(), 15, 0, 12, 100).is_err());
+        assert!(ScheduledPayment::new(1, "Alice".into(), "Netflix".into(), 15, 30, 0, 100).is_err());
+        assert!(ScheduledPayment::new(1, "Alice".into(), "Alice".into(), 15, 30, 1, 100).is_err());
+    }
+
+    #[test]
+    fn test_record_outcomes() {
+        let mut payment = ScheduledPayment::new(1, "Alice".into(), "Netflix".into(), 15, 10, 2, 0).unwrap();
+        payment.record(Ok(()));
+        payment.record(Err(AppError::insufficient_balance(5, 15)));
+
+        assert_eq!(payment.executed, 1);
+        assert_eq!(payment.failures.len(), 1);
+        assert_eq!(payment.failures[0].slot, 20);
+        assert!(!payment.is_active());
+        assert!(!payment.is_due(u64::MAX));
+    }
+}
@@ -0,0 +1,246 @@
+// ============================================
+// STAKING
+// ============================================
+// Stake accounts delegate to a validator. Delegated stake warms up over a few
+// epochs, cools down the same way after deactivation, and earns a share of
+// each epoch's rewards in proportion to its effective (active) stake.
+
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::pubkey::Pubkey;
+
+/// Program that owns every stake account
+pub const STAKE_PROGRAM_ID: Pubkey = Pubkey::new(*b"StakeProgram11111111111111111111");
+/// Number of slots in one epoch
+pub const SLOTS_PER_EPOCH: u64 = 32;
+/// Epochs it takes for stake to fully warm up or cool down
+pub const WARMUP_EPOCHS: u64 = 4;
+
+/// Where a delegation is in its lifecycle at a given epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StakeStatus {
+    Inactive,
+    Activating,
+    Active,
+    Deactivating,
+}
+
+/// Stake delegated to a validator
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delegation {
+    pub validator: String,
+    pub stake: u64,
+    pub activation_epoch: u64,
+    pub deactivation_epoch: Option<u64>,
+}
+
+impl Delegation {
+    /// Share of `stake` after `epochs` of a linear ramp over WARMUP_EPOCHS
+    fn ramp(stake: u64, epochs: u64) -> u64 {
+        let epochs = epochs.min(WARMUP_EPOCHS);
+        (stake as u128 * epochs as u128 / WARMUP_EPOCHS as u128) as u64
+    }
+
+    /// Stake that counts as active during `epoch`
+    pub fn effective_stake(&self, epoch: u64) -> u64 {
+        if epoch < self.activation_epoch {
+            return 0;
+        }
+        match self.deactivation_epoch {
+            Some(deactivation) if epoch >= deactivation => {
+                let at_deactivation = Delegation::ramp(self.stake, deactivation - self.activation_epoch);
+                let remaining = WARMUP_EPOCHS - (epoch - deactivation).min(WARMUP_EPOCHS);
+                (at_deactivation as u128 * remaining as u128 / WARMUP_EPOCHS as u128) as u64
+            }
+            _ => Delegation::ramp(self.stake, epoch - self.activation_epoch),
+        }
+    }
+
+    /// First epoch from which the effective stake no longer changes
+    pub fn settled_from(&self) -> u64 {
+        let ramp_start = self.deactivation_epoch.unwrap_or(self.activation_epoch);
+        ramp_start.saturating_add(WARMUP_EPOCHS)
+    }
+
+    /// Lifecycle status during `epoch`
+    pub fn status(&self, epoch: u64) -> StakeStatus {
+        let effective = self.effective_stake(epoch);
+        match self.deactivation_epoch {
+            Some(deactivation) if epoch >= deactivation => {
+                if effective == 0 {
+                    StakeStatus::Inactive
+                } else {
+                    StakeStatus::Deactivating
+                }
+            }
+            _ if effective == self.stake => StakeStatus::Active,
+            _ => StakeStatus::Activating,
+        }
+    }
+}
+
+/// Struct representing a stake account's metadata; its lamports live in the
+/// program owned account at `address`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StakeAccount {
+    pub name: String,
+    pub staker: String, // Only the staker may delegate, deactivate or withdraw
+    pub address: Pubkey,
+    pub delegation: Option<Delegation>,
+    pub rewards_earned: u64,
+}
+
+impl StakeAccount {
+    /// Create an undelegated stake account, deriving its address
+    pub fn new(name: String, staker: String) -> Result<Self> {
+        AppError::validate_account_name(&name)?;
+        let (address, _) = Pubkey::find_program_address(&StakeAccount::seeds(&hash::sha256(name.as_bytes())), &STAKE_PROGRAM_ID)?;
+        Ok(Self {
+            name,
+            staker,
+            address,
+            delegation: None,
+            rewards_earned: 0,
+        })
+    }
+
+    /// Seeds of the stake account PDA, from the hash of its name so that
+    /// every valid name fits in one seed
+    pub fn seeds(name_hash: &Hash) -> [&[u8]; 2] {
+        [b"stake", name_hash]
+    }
+
+    /// Check that `signer` is the staker
+    pub fn check_staker(&self, signer: &str) -> Result<()> {
+        if signer != self.staker {
+            return Err(AppError::Unauthorized(signer.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Lifecycle status during `epoch`
+    pub fn status(&self, epoch: u64) -> StakeStatus {
+        self.delegation
+            .as_ref()
+            .map_or(StakeStatus::Inactive, |delegation| delegation.status(epoch))
+    }
+
+    /// Stake that counts for rewards during `epoch`
+    pub fn effective_stake(&self, epoch: u64) -> u64 {
+        self.delegation
+            .as_ref()
+            .map_or(0, |delegation| delegation.effective_stake(epoch))
+    }
+
+    /// First epoch from which the effective stake no longer changes
+    pub fn settled_from(&self) -> u64 {
+        self.delegation.as_ref().map_or(0, Delegation::settled_from)
+    }
+
+    /// Lamports that cannot be withdrawn during `epoch`
+    pub fn locked(&self, epoch: u64) -> u64 {
+        match &self.delegation {
+            Some(delegation) if self.status(epoch) != StakeStatus::Inactive => delegation.stake,
+            _ => 0,
+        }
+    }
+
+    /// Delegate `stake` to `validator` starting at `epoch`
+    pub fn delegate(&mut self, validator: String, stake: u64, epoch: u64) -> Result<()> {
+        if self.status(epoch) != StakeStatus::Inactive {
+            return Err(AppError::InvalidStakeState(format!(
+                "{} is already delegated",
+                self.name
+            )));
+        }
+        AppError::validate_account_name(&validator)?;
+        AppError::validate_amount(stake)?;
+
+        self.delegation = Some(Delegation {
+            validator,
+            stake,
+            activation_epoch: epoch,
+            deactivation_epoch: None,
+        });
+        Ok(())
+    }
+
+    /// Start cooling down at `epoch`
+    pub fn deactivate(&mut self, epoch: u64) -> Result<()> {
+        match &mut self.delegation {
+            Some(delegation) if delegation.deactivation_epoch.is_none() => {
+                delegation.deactivation_epoch = Some(epoch);
+                Ok(())
+            }
+            _ => Err(AppError::InvalidStakeState(format!(
+                "{} has no active delegation",
+                self.name
+            ))),
+        }
+    }
+}
+
+/// Split `reward` among `(key, effective stake)` pairs in proportion to stake.
+/// Rounding dust is not paid out.
+pub fn split_rewards<K: Clone>(reward: u64, stakes: &[(K, u64)]) -> Vec<(K, u64)> {
+    let total: u128 = stakes.iter().map(|(_, stake)| *stake as u128).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    stakes
+        .iter()
+        .filter(|(_, stake)| *stake > 0)
+        .map(|(key, stake)| (key.clone(), (reward as u128 * *stake as u128 / total) as u64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_and_cooldown() {
+        let mut stake = StakeAccount::new("Stake1".into(), "Alice".into()).unwrap();
+        stake.delegate("Validator".into(), 1000, 10).unwrap();
+
+        assert_eq!(stake.effective_stake(9), 0);
+        assert_eq!(stake.effective_stake(10), 0);
+        assert_eq!(stake.status(10), StakeStatus::Activating);
+        assert_eq!(stake.effective_stake(11), 250);
+        assert_eq!(stake.effective_stake(14), 1000);
+        assert_eq!(stake.status(14), StakeStatus::Active);
+        assert_eq!(stake.settled_from(), 14);
+        assert!(stake.delegate("Other".into(), 10, 14).is_err());
+
+        stake.deactivate(20).unwrap();
+        assert_eq!(stake.status(20), StakeStatus::Deactivating);
+        assert_eq!(stake.effective_stake(21), 750);
+        assert_eq!(stake.locked(23), 1000);
+        assert_eq!(stake.status(24), StakeStatus::Inactive);
+        assert_eq!(stake.settled_from(), 24);
+        assert_eq!(stake.locked(24), 0);
+        assert!(stake.deactivate(25).is_err());
+    }
+
+    #[test]
+    fn test_deactivate_while_warming_up() {
+        let mut stake = StakeAccount::new("Stake1".into(), "Alice".into()).unwrap();
+        stake.delegate("Validator".into(), 1000, 0).unwrap();
+        stake.deactivate(2).unwrap();
+        // Cools down from the 500 that had warmed up
+        assert_eq!(stake.effective_stake(2), 500);
+        assert_eq!(stake.effective_stake(4), 250);
+        assert_eq!(stake.status(6), StakeStatus::Inactive);
+    }
+
+    #[test]
+    fn test_split_rewards() {
+        let shares = split_rewards(1000, &[("a", 300), ("b", 100), ("c", 0)]);
+        assert_eq!(shares, vec![("a", 750), ("b", 250)]);
+        assert!(split_rewards(1000, &[("a", 0)]).is_empty());
+
+        // Dust from rounding is never over-paid
+        let shares = split_rewards(10, &[("a", 1), ("b", 1), ("c", 1)]);
+        assert_eq!(shares.iter().map(|(_, r)| r).sum::<u64>(), 9);
+    }
+}
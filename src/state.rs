@@ -1,6 +1,35 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeBounds;
 use std::time::{SystemTime, UNIX_EPOCH};
+use crate::codec::Encoder;
 use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::amm;
+use crate::history::{self, AccountHistory};
+use crate::index::AccountIndex;
+use crate::journal::JournaledMap;
+use crate::merkle::SparseMerkleTree;
+use crate::names;
+use crate::pubkey::{Pubkey, SYSTEM_PROGRAM_ID};
+use crate::stake::{self, SLOTS_PER_EPOCH};
+use crate::loader;
+use crate::vm;
+
+// Records of the built-in programs, as `AppState` hands them out, and the
+// program IDs that own their accounts
+pub use crate::amm::{Deposit, Pool, AMM_PROGRAM_ID};
+pub use crate::escrow::{Escrow, EscrowStatus, ESCROW_PROGRAM_ID};
+pub use crate::history::{BalanceChange, HistoryPage};
+pub use crate::loader::{ProgramData, LOADER_PROGRAM_ID};
+pub use crate::merkle::MerkleProof;
+pub use crate::multisig::Multisig;
+pub use crate::names::NameRecord;
+pub use crate::nonce::{NonceState, NONCE_PROGRAM_ID};
+pub use crate::schedule::{PaymentFailure, ScheduledPayment};
+pub use crate::stake::{Delegation, StakeAccount, StakeStatus, STAKE_PROGRAM_ID};
+pub use crate::token::Mint;
+pub use crate::vesting::{VestingSchedule, VESTING_PROGRAM_ID};
 
 
 /// Struct representing an account
@@ -11,6 +40,15 @@ pub struct Account {
     pub balance: u64,
     pub created_at: u64,  // Timestamp (seconds)
     pub transaction_count: u32,
+    pub delegate: Option<String>, // Account allowed to spend on the owner's behalf
+    pub delegated_amount: u64,    // Remaining allowance of the delegate
+    pub frozen: bool,             // Frozen accounts cannot send or receive funds
+    pub authority: Option<String>, // Multisig that must sign for outgoing funds
+    pub address: Option<Pubkey>,   // Set for program derived accounts
+    pub owner: Pubkey,             // Program that owns the account
+    pub tokens: BTreeMap<String, u64>, // Token balances by mint symbol
+    pub data: Vec<u8>,                 // Written only by the owning program
+    pub executable: bool,              // Holds deployed program bytecode
 }
 
 impl Account {
@@ -27,15 +65,53 @@ impl Account {
             balance,
             created_at: timestamp,
             transaction_count: 0,
+            delegate: None,
+            delegated_amount: 0,
+            frozen: false,
+            authority: None,
+            address: None,
+            owner: SYSTEM_PROGRAM_ID,
+            tokens: BTreeMap::new(),
+            data: Vec::new(),
+            executable: false,
+        }
+    }
+
+    /// Create a program derived account; its name is the base58 address
+    pub fn new_program_account(address: Pubkey, owner: Pubkey, balance: u64) -> Self {
+        let mut account = Account::new(address.to_string(), balance);
+        account.address = Some(address);
+        account.owner = owner;
+        account
+    }
+
+    /// Canonical encoding of everything consensus cares about. `created_at`
+    /// is wall-clock time and would differ between nodes, so it is left out.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new()
+            .put(&self.name)
+            .put(&self.balance)
+            .put(&self.transaction_count)
+            .put(&self.delegate)
+            .put(&self.delegated_amount)
+            .put(&self.frozen)
+            .put(&self.authority)
+            .put(&self.address)
+            .put(&self.owner)
+            .put(&(self.tokens.len() as u32));
+        for (mint, amount) in &self.tokens {
+            encoder = encoder.put(mint).put(amount);
         }
+        encoder.put(&self.data).put(&self.executable).finish()
     }
 
     /// Add to balance
     pub fn add_balance(&mut self, amount: u64) -> Result<()> {
         // TODO 3.3: Add amount to balance with overflow check
+        self.ensure_not_frozen()?;
         self.balance = self.balance
             .checked_add(amount)
-            .ok_or_else(|| AppError::InvalidAmount(amount))?;
+            .ok_or(AppError::InvalidAmount(amount))?;
         
         self.transaction_count += 1;
         Ok(())
@@ -44,6 +120,7 @@ impl Account {
     /// Subtract from balance
     pub fn subtract_balance(&mut self, amount: u64) -> Result<()> {
         // TODO 3.4: Subtract amount from balance
+        self.ensure_not_frozen()?;
         if self.balance < amount {
             return Err(AppError::InsufficientBalance {
                 available: self.balance,
@@ -56,12 +133,120 @@ impl Account {
         Ok(())
     }
 
+    /// Fail with `AccountFrozen` if the account is frozen
+    pub fn ensure_not_frozen(&self) -> Result<()> {
+        if self.frozen {
+            return Err(AppError::AccountFrozen(self.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Fail with `Unauthorized` if the account is controlled by a multisig
+    pub fn ensure_no_authority(&self) -> Result<()> {
+        if self.authority.is_some() {
+            return Err(AppError::Unauthorized(self.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Fail with `Unauthorized` if the account is owned by a program, since
+    /// only that program may move its funds
+    pub fn ensure_system_owned(&self) -> Result<()> {
+        if self.owner != SYSTEM_PROGRAM_ID {
+            return Err(AppError::Unauthorized(self.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Allow `delegate` to spend up to `amount`, replacing any previous approval
+    pub fn approve(&mut self, delegate: String, amount: u64) -> Result<()> {
+        if delegate == self.name {
+            return Err(AppError::InvalidDelegate(delegate));
+        }
+        AppError::validate_amount(amount)?;
+
+        self.delegate = Some(delegate);
+        self.delegated_amount = amount;
+        Ok(())
+    }
+
+    /// Remove the current delegate and its allowance
+    pub fn revoke(&mut self) -> Result<()> {
+        if self.delegate.is_none() {
+            return Err(AppError::InvalidDelegate(self.name.clone()));
+        }
+
+        self.delegate = None;
+        self.delegated_amount = 0;
+        Ok(())
+    }
+
+    /// Check that `delegate` may spend `amount` from this account
+    pub fn check_allowance(&self, delegate: &str, amount: u64) -> Result<()> {
+        if self.delegate.as_deref() != Some(delegate) {
+            return Err(AppError::InvalidDelegate(delegate.to_string()));
+        }
+        if self.delegated_amount < amount {
+            return Err(AppError::InsufficientAllowance {
+                available: self.delegated_amount,
+                required: amount,
+            });
+        }
+        Ok(())
+    }
+
+    /// Consume `amount` of the allowance, clearing the delegate once it is used up
+    pub fn spend_allowance(&mut self, delegate: &str, amount: u64) -> Result<()> {
+        self.check_allowance(delegate, amount)?;
+
+        self.delegated_amount -= amount;
+        if self.delegated_amount == 0 {
+            self.delegate = None;
+        }
+        Ok(())
+    }
+
     /// Get current balance
     pub fn get_balance(&self) -> u64 {
         // TODO 3.5: Return balance
         self.balance
     }
 
+    /// Get the balance of token `symbol`
+    pub fn token_balance(&self, symbol: &str) -> u64 {
+        self.tokens.get(symbol).copied().unwrap_or(0)
+    }
+
+    /// Add tokens with overflow check
+    pub fn add_tokens(&mut self, symbol: &str, amount: u64) -> Result<()> {
+        self.ensure_not_frozen()?;
+        let balance = self
+            .token_balance(symbol)
+            .checked_add(amount)
+            .ok_or(AppError::InvalidAmount(amount))?;
+
+        self.tokens.insert(symbol.to_string(), balance);
+        self.transaction_count += 1;
+        Ok(())
+    }
+
+    /// Subtract tokens, dropping the entry once it reaches zero
+    pub fn subtract_tokens(&mut self, symbol: &str, amount: u64) -> Result<()> {
+        self.ensure_not_frozen()?;
+        let available = self.token_balance(symbol);
+        if available < amount {
+            return Err(AppError::insufficient_balance(available, amount));
+        }
+
+        if available == amount {
+            self.tokens.remove(symbol);
+        } else {
+            self.tokens.insert(symbol.to_string(), available - amount);
+        }
+        self.transaction_count += 1;
+        Ok(())
+    }
+
     /// Check if account has sufficient balance
     pub fn has_sufficient_balance(&self, amount: u64) -> bool {
         // TODO 3.6: Check balance >= amount
@@ -78,12 +263,82 @@ impl Account {
     }
 }
 
+/// Proof that an account has (or, with `account: None`, lacks) a given state
+/// under a state root
+#[derive(Debug, Clone)]
+pub struct AccountProof {
+    pub name: String,
+    pub account: Option<Account>,
+    pub proof: MerkleProof,
+}
+
+impl AccountProof {
+    /// Check the proof against a trusted state root, without the ledger
+    pub fn verify(&self, root: &Hash) -> bool {
+        if self.account.as_ref().is_some_and(|account| account.name != self.name) {
+            return false;
+        }
+        let value = self.account.as_ref().map(|account| hash::sha256(&account.encode()));
+        self.proof.verify(root, &hash::sha256(self.name.as_bytes()), value.as_ref())
+    }
+}
+
+/// Merkle tree over all accounts, brought up to date lazily: writes only
+/// mark the account dirty and the next root or proof rehashes those paths
+#[derive(Debug, Clone, Default)]
+struct StateCommitment {
+    tree: SparseMerkleTree,
+    dirty: BTreeSet<String>,
+}
+
+/// Fields of the state outside its maps, as they were when a checkpoint was
+/// taken; the maps journal their own writes
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    total_transactions: u64,
+    freeze_authority: Option<String>,
+    next_schedule_id: u64,
+    slot: u64,
+    epoch_reward: u64,
+}
+
+/// Call `$method` on every journaled map of the state but the accounts
+macro_rules! each_record_map {
+    ($state:expr, $method:ident) => {{
+        let state = $state;
+        state.multisigs.$method();
+        state.escrows.$method();
+        state.vestings.$method();
+        state.stakes.$method();
+        state.schedules.$method();
+        state.mints.$method();
+        state.pools.$method();
+        state.names.$method();
+    }};
+}
+
 /// Struct managing the entire application state
 #[derive(Debug, Clone)]
 pub struct AppState {
     // TODO 3.8: Define fields for AppState
-    accounts: HashMap<String, Account>,
+    accounts: JournaledMap<String, Account>,
     total_transactions: u64,
+    freeze_authority: Option<String>,
+    multisigs: JournaledMap<String, Multisig>,
+    escrows: JournaledMap<String, Escrow>,
+    vestings: JournaledMap<String, VestingSchedule>,
+    stakes: JournaledMap<String, StakeAccount>,
+    schedules: JournaledMap<u64, ScheduledPayment>,
+    next_schedule_id: u64,
+    mints: JournaledMap<String, Mint>,
+    pools: JournaledMap<String, Pool>,
+    names: JournaledMap<String, NameRecord>,
+    slot: u64,
+    epoch_reward: u64,
+    commitment: RefCell<StateCommitment>,
+    history: RefCell<AccountHistory>,
+    index: RefCell<AccountIndex>,
+    checkpoints: Vec<Checkpoint>, // Open checkpoints, innermost last
 }
 
 impl AppState {
@@ -91,8 +346,142 @@ impl AppState {
     pub fn new() -> Self {
         // TODO 3.9: Implement constructor
         Self {
-            accounts: HashMap::new(),
+            accounts: JournaledMap::default(),
             total_transactions: 0,
+            freeze_authority: None,
+            multisigs: JournaledMap::default(),
+            escrows: JournaledMap::default(),
+            vestings: JournaledMap::default(),
+            stakes: JournaledMap::default(),
+            schedules: JournaledMap::default(),
+            next_schedule_id: 1,
+            mints: JournaledMap::default(),
+            pools: JournaledMap::default(),
+            names: JournaledMap::default(),
+            slot: 0,
+            epoch_reward: 0,
+            commitment: RefCell::new(StateCommitment::default()),
+            history: RefCell::new(AccountHistory::default()),
+            index: RefCell::new(AccountIndex::default()),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Create AppState with a freeze authority configured
+    pub fn with_freeze_authority(authority: String) -> Self {
+        let mut state = Self::new();
+        state.set_freeze_authority(Some(authority));
+        state
+    }
+
+    /// Set or clear the authority allowed to freeze and thaw accounts
+    pub fn set_freeze_authority(&mut self, authority: Option<String>) {
+        self.freeze_authority = authority;
+    }
+
+    /// Get the configured freeze authority
+    pub fn freeze_authority(&self) -> Option<&str> {
+        self.freeze_authority.as_deref()
+    }
+
+    /// Get the current slot
+    pub fn current_slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// Get the current epoch
+    pub fn current_epoch(&self) -> u64 {
+        self.slot / SLOTS_PER_EPOCH
+    }
+
+    /// Set the reward minted and shared among stakers at the end of each epoch
+    pub fn set_epoch_reward(&mut self, reward: u64) {
+        self.epoch_reward = reward;
+    }
+
+    /// Move the clock forward by `slots`, closing every epoch that ends and
+    /// running every scheduled payment that falls due on the way
+    pub fn advance_slots(&mut self, slots: u64) {
+        self.record_history(history::DIRECT);
+        let target = self.slot.saturating_add(slots);
+        // Jump from one event to the next: an epoch boundary or a due payment
+        while self.slot < target {
+            let next_due = self
+                .schedules
+                .values()
+                .filter(|payment| payment.is_active())
+                .map(|payment| payment.next_slot)
+                .min()
+                .unwrap_or(u64::MAX);
+            let stop = target.min(next_due);
+            let ended = self.current_epoch();
+            let next_epoch_start = (ended + 1).saturating_mul(SLOTS_PER_EPOCH);
+            if next_epoch_start > stop {
+                self.slot = stop;
+                self.run_due_payments();
+                continue;
+            }
+
+            // Once no effective stake can change, every epoch until the stop
+            // pays the same rewards, so they are paid in one go
+            let settled = self.stakes.values().map(StakeAccount::settled_from).max().unwrap_or(0);
+            let epochs = if ended >= settled {
+                (stop / SLOTS_PER_EPOCH - ended).max(1)
+            } else {
+                1
+            };
+            self.slot = (ended + epochs).saturating_mul(SLOTS_PER_EPOCH);
+            self.distribute_epoch_rewards(ended, epochs);
+            self.record_history(history::EPOCH_REWARD);
+            self.run_due_payments();
+        }
+    }
+
+    /// Advance the clock by a single slot
+    pub fn tick(&mut self) {
+        self.advance_slots(1);
+    }
+
+    /// Move the clock to the first slot of the next epoch
+    pub fn advance_epoch(&mut self) {
+        let next_epoch_start = (self.current_epoch() + 1).saturating_mul(SLOTS_PER_EPOCH);
+        self.advance_slots(next_epoch_start - self.slot);
+    }
+
+    /// Mint the epoch reward into stake accounts in proportion to the stake
+    /// that was effective during `epoch`, for `epochs` epochs with the same
+    /// effective stakes
+    fn distribute_epoch_rewards(&mut self, epoch: u64, epochs: u64) {
+        if self.epoch_reward == 0 {
+            return;
+        }
+
+        let mut stakes: Vec<(String, u64)> = self
+            .stakes
+            .values()
+            .map(|stake| (stake.name.clone(), stake.effective_stake(epoch)))
+            .collect();
+        stakes.sort();
+
+        for (name, reward) in stake::split_rewards(self.epoch_reward, &stakes) {
+            if reward == 0 {
+                continue;
+            }
+            let Some(address) = self.stakes.get(&name).map(|stake| stake.address.to_string()) else {
+                continue;
+            };
+            // A frozen stake account simply misses these epochs' rewards, and
+            // one that would overflow gets the epochs that still fit
+            self.touch(&address);
+            if let Some(account) = self.accounts.get_mut(&address) {
+                let paid = reward * epochs.min((u64::MAX - account.balance) / reward);
+                if paid > 0
+                    && account.add_balance(paid).is_ok()
+                    && let Some(stake) = self.stakes.get_mut(&name)
+                {
+                    stake.rewards_earned = stake.rewards_earned.saturating_add(paid);
+                }
+            }
         }
     }
 
@@ -107,12 +496,97 @@ impl AppState {
         }
 
         let account = Account::new(name.clone(), balance);
-        self.accounts.insert(name, account);
+        self.put_account(account);
         self.total_transactions += 1;
         
         Ok(())
     }
 
+    /// Add a program derived account. The caller must already have checked
+    /// that `address` derives from `owner` (see `Pubkey::verify_program_address`).
+    pub fn create_program_account(&mut self, address: Pubkey, owner: Pubkey, balance: u64) -> Result<()> {
+        let name = address.to_string();
+        if self.accounts.contains_key(&name) {
+            return Err(AppError::AccountAlreadyExists(name));
+        }
+
+        let account = Account::new_program_account(address, owner, balance);
+        self.put_account(account);
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Deploy verified `bytecode` as an upgradeable program with `owner` as
+    /// its upgrade authority. The program ID is derived from the deployer and
+    /// the program name.
+    pub fn deploy_program(&mut self, owner: &str, name: &str, bytecode: Vec<u8>) -> Result<Pubkey> {
+        self.get_account(owner)?;
+        AppError::validate_account_name(name)?;
+        vm::verify(&bytecode)?;
+
+        let program_id = loader::program_id(owner, name)?;
+        let data_address = ProgramData::address(&program_id)?;
+        if self.accounts.contains_key(&data_address.to_string()) {
+            return Err(AppError::AccountAlreadyExists(data_address.to_string()));
+        }
+        self.create_program_account(program_id, LOADER_PROGRAM_ID, 0)?;
+        self.create_program_account(data_address, LOADER_PROGRAM_ID, 0)?;
+
+        let program = self.get_account_mut(&program_id.to_string())?;
+        program.data = data_address.to_bytes().to_vec();
+        program.executable = true;
+        let program_data = ProgramData::new(owner.to_string(), self.slot, bytecode);
+        self.get_account_mut(&data_address.to_string())?.data = program_data.pack();
+        Ok(program_id)
+    }
+
+    /// Program data (authority, version and bytecode) of the program deployed at `id`
+    pub fn program_data(&self, id: &Pubkey) -> Result<ProgramData> {
+        let data_address = match self.get_account_by_address(id) {
+            Ok(account) if account.executable && account.owner == LOADER_PROGRAM_ID => {
+                loader::program_data_address(&account.data)?
+            }
+            _ => return Err(AppError::ProgramNotFound(id.to_string())),
+        };
+        ProgramData::unpack(&self.get_account_by_address(&data_address)?.data)
+    }
+
+    /// Replace the bytecode of an upgradeable program, keeping its ID
+    pub fn upgrade_program(&mut self, id: &Pubkey, authority: &str, bytecode: Vec<u8>) -> Result<u32> {
+        let mut program_data = self.program_data(id)?;
+        program_data.check_authority(id, authority)?;
+        vm::verify(&bytecode)?;
+
+        program_data.upgrade(bytecode, self.slot);
+        self.write_program_data(id, &program_data)?;
+        Ok(program_data.version)
+    }
+
+    /// Hand the upgrade authority to `new_authority`, or drop it to make the
+    /// program immutable for good
+    pub fn set_upgrade_authority(&mut self, id: &Pubkey, authority: &str, new_authority: Option<String>) -> Result<()> {
+        let mut program_data = self.program_data(id)?;
+        program_data.check_authority(id, authority)?;
+        if let Some(new_authority) = &new_authority {
+            self.get_account(new_authority)?;
+        }
+
+        program_data.upgrade_authority = new_authority;
+        self.write_program_data(id, &program_data)
+    }
+
+    fn write_program_data(&mut self, id: &Pubkey, program_data: &ProgramData) -> Result<()> {
+        let data_address = ProgramData::address(id)?;
+        self.get_account_mut(&data_address.to_string())?.data = program_data.pack();
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Get an account by its address rather than its name
+    pub fn get_account_by_address(&self, address: &Pubkey) -> Result<&Account> {
+        self.get_account(&address.to_string())
+    }
+
     /// Get reference to an account (immutable)
     pub fn get_account(&self, name: &str) -> Result<&Account> {
         // TODO 3.11: Implement get_account
@@ -124,22 +598,163 @@ impl AppState {
     /// Get mutable reference to an account
     pub fn get_account_mut(&mut self, name: &str) -> Result<&mut Account> {
         // TODO 3.12: Implement get_account_mut
-        self.accounts
-            .get_mut(name)
-            .ok_or_else(|| AppError::AccountNotFound(name.to_string()))
+        self.get_account(name)?;
+        self.touch(name);
+        Ok(self.accounts.get_mut(name).expect("account exists"))
+    }
+
+    /// Insert or replace an account, keyed by its name
+    fn put_account(&mut self, account: Account) {
+        self.touch(&account.name);
+        self.accounts.insert(account.name.clone(), account);
+    }
+
+    /// Remove an account
+    fn take_account(&mut self, name: &str) -> Option<Account> {
+        self.touch(name);
+        self.accounts.remove(name)
+    }
+
+    /// Note a coming write to account `name`: its Merkle path and index
+    /// entries need refreshing, and its balance before the write is kept for
+    /// the history
+    fn touch(&mut self, name: &str) {
+        self.commitment.get_mut().dirty.insert(name.to_string());
+        self.index.get_mut().mark_stale(name);
+        let balance = self.accounts.get(name).map_or(0, |account| account.balance);
+        self.history.get_mut().touch(name, balance);
+    }
+
+    /// Start keeping what the state holds before each write from now on, so
+    /// `rollback` can restore it. Checkpoints nest; each is closed by
+    /// `commit` or `rollback`.
+    pub(crate) fn checkpoint(&mut self) {
+        self.checkpoints.push(Checkpoint {
+            total_transactions: self.total_transactions,
+            freeze_authority: self.freeze_authority.clone(),
+            next_schedule_id: self.next_schedule_id,
+            slot: self.slot,
+            epoch_reward: self.epoch_reward,
+        });
+        self.accounts.checkpoint();
+        each_record_map!(&mut *self, checkpoint);
+        self.history.get_mut().checkpoint();
+    }
+
+    /// Keep the writes made since the innermost checkpoint
+    pub(crate) fn commit(&mut self) {
+        if self.checkpoints.pop().is_some() {
+            self.accounts.commit();
+            each_record_map!(&mut *self, commit);
+            self.history.get_mut().commit();
+        }
+    }
+
+    /// Undo the writes made since the innermost checkpoint
+    pub(crate) fn rollback(&mut self) {
+        let Some(checkpoint) = self.checkpoints.pop() else {
+            return;
+        };
+        // The Merkle tree or index may have caught up with the undone writes
+        for name in self.accounts.rollback() {
+            self.commitment.get_mut().dirty.insert(name.clone());
+            self.index.get_mut().mark_stale(&name);
+        }
+        each_record_map!(&mut *self, rollback);
+        self.history.get_mut().rollback();
+        self.total_transactions = checkpoint.total_transactions;
+        self.freeze_authority = checkpoint.freeze_authority;
+        self.next_schedule_id = checkpoint.next_schedule_id;
+        self.slot = checkpoint.slot;
+        self.epoch_reward = checkpoint.epoch_reward;
+    }
+
+    /// Add the balance changes of the accounts written since the last record
+    /// to their history, attributed to `instruction`
+    pub(crate) fn record_history(&mut self, instruction: &'static str) {
+        self.history.get_mut().record(&self.accounts, self.slot, instruction);
+    }
+
+    /// Up to `limit` changes to `name`'s balance made in `slots`, oldest
+    /// first, starting at `cursor`: 0 for the first page, then the previous
+    /// page's `next`. Writes made outside any instruction are attributed to
+    /// "Direct" at the slot they are first seen in.
+    pub fn history(&self, name: &str, slots: impl RangeBounds<u64>, cursor: usize, limit: usize) -> HistoryPage {
+        self.flush_history().page(name, slots, cursor, limit)
+    }
+
+    /// `name`'s balance at the end of `slot`, from its history
+    pub fn balance_at(&self, name: &str, slot: u64) -> Result<u64> {
+        match self.flush_history().balance_at(name, slot) {
+            Some(balance) => Ok(balance),
+            // Never changed: it has always held what it holds now
+            None => self.get_account(name).map(Account::get_balance),
+        }
+    }
+
+    fn flush_history(&self) -> std::cell::RefMut<'_, AccountHistory> {
+        let mut history = self.history.borrow_mut();
+        history.record(&self.accounts, self.slot, history::DIRECT);
+        history
     }
 
     /// Delete an account
     pub fn delete_account(&mut self, name: &str) -> Result<Account> {
         // TODO 3.13: Implement delete_account
-        let account = self.accounts
-            .remove(name)
+        let existing = self.get_account(name)?;
+        existing.ensure_not_frozen()?;
+        existing.ensure_no_authority()?;
+        existing.ensure_system_owned()?;
+        let account = self
+            .take_account(name)
             .ok_or_else(|| AppError::AccountNotFound(name.to_string()))?;
         
         self.total_transactions += 1;
         Ok(account)
     }
 
+    /// Every account owned by program `owner`, in name order
+    pub fn program_accounts(&self, owner: &Pubkey) -> Vec<&Account> {
+        let index = self.flush_index();
+        self.indexed(index.owned_by(owner))
+    }
+
+    /// The `limit` accounts with the largest balances, largest first
+    pub fn largest_accounts(&self, limit: usize) -> Vec<&Account> {
+        let index = self.flush_index();
+        self.indexed(index.by_balance(..).rev().take(limit))
+    }
+
+    /// Accounts whose balance lies in `balances`, smallest first
+    pub fn accounts_by_balance(&self, balances: impl RangeBounds<u64>) -> Vec<&Account> {
+        let index = self.flush_index();
+        self.indexed(index.by_balance(balances))
+    }
+
+    /// Accounts created at a time (seconds since the epoch) in `times`,
+    /// oldest first
+    pub fn accounts_created(&self, times: impl RangeBounds<u64>) -> Vec<&Account> {
+        let index = self.flush_index();
+        self.indexed(index.by_creation(times))
+    }
+
+    /// Accounts holding a non-zero balance of token `symbol`, in name order
+    pub fn token_holders(&self, symbol: &str) -> Vec<&Account> {
+        let index = self.flush_index();
+        self.indexed(index.holders(symbol))
+    }
+
+    fn indexed<'a>(&self, names: impl Iterator<Item = &'a String>) -> Vec<&Account> {
+        names.filter_map(|name| self.accounts.get(name)).collect()
+    }
+
+    /// Bring the indexes up to date with the accounts written since the last query
+    fn flush_index(&self) -> std::cell::RefMut<'_, AccountIndex> {
+        let mut index = self.index.borrow_mut();
+        index.refresh(&self.accounts);
+        index
+    }
+
     /// Check if account exists
     pub fn account_exists(&self, name: &str) -> bool {
         // TODO 3.14: Use contains_key
@@ -153,6 +768,16 @@ impl AppState {
             return Err(AppError::InvalidAmount(amount));
         }
         AppError::validate_amount(amount)?;
+        let from_account = self.get_account(from)?;
+        from_account.ensure_no_authority()?;
+        from_account.ensure_system_owned()?;
+        self.move_funds(from, to, amount)
+    }
+
+    /// Move funds without any authority check (callers must check first)
+    fn move_funds(&mut self, from: &str, to: &str, amount: u64) -> Result<()> {
+        // Make sure the destination can receive before touching the source
+        self.get_account(to)?.ensure_not_frozen()?;
 
         // Borrow 1: Subtract from source
         {
@@ -170,82 +795,834 @@ impl AppState {
         Ok(())
     }
 
-    /// Get total balance across all accounts
-    pub fn total_balance(&self) -> u64 {
-        // TODO 3.16: Iterate and sum
-        self.accounts.values().map(|acc| acc.balance).sum()
+    /// Approve `delegate` to spend up to `amount` from `owner`
+    pub fn approve(&mut self, owner: &str, delegate: &str, amount: u64) -> Result<()> {
+        AppError::validate_account_name(delegate)?;
+        let owner_account = self.get_account(owner)?;
+        owner_account.ensure_no_authority()?;
+        owner_account.ensure_system_owned()?;
+        self.get_account_mut(owner)?.approve(delegate.to_string(), amount)?;
+        self.total_transactions += 1;
+        Ok(())
     }
 
-    /// Get number of accounts
-    pub fn account_count(&self) -> usize {
-        // TODO 3.17: Return length
-        self.accounts.len()
+    /// Revoke the delegate of `owner`
+    pub fn revoke(&mut self, owner: &str) -> Result<()> {
+        let owner_account = self.get_account(owner)?;
+        owner_account.ensure_no_authority()?;
+        owner_account.ensure_system_owned()?;
+        self.get_account_mut(owner)?.revoke()?;
+        self.total_transactions += 1;
+        Ok(())
     }
 
-    /// Get list of all account names (sorted)
-    pub fn list_accounts(&self) -> Vec<String> {
-        // TODO 3.18: Collect keys and sort
-        let mut names: Vec<String> = self.accounts.keys().cloned().collect();
-        names.sort();
-        names
-    }
+    /// Transfer from `from` to `to`, spending the allowance given to `delegate`
+    pub fn transfer_from(&mut self, delegate: &str, from: &str, to: &str, amount: u64) -> Result<()> {
+        if from == to {
+            return Err(AppError::TransferToSelf(from.to_string()));
+        }
+        AppError::validate_amount(amount)?;
 
-    /// Display state to console
-    pub fn display(&self) {
-        // TODO 3.19: Print state
-        println!("--- System State ---");
-        println!("Accounts: {}", self.account_count());
-        println!("Total Balance: {}", self.total_balance());
-        println!("Total Transactions: {}", self.total_transactions);
-        println!("Account List: {:?}", self.list_accounts());
-        println!("--------------------");
-    }
-}
+        // Check everything up front so a failure leaves both accounts untouched
+        {
+            let from_account = self.get_account(from)?;
+            // Program vaults only pay out through their program, and
+            // multisig-controlled accounts only through the multisig
+            from_account.ensure_system_owned()?;
+            from_account.ensure_no_authority()?;
+            from_account.check_allowance(delegate, amount)?;
+            if !from_account.has_sufficient_balance(amount) {
+                return Err(AppError::insufficient_balance(from_account.balance, amount));
+            }
+        }
+        self.get_account(to)?.ensure_not_frozen()?;
 
-// TODO 3.20: Implement Default trait
-impl Default for AppState {
-    fn default() -> Self {
-        Self::new()
+        {
+            let from_account = self.get_account_mut(from)?;
+            from_account.subtract_balance(amount)?;
+            from_account.spend_allowance(delegate, amount)?;
+        }
+
+        {
+            let to_account = self.get_account_mut(to)?;
+            to_account.add_balance(amount)?;
+        }
+
+        self.total_transactions += 1;
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Register a new M-of-N multisig
+    pub fn create_multisig(&mut self, name: String, signers: Vec<String>, threshold: u8) -> Result<()> {
+        if self.multisigs.contains_key(&name) {
+            return Err(AppError::AccountAlreadyExists(name));
+        }
 
-    #[test]
-    fn test_create_account() {
-        // TODO 3.21: Test creating account
-        let name = String::from("Alice");
-        let account = Account::new(name.clone(), 1000);
-        assert_eq!(account.name, name);
-        assert_eq!(account.balance, 1000);
-        assert_eq!(account.transaction_count, 0);
+        let multisig = Multisig::new(name.clone(), signers, threshold)?;
+        self.multisigs.insert(name, multisig);
+        self.total_transactions += 1;
+        Ok(())
     }
 
-    #[test]
-    fn test_add_balance() {
-        // TODO 3.22: Test adding balance
-        let mut account = Account::new("Bob".into(), 500);
-        account.add_balance(200).unwrap();
-        assert_eq!(account.get_balance(), 700);
-        assert_eq!(account.transaction_count, 1);
+    /// Get reference to a multisig
+    pub fn get_multisig(&self, name: &str) -> Result<&Multisig> {
+        self.multisigs
+            .get(name)
+            .ok_or_else(|| AppError::MultisigNotFound(name.to_string()))
     }
 
-    #[test]
-    fn test_subtract_balance() {
-        // TODO 3.23: Test subtracting balance
-        let mut account = Account::new("Charlie".into(), 1000);
-        
-        // Success case
-        assert!(account.subtract_balance(400).is_ok());
-        assert_eq!(account.get_balance(), 600);
+    /// Check that the multisig controlling `name` (if any) has enough signatures
+    fn check_account_authority(&self, name: &str, signers: &[String]) -> Result<()> {
+        match &self.get_account(name)?.authority {
+            Some(authority) => self.get_multisig(authority)?.check_signatures(signers),
+            // Without an authority the account answers for itself
+            None if signers.iter().any(|signer| signer == name) => Ok(()),
+            None => Err(AppError::Unauthorized(name.to_string())),
+        }
+    }
 
-        // Failure case (insufficient)
-        assert!(account.subtract_balance(1000).is_err());
+    /// Put `name` under the control of a multisig, or release it with `None`.
+    /// If the account already has an authority, that multisig must sign;
+    /// otherwise the account itself must be among `signers`. Any delegate is
+    /// revoked.
+    pub fn set_authority(&mut self, name: &str, authority: Option<String>, signers: &[String]) -> Result<()> {
+        self.check_account_authority(name, signers)?;
+        if let Some(multisig) = &authority {
+            self.get_multisig(multisig)?;
+        }
+
+        let account = self.get_account_mut(name)?;
+        account.authority = authority;
+        // An allowance granted under the old control does not carry over
+        account.delegate = None;
+        account.delegated_amount = 0;
+        self.total_transactions += 1;
+        Ok(())
     }
 
-    #[test]
+    /// Transfer out of a multisig-controlled account once M of N keys signed
+    pub fn multisig_transfer(&mut self, from: &str, to: &str, amount: u64, signers: &[String]) -> Result<()> {
+        if from == to {
+            return Err(AppError::TransferToSelf(from.to_string()));
+        }
+        AppError::validate_amount(amount)?;
+        if self.get_account(from)?.authority.is_none() {
+            return Err(AppError::Unauthorized(from.to_string()));
+        }
+        self.check_account_authority(from, signers)?;
+        self.move_funds(from, to, amount)
+    }
+
+    /// Open a program owned vault at `address` and fund it from `from`.
+    /// The vault is removed again if the funding transfer fails.
+    fn open_vault(&mut self, address: Pubkey, owner: Pubkey, from: &str, amount: u64) -> Result<()> {
+        let vault = Account::new_program_account(address, owner, 0);
+        if self.accounts.contains_key(&vault.name) {
+            return Err(AppError::AccountAlreadyExists(vault.name));
+        }
+
+        let vault_name = vault.name.clone();
+        self.put_account(vault);
+        if let Err(e) = self.move_funds(from, &vault_name, amount) {
+            self.take_account(&vault_name);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Lock `amount` from `depositor` in a new escrow vault until `expiry_slot`
+    pub fn create_escrow(
+        &mut self,
+        name: String,
+        depositor: &str,
+        recipient: &str,
+        arbiter: &str,
+        amount: u64,
+        expiry_slot: u64,
+    ) -> Result<()> {
+        if self.escrows.contains_key(&name) {
+            return Err(AppError::AccountAlreadyExists(name));
+        }
+        if expiry_slot <= self.slot {
+            return Err(AppError::EscrowExpired { name, expiry_slot });
+        }
+        self.get_account(recipient)?;
+        AppError::validate_account_name(arbiter)?;
+
+        let depositor_account = self.get_account(depositor)?;
+        depositor_account.ensure_no_authority()?;
+        depositor_account.ensure_system_owned()?;
+
+        let escrow = Escrow::new(
+            name.clone(),
+            depositor.to_string(),
+            recipient.to_string(),
+            arbiter.to_string(),
+            amount,
+            expiry_slot,
+        )?;
+        self.open_vault(escrow.vault, ESCROW_PROGRAM_ID, depositor, amount)?;
+        self.escrows.insert(name, escrow);
+        Ok(())
+    }
+
+    /// Pay everything left in vault `name` to `to` and remove it, so lamports
+    /// sent to the vault on top of what its program locked are not lost
+    fn close_vault(&mut self, name: &str, to: &str) -> Result<()> {
+        let balance = self.get_account(name)?.balance;
+        if balance > 0 {
+            self.move_funds(name, to, balance)?;
+        }
+        self.take_account(name);
+        Ok(())
+    }
+
+    /// Get reference to an escrow
+    pub fn get_escrow(&self, name: &str) -> Result<&Escrow> {
+        self.escrows
+            .get(name)
+            .ok_or_else(|| AppError::EscrowNotFound(name.to_string()))
+    }
+
+    /// Release an escrow to its recipient
+    pub fn release_escrow(&mut self, name: &str, signer: &str) -> Result<()> {
+        let escrow = self.get_escrow(name)?;
+        escrow.check_release(signer, self.slot)?;
+        let recipient = escrow.recipient.clone();
+        self.settle_escrow(name, &recipient, EscrowStatus::Released)
+    }
+
+    /// Cancel an escrow, refunding the depositor
+    pub fn cancel_escrow(&mut self, name: &str, signer: &str) -> Result<()> {
+        let escrow = self.get_escrow(name)?;
+        escrow.check_cancel(signer, self.slot)?;
+        let depositor = escrow.depositor.clone();
+        self.settle_escrow(name, &depositor, EscrowStatus::Cancelled)
+    }
+
+    /// Pay out the vault to `to`, close it and record the final status
+    fn settle_escrow(&mut self, name: &str, to: &str, status: EscrowStatus) -> Result<()> {
+        let vault = self.get_escrow(name)?.vault;
+        self.close_vault(&vault.to_string(), to)?;
+        if let Some(escrow) = self.escrows.get_mut(name) {
+            escrow.status = status;
+        }
+        Ok(())
+    }
+
+    /// Fund a vesting schedule for `beneficiary` from `funder`
+    pub fn create_vesting(
+        &mut self,
+        funder: &str,
+        beneficiary: &str,
+        total: u64,
+        start: u64,
+        cliff: u64,
+        duration: u64,
+    ) -> Result<()> {
+        if self.vestings.contains_key(beneficiary) {
+            return Err(AppError::AccountAlreadyExists(beneficiary.to_string()));
+        }
+        self.get_account(beneficiary)?;
+
+        let funder_account = self.get_account(funder)?;
+        funder_account.ensure_no_authority()?;
+        funder_account.ensure_system_owned()?;
+
+        let schedule = VestingSchedule::new(beneficiary.to_string(), total, start, cliff, duration)?;
+        self.open_vault(schedule.vault, VESTING_PROGRAM_ID, funder, total)?;
+        self.vestings.insert(beneficiary.to_string(), schedule);
+        Ok(())
+    }
+
+    /// Get reference to a vesting schedule
+    pub fn get_vesting(&self, beneficiary: &str) -> Result<&VestingSchedule> {
+        self.vestings
+            .get(beneficiary)
+            .ok_or_else(|| AppError::VestingNotFound(beneficiary.to_string()))
+    }
+
+    /// Pay `amount` of the unlocked portion to the beneficiary; returns the
+    /// schedule after the claim. A fully claimed schedule is closed, which
+    /// leaves the beneficiary free to receive a new grant.
+    pub fn claim_vested(&mut self, beneficiary: &str, amount: u64) -> Result<VestingSchedule> {
+        let slot = self.slot;
+        let mut schedule = self.get_vesting(beneficiary)?.clone();
+        schedule.claim(amount, slot)?;
+
+        let vault_name = schedule.vault.to_string();
+        self.move_funds(&vault_name, beneficiary, amount)?;
+        if schedule.is_fully_claimed() {
+            self.close_vault(&vault_name, beneficiary)?;
+            self.vestings.remove(beneficiary);
+        } else {
+            self.vestings.insert(beneficiary.to_string(), schedule.clone());
+        }
+        Ok(schedule)
+    }
+
+    /// Open stake account `name` for `staker`, funded with `amount`
+    pub fn create_stake(&mut self, staker: &str, name: &str, amount: u64) -> Result<()> {
+        if self.stakes.contains_key(name) {
+            return Err(AppError::AccountAlreadyExists(name.to_string()));
+        }
+
+        let staker_account = self.get_account(staker)?;
+        staker_account.ensure_no_authority()?;
+        staker_account.ensure_system_owned()?;
+
+        let stake = StakeAccount::new(name.to_string(), staker.to_string())?;
+        self.open_vault(stake.address, STAKE_PROGRAM_ID, staker, amount)?;
+        self.stakes.insert(name.to_string(), stake);
+        Ok(())
+    }
+
+    /// Get reference to a stake account
+    pub fn get_stake(&self, name: &str) -> Result<&StakeAccount> {
+        self.stakes
+            .get(name)
+            .ok_or_else(|| AppError::StakeNotFound(name.to_string()))
+    }
+
+    fn get_stake_mut(&mut self, name: &str) -> Result<&mut StakeAccount> {
+        self.stakes
+            .get_mut(name)
+            .ok_or_else(|| AppError::StakeNotFound(name.to_string()))
+    }
+
+    /// Delegate the whole stake account balance to `validator`
+    pub fn delegate_stake(&mut self, name: &str, signer: &str, validator: &str) -> Result<()> {
+        let epoch = self.current_epoch();
+        let stake = self.get_stake(name)?;
+        stake.check_staker(signer)?;
+        let balance = self.get_account_by_address(&stake.address)?.balance;
+
+        self.get_stake_mut(name)?.delegate(validator.to_string(), balance, epoch)?;
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Start cooling down a delegated stake account
+    pub fn deactivate_stake(&mut self, name: &str, signer: &str) -> Result<()> {
+        let epoch = self.current_epoch();
+        self.get_stake(name)?.check_staker(signer)?;
+        self.get_stake_mut(name)?.deactivate(epoch)?;
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Withdraw lamports that are not locked by an active or cooling delegation
+    pub fn withdraw_stake(&mut self, name: &str, signer: &str, to: &str, amount: u64) -> Result<()> {
+        AppError::validate_amount(amount)?;
+        let epoch = self.current_epoch();
+        let stake = self.get_stake(name)?;
+        stake.check_staker(signer)?;
+
+        let address = stake.address.to_string();
+        let locked = stake.locked(epoch);
+        let balance = self.get_account(&address)?.balance;
+        let available = balance.saturating_sub(locked);
+        if amount > available {
+            return Err(AppError::insufficient_balance(available, amount));
+        }
+
+        self.move_funds(&address, to, amount)?;
+        if locked == 0 {
+            // A fully cooled-down delegation is finished
+            self.get_stake_mut(name)?.delegation = None;
+        }
+        Ok(())
+    }
+
+    /// Open nonce account `name` controlled by `authority`, funded with
+    /// `amount` from `funder`. Returns the account address.
+    pub fn create_nonce_account(&mut self, funder: &str, name: &str, authority: &str, amount: u64) -> Result<Pubkey> {
+        AppError::validate_amount(amount)?;
+        let funder_account = self.get_account(funder)?;
+        funder_account.ensure_no_authority()?;
+        funder_account.ensure_system_owned()?;
+        self.get_account(authority)?;
+
+        let address = NonceState::address(name)?;
+        self.open_vault(address, NONCE_PROGRAM_ID, funder, amount)?;
+        let nonce = NonceState::new(authority.to_string(), &address, self.slot);
+        self.get_account_mut(&address.to_string())?.data = nonce.pack();
+        Ok(address)
+    }
+
+    /// Current state of nonce account `name`
+    pub fn get_nonce(&self, name: &str) -> Result<NonceState> {
+        let not_found = || AppError::NonceNotFound(name.to_string());
+        let address = NonceState::address(name).map_err(|_| not_found())?;
+        match self.get_account_by_address(&address) {
+            Ok(account) if account.owner == NONCE_PROGRAM_ID => NonceState::unpack(&account.data),
+            _ => Err(not_found()),
+        }
+    }
+
+    /// Replace the stored nonce, invalidating transactions that used the old one.
+    /// Returns the new nonce.
+    pub fn advance_nonce(&mut self, name: &str, authority: &str) -> Result<Hash> {
+        let mut nonce = self.get_nonce(name)?;
+        nonce.check_authority(authority)?;
+
+        nonce.advance();
+        let address = NonceState::address(name)?;
+        self.get_account_mut(&address.to_string())?.data = nonce.pack();
+        self.total_transactions += 1;
+        Ok(nonce.nonce)
+    }
+
+    /// Withdraw from nonce account `name`; withdrawing everything closes it
+    pub fn withdraw_nonce(&mut self, name: &str, authority: &str, to: &str, amount: u64) -> Result<()> {
+        AppError::validate_amount(amount)?;
+        self.get_nonce(name)?.check_authority(authority)?;
+
+        let address = NonceState::address(name)?.to_string();
+        self.move_funds(&address, to, amount)?;
+        if self.get_account(&address)?.balance == 0 {
+            self.take_account(&address);
+        }
+        Ok(())
+    }
+
+    /// Create a standing order paying `amount` from `from` to `to` every
+    /// `interval` slots, `count` times. Returns the schedule ID.
+    pub fn schedule_payment(&mut self, from: &str, to: &str, amount: u64, interval: u64, count: u32) -> Result<u64> {
+        let from_account = self.get_account(from)?;
+        from_account.ensure_no_authority()?;
+        from_account.ensure_system_owned()?;
+        self.get_account(to)?;
+
+        let id = self.next_schedule_id;
+        let payment = ScheduledPayment::new(id, from.to_string(), to.to_string(), amount, interval, count, self.slot)?;
+        self.schedules.insert(id, payment);
+        self.next_schedule_id += 1;
+        self.total_transactions += 1;
+        Ok(id)
+    }
+
+    /// Get reference to a standing order
+    pub fn get_scheduled_payment(&self, id: u64) -> Result<&ScheduledPayment> {
+        self.schedules
+            .get(&id)
+            .ok_or(AppError::ScheduleNotFound(id))
+    }
+
+    /// Run every payment due at the current slot. A failed payment is recorded
+    /// on its schedule and does not stop the others.
+    fn run_due_payments(&mut self) {
+        let slot = self.slot;
+        let mut due: Vec<u64> = self
+            .schedules
+            .values()
+            .filter(|payment| payment.is_due(slot))
+            .map(|payment| payment.id)
+            .collect();
+        due.sort_unstable(); // Oldest standing order first
+
+        for id in due {
+            let Some(payment) = self.schedules.get(&id) else {
+                continue;
+            };
+            let (from, to, amount) = (payment.from.clone(), payment.to.clone(), payment.amount);
+            let outcome = self.transfer(&from, &to, amount);
+            self.record_history(history::SCHEDULED_PAYMENT);
+            if let Some(payment) = self.schedules.get_mut(&id) {
+                payment.record(outcome);
+            }
+        }
+    }
+
+    /// Create a new token mint controlled by `authority`
+    pub fn create_mint(&mut self, symbol: &str, authority: &str) -> Result<()> {
+        // LP symbols belong to the pools that create them
+        if symbol.starts_with(amm::LP_MINT_PREFIX) {
+            return Err(AppError::InvalidAccountName(symbol.to_string()));
+        }
+        if self.mints.contains_key(symbol) {
+            return Err(AppError::AccountAlreadyExists(symbol.to_string()));
+        }
+        AppError::validate_account_name(authority)?;
+
+        let mint = Mint::new(symbol.to_string(), authority.to_string())?;
+        self.mints.insert(symbol.to_string(), mint);
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Get reference to a mint
+    pub fn get_mint(&self, symbol: &str) -> Result<&Mint> {
+        self.mints
+            .get(symbol)
+            .ok_or_else(|| AppError::MintNotFound(symbol.to_string()))
+    }
+
+    fn get_mint_mut(&mut self, symbol: &str) -> Result<&mut Mint> {
+        self.mints
+            .get_mut(symbol)
+            .ok_or_else(|| AppError::MintNotFound(symbol.to_string()))
+    }
+
+    /// Mint new tokens to `to`; only the mint authority may do this, and
+    /// only if it is not a program account
+    pub fn mint_to(&mut self, symbol: &str, authority: &str, to: &str, amount: u64) -> Result<()> {
+        AppError::validate_amount(amount)?;
+        let mint = self.get_mint(symbol)?;
+        if mint.authority != authority {
+            return Err(AppError::Unauthorized(authority.to_string()));
+        }
+        // A mint run by a program account, like a pool's LP mint, only
+        // changes supply through that program
+        if let Some(account) = self.accounts.get(authority) {
+            account.ensure_system_owned()?;
+        }
+
+        let mut updated = mint.clone();
+        updated.increase_supply(amount)?;
+        self.get_account_mut(to)?.add_tokens(symbol, amount)?;
+        self.mints.insert(symbol.to_string(), updated);
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Transfer tokens between two accounts
+    pub fn transfer_tokens(&mut self, symbol: &str, from: &str, to: &str, amount: u64) -> Result<()> {
+        if from == to {
+            return Err(AppError::TransferToSelf(from.to_string()));
+        }
+        AppError::validate_amount(amount)?;
+        self.get_mint(symbol)?;
+
+        let mut from_account = self.get_account(from)?.clone();
+        let mut to_account = self.get_account(to)?.clone();
+        from_account.ensure_no_authority()?;
+        from_account.ensure_system_owned()?;
+        from_account.subtract_tokens(symbol, amount)?;
+        to_account.add_tokens(symbol, amount)?;
+
+        self.put_account(from_account);
+        self.put_account(to_account);
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Create an empty pool trading `mint_a` against `mint_b`
+    pub fn create_pool(&mut self, name: &str, mint_a: &str, mint_b: &str, fee_bps: u16) -> Result<()> {
+        if self.pools.contains_key(name) {
+            return Err(AppError::AccountAlreadyExists(name.to_string()));
+        }
+        self.get_mint(mint_a)?;
+        self.get_mint(mint_b)?;
+
+        let pool = Pool::new(name.to_string(), mint_a.to_string(), mint_b.to_string(), fee_bps)?;
+        let vault = Account::new_program_account(pool.vault, AMM_PROGRAM_ID, 0);
+        if self.accounts.contains_key(&vault.name) || self.mints.contains_key(&pool.lp_mint) {
+            return Err(AppError::AccountAlreadyExists(vault.name));
+        }
+
+        // The pool vault is the only authority of the LP mint
+        let lp_mint = Mint::new(pool.lp_mint.clone(), vault.name.clone())?;
+        self.mints.insert(pool.lp_mint.clone(), lp_mint);
+        self.put_account(vault);
+        self.pools.insert(name.to_string(), pool);
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Get reference to a pool
+    pub fn get_pool(&self, name: &str) -> Result<&Pool> {
+        self.pools
+            .get(name)
+            .ok_or_else(|| AppError::PoolNotFound(name.to_string()))
+    }
+
+    /// Clone a pool and the account trading with it, so an operation can be
+    /// applied to the copies and committed only if every step succeeds
+    fn pool_accounts(&self, name: &str, trader: &str) -> Result<(Pool, Account, Account)> {
+        let pool = self.get_pool(name)?.clone();
+        let trader_account = self.get_account(trader)?.clone();
+        trader_account.ensure_no_authority()?;
+        trader_account.ensure_system_owned()?;
+        let vault = self.get_account_by_address(&pool.vault)?.clone();
+        Ok((pool, trader_account, vault))
+    }
+
+    fn commit_pool(&mut self, pool: Pool, trader: Account, vault: Account) {
+        self.put_account(trader);
+        self.put_account(vault);
+        self.pools.insert(pool.name.clone(), pool);
+        self.total_transactions += 1;
+    }
+
+    /// Deposit up to `max_a` / `max_b` and receive at least `min_shares` LP tokens
+    pub fn add_liquidity(
+        &mut self,
+        name: &str,
+        provider: &str,
+        max_a: u64,
+        max_b: u64,
+        min_shares: u64,
+    ) -> Result<Deposit> {
+        let (mut pool, mut provider_account, mut vault) = self.pool_accounts(name, provider)?;
+        let deposit = pool.quote_deposit(max_a, max_b)?;
+        amm::check_slippage(deposit.shares, min_shares)?;
+
+        provider_account.subtract_tokens(&pool.mint_a, deposit.amount_a)?;
+        provider_account.subtract_tokens(&pool.mint_b, deposit.amount_b)?;
+        vault.add_tokens(&pool.mint_a, deposit.amount_a)?;
+        vault.add_tokens(&pool.mint_b, deposit.amount_b)?;
+        provider_account.add_tokens(&pool.lp_mint, deposit.shares)?;
+        pool.apply_deposit(&deposit)?;
+
+        let lp_mint = pool.lp_mint.clone();
+        self.get_mint_mut(&lp_mint)?.increase_supply(deposit.shares)?;
+        self.commit_pool(pool, provider_account, vault);
+        Ok(deposit)
+    }
+
+    /// Burn `shares` LP tokens for at least `min_a` / `min_b` of the reserves
+    pub fn remove_liquidity(
+        &mut self,
+        name: &str,
+        provider: &str,
+        shares: u64,
+        min_a: u64,
+        min_b: u64,
+    ) -> Result<(u64, u64)> {
+        let (mut pool, mut provider_account, mut vault) = self.pool_accounts(name, provider)?;
+        let (amount_a, amount_b) = pool.quote_withdraw(shares)?;
+        amm::check_slippage(amount_a, min_a)?;
+        amm::check_slippage(amount_b, min_b)?;
+
+        provider_account.subtract_tokens(&pool.lp_mint, shares)?;
+        vault.subtract_tokens(&pool.mint_a, amount_a)?;
+        vault.subtract_tokens(&pool.mint_b, amount_b)?;
+        provider_account.add_tokens(&pool.mint_a, amount_a)?;
+        provider_account.add_tokens(&pool.mint_b, amount_b)?;
+        pool.apply_withdraw(shares, amount_a, amount_b)?;
+
+        let lp_mint = pool.lp_mint.clone();
+        self.get_mint_mut(&lp_mint)?.decrease_supply(shares)?;
+        self.commit_pool(pool, provider_account, vault);
+        Ok((amount_a, amount_b))
+    }
+
+    /// Sell `amount_in` of `mint_in` for at least `min_out` of the other side
+    pub fn swap(&mut self, name: &str, trader: &str, mint_in: &str, amount_in: u64, min_out: u64) -> Result<u64> {
+        let (mut pool, mut trader_account, mut vault) = self.pool_accounts(name, trader)?;
+        let amount_out = pool.quote_swap(mint_in, amount_in)?;
+        amm::check_slippage(amount_out, min_out)?;
+        if amount_out == 0 {
+            return Err(AppError::InvalidAmount(amount_out));
+        }
+
+        let mint_out = if pool.side_of(mint_in)? {
+            pool.mint_b.clone()
+        } else {
+            pool.mint_a.clone()
+        };
+        trader_account.subtract_tokens(mint_in, amount_in)?;
+        vault.add_tokens(mint_in, amount_in)?;
+        vault.subtract_tokens(&mint_out, amount_out)?;
+        trader_account.add_tokens(&mint_out, amount_out)?;
+        pool.apply_swap(mint_in, amount_in, amount_out)?;
+
+        self.commit_pool(pool, trader_account, vault);
+        Ok(amount_out)
+    }
+
+    /// Register `name` for `owner`, pointing at account `target`, for
+    /// `duration` slots. The current owner may re-register to renew the name
+    /// or change its target; anyone may take it over once it has expired.
+    pub fn register_name(&mut self, name: &str, owner: &str, target: &str, duration: u64) -> Result<()> {
+        self.get_account(owner)?;
+        self.get_account(target)?;
+
+        let mut record = NameRecord::new(name.to_string(), owner.to_string(), target.to_string(), self.slot, duration)?;
+        if let Some(existing) = self.names.get(name)
+            && !existing.is_expired(self.slot)
+        {
+            if existing.owner != owner {
+                return Err(AppError::NameTaken(name.to_string()));
+            }
+            // Renewal extends the current registration
+            record.expires_at = existing.expires_at.saturating_add(duration);
+        }
+
+        self.names.insert(name.to_string(), record);
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Get reference to a name registration, expired or not
+    pub fn get_name_record(&self, name: &str) -> Result<&NameRecord> {
+        self.names
+            .get(name)
+            .ok_or_else(|| AppError::NameNotFound(name.to_string()))
+    }
+
+    /// Resolve a registered name to the account it points at
+    pub fn resolve_name(&self, name: &str) -> Result<&str> {
+        let record = self.get_name_record(name)?;
+        if record.is_expired(self.slot) {
+            return Err(AppError::NameExpired(name.to_string()));
+        }
+        Ok(&record.target)
+    }
+
+    /// Turn a registered name or an account key into an account key
+    pub fn resolve_account(&self, key: &str) -> Result<String> {
+        if names::is_registered_name(key) {
+            return self.resolve_name(key).map(String::from);
+        }
+        Ok(key.to_string())
+    }
+
+    /// Hand a name over to `new_owner`
+    pub fn transfer_name(&mut self, name: &str, owner: &str, new_owner: &str) -> Result<()> {
+        self.get_name_record(name)?.check_owner(owner, self.slot)?;
+        self.get_account(new_owner)?;
+
+        if let Some(record) = self.names.get_mut(name) {
+            record.owner = new_owner.to_string();
+        }
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Check that `authority` is the configured freeze authority
+    fn check_freeze_authority(&self, authority: &str) -> Result<()> {
+        if self.freeze_authority.as_deref() != Some(authority) {
+            return Err(AppError::Unauthorized(authority.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Freeze an account (only the freeze authority may do this)
+    pub fn freeze_account(&mut self, authority: &str, name: &str) -> Result<()> {
+        self.check_freeze_authority(authority)?;
+        let account = self.get_account_mut(name)?;
+        account.ensure_not_frozen()?;
+        account.frozen = true;
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Thaw a frozen account (only the freeze authority may do this)
+    pub fn thaw_account(&mut self, authority: &str, name: &str) -> Result<()> {
+        self.check_freeze_authority(authority)?;
+        let account = self.get_account_mut(name)?;
+        if !account.frozen {
+            return Err(AppError::AccountNotFrozen(name.to_string()));
+        }
+        account.frozen = false;
+        self.total_transactions += 1;
+        Ok(())
+    }
+
+    /// Get total balance across all accounts
+    pub fn total_balance(&self) -> u64 {
+        // TODO 3.16: Iterate and sum
+        u64::try_from(self.flush_index().total()).unwrap_or(u64::MAX)
+    }
+
+    /// Get number of accounts
+    pub fn account_count(&self) -> usize {
+        // TODO 3.17: Return length
+        self.accounts.len()
+    }
+
+    /// Get list of all account names (sorted)
+    pub fn list_accounts(&self) -> Vec<String> {
+        // TODO 3.18: Collect keys and sort
+        self.flush_index().names().cloned().collect()
+    }
+
+    /// Root of the Merkle tree over all accounts, keyed by the hash of the
+    /// account name. Protocol records kept outside accounts (escrows, stakes,
+    /// names, ...) are not covered.
+    pub fn state_root(&self) -> Hash {
+        self.flush_commitment().tree.root()
+    }
+
+    /// Proof of `name`'s current account state (or its absence) against `state_root()`
+    pub fn prove_account(&self, name: &str) -> AccountProof {
+        let proof = self.flush_commitment().tree.prove(&hash::sha256(name.as_bytes()));
+        AccountProof {
+            name: name.to_string(),
+            account: self.accounts.get(name).cloned(),
+            proof,
+        }
+    }
+
+    /// Rehash the paths of accounts written since the last root
+    fn flush_commitment(&self) -> std::cell::RefMut<'_, StateCommitment> {
+        let mut commitment = self.commitment.borrow_mut();
+        let dirty = std::mem::take(&mut commitment.dirty);
+        for name in dirty {
+            let value = self.accounts.get(&name).map(|account| hash::sha256(&account.encode()));
+            commitment.tree.update(&hash::sha256(name.as_bytes()), value.as_ref());
+        }
+        commitment
+    }
+
+    /// Display state to console
+    pub fn display(&self) {
+        // TODO 3.19: Print state
+        println!("--- System State ---");
+        println!("Accounts: {}", self.account_count());
+        println!("Total Balance: {}", self.total_balance());
+        println!("Total Transactions: {}", self.total_transactions);
+        println!("Account List: {:?}", self.list_accounts());
+        println!("--------------------");
+    }
+}
+
+// TODO 3.20: Implement Default trait
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_account() {
+        // TODO 3.21: Test creating account
+        let name = String::from("Alice");
+        let account = Account::new(name.clone(), 1000);
+        assert_eq!(account.name, name);
+        assert_eq!(account.balance, 1000);
+        assert_eq!(account.transaction_count, 0);
+    }
+
+    #[test]
+    fn test_add_balance() {
+        // TODO 3.22: Test adding balance
+        let mut account = Account::new("Bob".into(), 500);
+        account.add_balance(200).unwrap();
+        assert_eq!(account.get_balance(), 700);
+        assert_eq!(account.transaction_count, 1);
+    }
+
+    #[test]
+    fn test_subtract_balance() {
+        // TODO 3.23: Test subtracting balance
+        let mut account = Account::new("Charlie".into(), 1000);
+        
+        // Success case
+        assert!(account.subtract_balance(400).is_ok());
+        assert_eq!(account.get_balance(), 600);
+
+        // Failure case (insufficient)
+        assert!(account.subtract_balance(1000).is_err());
+    }
+
+    #[test]
     fn test_app_state_create_account() {
         // TODO 3.24: Test AppState account creation
         let mut state = AppState::new();
@@ -270,12 +1647,634 @@ mod tests {
         assert!(state.transfer("Alice", "Alice", 100).is_err());
     }
 
+    #[test]
+    fn test_checkpoint_rollback_and_commit() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 500).unwrap();
+        let (root, transactions) = (state.state_root(), state.total_transactions);
+
+        state.checkpoint();
+        state.transfer("Alice", "Bob", 100).unwrap();
+        state.checkpoint();
+        state.transfer("Bob", "Alice", 50).unwrap();
+        state.create_account("Carol".into(), 5).unwrap();
+        state.rollback();
+        assert_eq!(state.get_account("Bob").unwrap().balance, 600);
+        assert!(state.get_account("Carol").is_err());
+
+        // Committing the inner checkpoint hands its accounts to the outer one
+        state.checkpoint();
+        state.transfer("Bob", "Alice", 50).unwrap();
+        state.commit();
+        state.rollback();
+        assert_eq!(state.get_account("Alice").unwrap().balance, 1000);
+        assert_eq!(state.get_account("Bob").unwrap().balance, 500);
+        assert_eq!(state.total_transactions, transactions);
+        assert_eq!(state.state_root(), root);
+    }
+
     #[test]
     fn test_total_balance() {
         // TODO 3.26: Test total balance
         let mut state = AppState::new();
-        state.create_account("A".into(), 100).unwrap();
-        state.create_account("B".into(), 200).unwrap();
+        state.create_account("AA".into(), 100).unwrap();
+        state.create_account("BB".into(), 200).unwrap();
         assert_eq!(state.total_balance(), 300);
     }
+
+    #[test]
+    fn test_approve_and_transfer_from() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 500).unwrap();
+        state.create_account("Carol".into(), 100).unwrap();
+
+        state.approve("Alice", "Bob", 300).unwrap();
+        state.transfer_from("Bob", "Alice", "Carol", 200).unwrap();
+        assert_eq!(state.get_account("Alice").unwrap().balance, 800);
+        assert_eq!(state.get_account("Carol").unwrap().balance, 300);
+        assert_eq!(state.get_account("Alice").unwrap().delegated_amount, 100);
+
+        // Allowance exceeded
+        let result = state.transfer_from("Bob", "Alice", "Carol", 150);
+        assert!(matches!(result, Err(AppError::InsufficientAllowance { available: 100, required: 150 })));
+        assert_eq!(state.get_account("Alice").unwrap().balance, 800);
+
+        // Spending the rest clears the delegate
+        state.transfer_from("Bob", "Alice", "Carol", 100).unwrap();
+        assert_eq!(state.get_account("Alice").unwrap().delegate, None);
+    }
+
+    #[test]
+    fn test_revoke() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 500).unwrap();
+
+        state.approve("Alice", "Bob", 300).unwrap();
+        state.revoke("Alice").unwrap();
+        let result = state.transfer_from("Bob", "Alice", "Bob", 100);
+        assert!(matches!(result, Err(AppError::InvalidDelegate(_))));
+
+        // Nothing left to revoke
+        assert!(state.revoke("Alice").is_err());
+        // Cannot delegate to self
+        assert!(state.approve("Alice", "Alice", 10).is_err());
+    }
+
+    #[test]
+    fn test_frozen_account() {
+        let mut account = Account::new("Dave".into(), 100);
+        account.frozen = true;
+        assert!(matches!(account.add_balance(10), Err(AppError::AccountFrozen(_))));
+        assert!(matches!(account.subtract_balance(10), Err(AppError::AccountFrozen(_))));
+        assert_eq!(account.get_balance(), 100);
+    }
+
+    #[test]
+    fn test_freeze_and_thaw() {
+        let mut state = AppState::with_freeze_authority("Compliance".into());
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 500).unwrap();
+
+        // Only the freeze authority may freeze
+        assert!(matches!(state.freeze_account("Bob", "Alice"), Err(AppError::Unauthorized(_))));
+
+        state.freeze_account("Compliance", "Alice").unwrap();
+        assert!(matches!(state.transfer("Alice", "Bob", 100), Err(AppError::AccountFrozen(_))));
+        // A frozen destination leaves the source untouched
+        assert!(matches!(state.transfer("Bob", "Alice", 100), Err(AppError::AccountFrozen(_))));
+        assert_eq!(state.get_account("Bob").unwrap().balance, 500);
+        assert!(state.delete_account("Alice").is_err());
+
+        state.thaw_account("Compliance", "Alice").unwrap();
+        state.transfer("Alice", "Bob", 100).unwrap();
+        assert!(matches!(state.thaw_account("Compliance", "Alice"), Err(AppError::AccountNotFrozen(_))));
+    }
+
+    #[test]
+    fn test_program_account_lookup() {
+        let mut state = AppState::new();
+        let program_id = Pubkey::new([3; 32]);
+        let (address, _) = Pubkey::find_program_address(&[b"vault"], &program_id).unwrap();
+        state.create_program_account(address, program_id, 100).unwrap();
+
+        let account = state.get_account_by_address(&address).unwrap();
+        assert_eq!(account.owner, program_id);
+        assert_eq!(account.address, Some(address));
+        // Name-based access keeps working, but only the owning program may debit
+        state.create_account("Alice".into(), 50).unwrap();
+        state.transfer("Alice", &address.to_string(), 40).unwrap();
+        let result = state.transfer(&address.to_string(), "Alice", 40);
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        assert_eq!(state.get_account("Alice").unwrap().owner, SYSTEM_PROGRAM_ID);
+    }
+
+    #[test]
+    fn test_escrow_release_and_expiry() {
+        let mut state = AppState::new();
+        state.create_account("Buyer".into(), 1000).unwrap();
+        state.create_account("Seller".into(), 10).unwrap();
+
+        state.create_escrow("Order1".into(), "Buyer", "Seller", "Market", 300, 50).unwrap();
+        let vault = state.get_escrow("Order1").unwrap().vault;
+        assert_eq!(state.get_account("Buyer").unwrap().balance, 700);
+        assert_eq!(state.get_account_by_address(&vault).unwrap().balance, 300);
+
+        // Nobody can drain the vault with a plain transfer
+        assert!(state.transfer(&vault.to_string(), "Seller", 300).is_err());
+
+        // Lamports sent to the vault directly go out with the escrow
+        state.transfer("Buyer", &vault.to_string(), 5).unwrap();
+        state.release_escrow("Order1", "Market").unwrap();
+        assert_eq!(state.get_account("Seller").unwrap().balance, 315);
+        assert!(!state.account_exists(&vault.to_string()));
+        assert_eq!(state.get_escrow("Order1").unwrap().status, EscrowStatus::Released);
+        assert!(state.cancel_escrow("Order1", "Market").is_err());
+
+        // A second escrow runs past its expiry and goes back to the buyer
+        state.create_escrow("Order2".into(), "Buyer", "Seller", "Market", 200, 60).unwrap();
+        assert!(state.cancel_escrow("Order2", "Buyer").is_err());
+        state.advance_slots(60);
+        assert!(state.release_escrow("Order2", "Market").is_err());
+        state.cancel_escrow("Order2", "Buyer").unwrap();
+        assert_eq!(state.get_account("Buyer").unwrap().balance, 695);
+        assert_eq!(state.total_balance(), 1010);
+    }
+
+    #[test]
+    fn test_no_delegation_on_program_owned_accounts() {
+        let mut state = AppState::new();
+        state.create_account("Buyer".into(), 1000).unwrap();
+        state.create_account("Seller".into(), 10).unwrap();
+        state.create_escrow("Order1".into(), "Buyer", "Seller", "Market", 300, 50).unwrap();
+        let vault = state.get_escrow("Order1").unwrap().vault.to_string();
+
+        // A vault's lamports only move through its program
+        let approved = state.approve(&vault, "Seller", 300);
+        assert!(matches!(approved, Err(AppError::Unauthorized(_))));
+        assert!(matches!(state.revoke(&vault), Err(AppError::Unauthorized(_))));
+        assert_eq!(state.get_account(&vault).unwrap().delegate, None);
+
+        // Even with an allowance in place, a delegate cannot spend from it
+        state.get_account_mut(&vault).unwrap().approve("Seller".into(), 300).unwrap();
+        let drained = state.transfer_from("Seller", &vault, "Seller", 300);
+        assert!(matches!(drained, Err(AppError::Unauthorized(_))));
+        assert_eq!(state.get_account(&vault).unwrap().balance, 300);
+    }
+
+    #[test]
+    fn test_escrow_insufficient_funds_leaves_no_vault() {
+        let mut state = AppState::new();
+        state.create_account("Buyer".into(), 100).unwrap();
+        state.create_account("Seller".into(), 10).unwrap();
+
+        let result = state.create_escrow("Order1".into(), "Buyer", "Seller", "Market", 300, 50);
+        assert!(matches!(result, Err(AppError::InsufficientBalance { .. })));
+        assert_eq!(state.account_count(), 2);
+        assert!(state.get_escrow("Order1").is_err());
+    }
+
+    #[test]
+    fn test_vesting_claims_follow_clock() {
+        let mut state = AppState::new();
+        state.create_account("Foundation".into(), 10_000).unwrap();
+        state.create_account("Grantee".into(), 1).unwrap();
+
+        state.create_vesting("Foundation", "Grantee", 1200, 10, 30, 120).unwrap();
+        assert_eq!(state.get_account("Foundation").unwrap().balance, 8800);
+
+        state.advance_slots(39);
+        assert!(matches!(state.claim_vested("Grantee", 1), Err(AppError::InsufficientVested { .. })));
+
+        state.advance_slots(1); // slot 40: cliff reached, 30/120 unlocked
+        state.claim_vested("Grantee", 300).unwrap();
+        assert!(state.claim_vested("Grantee", 1).is_err());
+
+        state.advance_slots(1000);
+        let vault = state.claim_vested("Grantee", 900).unwrap().vault;
+        assert_eq!(state.get_account("Grantee").unwrap().balance, 1201);
+        assert!(state.get_account_by_address(&vault).is_err());
+
+        // The finished grant is closed, so a second one can follow
+        assert!(matches!(state.get_vesting("Grantee"), Err(AppError::VestingNotFound(_))));
+        state.create_vesting("Foundation", "Grantee", 100, 1040, 0, 10).unwrap();
+        state.advance_slots(5);
+        assert_eq!(state.claim_vested("Grantee", 50).unwrap().claimed, 50);
+    }
+
+    #[test]
+    fn test_stake_lifecycle_and_rewards() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 10_000).unwrap();
+        state.create_account("Bob".into(), 10_000).unwrap();
+        state.set_epoch_reward(1000);
+
+        state.create_stake("Alice", "AliceStake", 3000).unwrap();
+        state.create_stake("Bob", "BobStake", 1000).unwrap();
+        assert!(state.delegate_stake("AliceStake", "Bob", "Validator").is_err());
+        state.delegate_stake("AliceStake", "Alice", "Validator").unwrap();
+        state.delegate_stake("BobStake", "Bob", "Validator").unwrap();
+
+        // Epoch 0 has no effective stake yet, so nothing is minted
+        state.advance_epoch();
+        assert_eq!(state.get_stake("AliceStake").unwrap().rewards_earned, 0);
+
+        // Epoch 1: 25% warmed up on both sides, rewards split 3:1
+        state.advance_epoch();
+        assert_eq!(state.get_stake("AliceStake").unwrap().rewards_earned, 750);
+        assert_eq!(state.get_stake("BobStake").unwrap().rewards_earned, 250);
+
+        // Rewards can be withdrawn, delegated principal cannot
+        assert!(state.withdraw_stake("AliceStake", "Alice", "Alice", 751).is_err());
+        state.withdraw_stake("AliceStake", "Alice", "Alice", 750).unwrap();
+
+        state.deactivate_stake("AliceStake", "Alice").unwrap();
+        state.advance_slots(stake::WARMUP_EPOCHS * SLOTS_PER_EPOCH);
+        let address = state.get_stake("AliceStake").unwrap().address;
+        let balance = state.get_account_by_address(&address).unwrap().balance;
+        state.withdraw_stake("AliceStake", "Alice", "Alice", balance).unwrap();
+        assert_eq!(state.get_stake("AliceStake").unwrap().delegation, None);
+    }
+
+    #[test]
+    fn test_advance_slots_jumps_between_events() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 10_000).unwrap();
+        state.create_account("Bob".into(), 10_000).unwrap();
+        state.set_epoch_reward(1000);
+        state.create_stake("Alice", "AliceStake", 3000).unwrap();
+        state.create_stake("Bob", "BobStake", 1000).unwrap();
+        state.delegate_stake("AliceStake", "Alice", "Validator").unwrap();
+        state.delegate_stake("BobStake", "Bob", "Validator").unwrap();
+        let subscription = state.schedule_payment("Alice", "Bob", 1, 1000, 3).unwrap();
+
+        // Paying settled epochs in one go matches paying them one at a time
+        let mut stepped = state.clone();
+        for _ in 0..40 {
+            stepped.advance_epoch();
+        }
+        state.advance_slots(40 * SLOTS_PER_EPOCH);
+        for name in state.list_accounts() {
+            let balance = |state: &AppState| state.get_account(&name).unwrap().balance;
+            assert_eq!(balance(&state), balance(&stepped));
+        }
+        assert_eq!(state.get_stake("AliceStake").unwrap().rewards_earned, 750 * 39);
+
+        // A huge advance still stops for every due payment
+        state.advance_slots(1 << 40);
+        assert_eq!(state.current_slot(), 40 * SLOTS_PER_EPOCH + (1 << 40));
+        assert_eq!(state.get_scheduled_payment(subscription).unwrap().executed, 3);
+        let epochs = state.current_epoch();
+        assert_eq!(state.get_stake("AliceStake").unwrap().rewards_earned, 750 * (epochs - 1));
+        assert_eq!(state.get_stake("BobStake").unwrap().rewards_earned, 250 * (epochs - 1));
+    }
+
+    #[test]
+    fn test_vaults_for_names_longer_than_a_seed() {
+        use crate::keypair::Keypair;
+
+        // A keypair account's name is its 43-44 character address
+        let owner = Keypair::from_seed([3; 32]).pubkey().to_string();
+        let long_name = "a-stake-account-name-of-35-chars-xx";
+        let mut state = AppState::new();
+        state.create_account(owner.clone(), 10_000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+
+        state.create_vesting(&owner, &owner, 100, 0, 0, 10).unwrap();
+        state.create_escrow(long_name.into(), &owner, "Bob", "Bob", 100, 10).unwrap();
+        state.create_stake(&owner, long_name, 100).unwrap();
+        state.create_nonce_account(&owner, long_name, &owner, 100).unwrap();
+        assert_eq!(state.get_account(&owner).unwrap().balance, 9_600);
+
+        state.advance_slots(10);
+        state.claim_vested(&owner, 100).unwrap();
+        assert_eq!(state.get_account(&owner).unwrap().balance, 9_700);
+    }
+
+    #[test]
+    fn test_nonce_account_lifecycle() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1000).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+
+        let address = state.create_nonce_account("Alice", "AliceNonce", "Bob", 100).unwrap();
+        assert_eq!(state.get_account_by_address(&address).unwrap().balance, 100);
+        assert!(state.create_nonce_account("Alice", "AliceNonce", "Bob", 100).is_err());
+        assert!(matches!(state.get_nonce("Missing"), Err(AppError::NonceNotFound(_))));
+        // Plain accounts are not nonce accounts
+        assert!(matches!(state.get_nonce("Alice"), Err(AppError::NonceNotFound(_))));
+
+        let first = state.get_nonce("AliceNonce").unwrap().nonce;
+        assert!(matches!(state.advance_nonce("AliceNonce", "Alice"), Err(AppError::Unauthorized(_))));
+        let second = state.advance_nonce("AliceNonce", "Bob").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(state.get_nonce("AliceNonce").unwrap().nonce, second);
+
+        // Only the authority may withdraw, and the account cannot be drained by a plain transfer
+        assert!(state.transfer(&address.to_string(), "Alice", 10).is_err());
+        assert!(state.withdraw_nonce("AliceNonce", "Alice", "Alice", 10).is_err());
+        state.withdraw_nonce("AliceNonce", "Bob", "Bob", 40).unwrap();
+        assert_eq!(state.get_account("Bob").unwrap().balance, 41);
+        state.withdraw_nonce("AliceNonce", "Bob", "Bob", 60).unwrap();
+        assert!(matches!(state.get_nonce("AliceNonce"), Err(AppError::NonceNotFound(_))));
+    }
+
+    #[test]
+    fn test_scheduled_payments_run_on_tick() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 250).unwrap();
+        state.create_account("Bob".into(), 100).unwrap();
+        state.create_account("Netflix".into(), 1).unwrap();
+
+        let subscription = state.schedule_payment("Alice", "Netflix", 100, 10, 3).unwrap();
+        let rent = state.schedule_payment("Bob", "Netflix", 10, 5, 4).unwrap();
+
+        for _ in 0..9 {
+            state.tick();
+        }
+        assert_eq!(state.get_account("Netflix").unwrap().balance, 11);
+        state.tick(); // slot 10
+        assert_eq!(state.get_account("Alice").unwrap().balance, 150);
+
+        // The third charge bounces; it is recorded and the rent still goes out
+        state.advance_slots(20);
+        let payment = state.get_scheduled_payment(subscription).unwrap();
+        assert_eq!(payment.executed, 2);
+        assert_eq!(payment.failures.len(), 1);
+        assert_eq!(payment.failures[0].slot, 30);
+        assert!(matches!(payment.failures[0].error, AppError::InsufficientBalance { .. }));
+        assert!(!payment.is_active());
+
+        let payment = state.get_scheduled_payment(rent).unwrap();
+        assert_eq!(payment.executed, 4);
+        assert_eq!(state.get_account("Netflix").unwrap().balance, 241);
+        assert!(state.get_scheduled_payment(99).is_err());
+    }
+
+    #[test]
+    fn test_tokens_mint_and_transfer() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 1).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+        state.create_mint("USDC", "Circle").unwrap();
+
+        assert!(matches!(state.mint_to("USDC", "Alice", "Alice", 100), Err(AppError::Unauthorized(_))));
+        state.mint_to("USDC", "Circle", "Alice", 100).unwrap();
+        state.transfer_tokens("USDC", "Alice", "Bob", 60).unwrap();
+        assert!(state.transfer_tokens("USDC", "Alice", "Bob", 41).is_err());
+
+        assert_eq!(state.get_account("Alice").unwrap().token_balance("USDC"), 40);
+        assert_eq!(state.get_account("Bob").unwrap().token_balance("USDC"), 60);
+        assert_eq!(state.get_mint("USDC").unwrap().supply, 100);
+    }
+
+    #[test]
+    fn test_amm_pool_round_trip() {
+        let mut state = AppState::new();
+        state.create_account("Lp".into(), 1).unwrap();
+        state.create_account("Trader".into(), 1).unwrap();
+        state.create_mint("SOL", "Issuer").unwrap();
+        state.create_mint("USDC", "Issuer").unwrap();
+        state.mint_to("SOL", "Issuer", "Lp", 1_000).unwrap();
+        state.mint_to("USDC", "Issuer", "Lp", 100_000).unwrap();
+        state.mint_to("SOL", "Issuer", "Trader", 50).unwrap();
+
+        state.create_pool("SOL-USDC", "SOL", "USDC", 30).unwrap();
+        let deposit = state.add_liquidity("SOL-USDC", "Lp", 1_000, 100_000, 10_000).unwrap();
+        assert_eq!(deposit.shares, 10_000);
+        assert_eq!(state.get_mint("LP-SOL-USDC").unwrap().supply, 10_000);
+
+        // Slippage protection rejects the trade and changes nothing
+        let result = state.swap("SOL-USDC", "Trader", "SOL", 10, 1_000);
+        assert!(matches!(result, Err(AppError::SlippageExceeded { .. })));
+        assert_eq!(state.get_account("Trader").unwrap().token_balance("SOL"), 50);
+
+        let out = state.swap("SOL-USDC", "Trader", "SOL", 10, 980).unwrap();
+        assert_eq!(out, 987);
+        assert_eq!(state.get_account("Trader").unwrap().token_balance("USDC"), 987);
+
+        // Withdrawing everything returns the reserves including the fee
+        let (a, b) = state.remove_liquidity("SOL-USDC", "Lp", 10_000, 1_010, 0).unwrap();
+        assert_eq!((a, b), (1_010, 100_000 - 987));
+        assert_eq!(state.get_pool("SOL-USDC").unwrap().lp_supply, 0);
+        assert_eq!(state.get_mint("SOL").unwrap().supply, 1_050);
+    }
+
+    #[test]
+    fn test_lp_mint_only_changes_through_its_pool() {
+        let mut state = AppState::new();
+        state.create_account("Lp".into(), 1).unwrap();
+        state.create_account("Mallory".into(), 1).unwrap();
+        state.create_mint("SOL", "Issuer").unwrap();
+        state.create_mint("USDC", "Issuer").unwrap();
+        state.mint_to("SOL", "Issuer", "Lp", 1_000).unwrap();
+        state.mint_to("USDC", "Issuer", "Lp", 100_000).unwrap();
+        state.create_pool("pool2", "SOL", "USDC", 30).unwrap();
+        state.add_liquidity("pool2", "Lp", 1_000, 100_000, 0).unwrap();
+
+        // The vault's address is public, but naming it does not mint shares
+        let vault = state.get_pool("pool2").unwrap().vault.to_string();
+        let result = state.mint_to("LP-pool2", &vault, "Mallory", 1_000_000);
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        assert!(state.remove_liquidity("pool2", "Mallory", 10_000, 0, 0).is_err());
+        assert_eq!(state.get_mint("LP-pool2").unwrap().supply, state.get_pool("pool2").unwrap().lp_supply);
+        assert_eq!(state.get_account("Mallory").unwrap().token_balance("SOL"), 0);
+    }
+
+    #[test]
+    fn test_lp_symbols_are_reserved_for_pools() {
+        let mut state = AppState::new();
+        state.create_mint("SOL", "Issuer").unwrap();
+        state.create_mint("USDC", "Issuer").unwrap();
+
+        // Nobody can take a pool's LP symbol before the pool exists
+        assert!(matches!(state.create_mint("LP-pool", "Mallory"), Err(AppError::InvalidAccountName(_))));
+        state.create_pool("pool", "SOL", "USDC", 30).unwrap();
+
+        // A pool name must also make a valid LP symbol
+        let result = state.create_pool("AAA_BBB_pool_main", "SOL", "USDC", 30);
+        assert!(matches!(result, Err(AppError::InvalidAccountName(name)) if name == "AAA_BBB_pool_main"));
+        assert!(state.create_pool("SOL-USDC-main", "SOL", "USDC", 30).is_ok());
+        assert!(state.create_pool("SOL-USDC-main2", "SOL", "USDC", 30).is_err());
+    }
+
+    #[test]
+    fn test_state_root_and_account_proofs() {
+        let mut state = AppState::new();
+        let empty_root = state.state_root();
+        state.create_account("Alice".into(), 100).unwrap();
+        state.create_account("Bob".into(), 50).unwrap();
+        let root = state.state_root();
+        assert_ne!(root, empty_root);
+
+        let proof = state.prove_account("Alice");
+        assert!(proof.verify(&root));
+        assert_eq!(proof.account.as_ref().unwrap().balance, 100);
+
+        // A forged balance does not check out
+        let mut forged = proof.clone();
+        forged.account.as_mut().unwrap().balance = 1_000_000;
+        assert!(!forged.verify(&root));
+
+        // Absence can be proven too
+        let missing = state.prove_account("Carol");
+        assert!(missing.account.is_none());
+        assert!(missing.verify(&root));
+
+        // Every write path moves the root; old proofs go stale
+        state.transfer("Alice", "Bob", 10).unwrap();
+        let after_transfer = state.state_root();
+        assert_ne!(after_transfer, root);
+        assert!(!proof.verify(&after_transfer));
+        assert!(state.prove_account("Alice").verify(&after_transfer));
+
+        state.get_account_mut("Bob").unwrap().add_balance(1).unwrap();
+        assert_ne!(state.state_root(), after_transfer);
+
+        // A deleted account is proven absent under the new root
+        state.delete_account("Bob").unwrap();
+        let after_delete = state.state_root();
+        let gone = state.prove_account("Bob");
+        assert!(gone.account.is_none());
+        assert!(gone.verify(&after_delete));
+    }
+
+    #[test]
+    fn test_program_upgrade_lifecycle() {
+        use crate::vm::{assemble, Insn, Opcode};
+
+        let v1 = assemble(&[Insn::new(Opcode::MovImm, 0, 0, 0), Insn::new(Opcode::Exit, 0, 0, 0)]);
+        let v2 = assemble(&[Insn::new(Opcode::MovImm, 0, 0, 1), Insn::new(Opcode::Exit, 0, 0, 0)]);
+        let mut state = AppState::new();
+        state.create_account("Dev".into(), 1).unwrap();
+        state.create_account("Ops".into(), 1).unwrap();
+
+        let id = state.deploy_program("Dev", "app", v1).unwrap();
+        assert_eq!(state.program_data(&id).unwrap().version, 1);
+
+        state.advance_slots(5);
+        assert!(matches!(state.upgrade_program(&id, "Ops", v2.clone()), Err(AppError::Unauthorized(_))));
+        assert!(state.upgrade_program(&id, "Dev", vec![0xff; 8]).is_err());
+        assert_eq!(state.upgrade_program(&id, "Dev", v2.clone()).unwrap(), 2);
+        let data = state.program_data(&id).unwrap();
+        assert_eq!((data.version, data.deployed_slot), (2, 5));
+        assert_eq!(data.bytecode, v2);
+
+        state.set_upgrade_authority(&id, "Dev", Some("Ops".into())).unwrap();
+        assert!(state.upgrade_program(&id, "Dev", v2.clone()).is_err());
+        state.set_upgrade_authority(&id, "Ops", None).unwrap();
+        assert!(matches!(state.upgrade_program(&id, "Ops", v2), Err(AppError::ProgramImmutable(_))));
+        assert!(state.set_upgrade_authority(&id, "Ops", Some("Ops".into())).is_err());
+        assert!(matches!(state.program_data(&Pubkey::new([9; 32])), Err(AppError::ProgramNotFound(_))));
+    }
+
+    #[test]
+    fn test_deploy_from_keypair_account() {
+        use crate::keypair::Keypair;
+        use crate::vm::{assemble, Insn, Opcode};
+
+        // Keypair account names are longer than a single seed
+        let dev = Keypair::from_seed([7; 32]).pubkey().to_string();
+        let mut state = AppState::new();
+        state.create_account(dev.clone(), 1).unwrap();
+
+        let bytecode = assemble(&[Insn::new(Opcode::MovImm, 0, 0, 0), Insn::new(Opcode::Exit, 0, 0, 0)]);
+        let id = state.deploy_program(&dev, "long-program-name-over-32-bytes", bytecode).unwrap();
+        assert_eq!(id, loader::program_id(&dev, "long-program-name-over-32-bytes").unwrap());
+        assert_eq!(state.program_data(&id).unwrap().upgrade_authority, Some(dev));
+    }
+
+    #[test]
+    fn test_name_registration_and_expiry() {
+        let mut state = AppState::new();
+        state.create_account("Alice".into(), 100).unwrap();
+        state.create_account("Bob".into(), 100).unwrap();
+
+        state.register_name("alice.sol", "Alice", "Alice", 100).unwrap();
+        assert_eq!(state.resolve_account("alice.sol").unwrap(), "Alice");
+        assert_eq!(state.resolve_account("Bob").unwrap(), "Bob");
+        assert!(matches!(state.register_name("alice.sol", "Bob", "Bob", 100), Err(AppError::NameTaken(_))));
+        assert!(state.register_name("Alice", "Alice", "Alice", 100).is_err());
+
+        // Renewal extends from the current expiry
+        state.advance_slots(50);
+        state.register_name("alice.sol", "Alice", "Alice", 100).unwrap();
+        assert_eq!(state.get_name_record("alice.sol").unwrap().expires_at, 200);
+
+        state.transfer_name("alice.sol", "Alice", "Bob").unwrap();
+        assert!(state.transfer_name("alice.sol", "Alice", "Bob").is_err());
+
+        // Once lapsed the name stops resolving and is up for grabs
+        state.advance_slots(150);
+        assert!(matches!(state.resolve_name("alice.sol"), Err(AppError::NameExpired(_))));
+        state.register_name("alice.sol", "Alice", "Alice", 10).unwrap();
+        assert_eq!(state.get_name_record("alice.sol").unwrap().owner, "Alice");
+    }
+
+    #[test]
+    fn test_multisig_controlled_account() {
+        let mut state = AppState::new();
+        state.create_account("Treasury".into(), 1000).unwrap();
+        state.create_account("Vendor".into(), 10).unwrap();
+        let keys: Vec<String> = vec!["Alice".into(), "Bob".into(), "Carol".into()];
+        state.create_multisig("Council".into(), keys, 2).unwrap();
+        state.set_authority("Treasury", Some("Council".into()), &["Treasury".into()]).unwrap();
+
+        // Single-key paths are closed once a multisig is in charge
+        assert!(matches!(state.transfer("Treasury", "Vendor", 100), Err(AppError::Unauthorized(_))));
+        assert!(state.approve("Treasury", "Vendor", 100).is_err());
+
+        let one = vec!["Alice".to_string()];
+        let result = state.multisig_transfer("Treasury", "Vendor", 100, &one);
+        assert!(matches!(result, Err(AppError::NotEnoughSignatures { required: 2, provided: 1 })));
+
+        let two = vec!["Alice".to_string(), "Carol".to_string()];
+        state.multisig_transfer("Treasury", "Vendor", 100, &two).unwrap();
+        assert_eq!(state.get_account("Treasury").unwrap().balance, 900);
+
+        // Releasing control also needs the multisig
+        assert!(state.set_authority("Treasury", None, &one).is_err());
+        state.set_authority("Treasury", None, &two).unwrap();
+        state.transfer("Treasury", "Vendor", 100).unwrap();
+    }
+
+    #[test]
+    fn test_set_authority_needs_the_accounts_own_signature() {
+        let mut state = AppState::new();
+        state.create_account("Treasury".into(), 1000).unwrap();
+        state.create_multisig("Gang".into(), vec!["Mallory".into()], 1).unwrap();
+
+        // Anyone could otherwise hand an account without an authority to a
+        // multisig of their own
+        for signers in [vec![], vec!["Mallory".to_string()]] {
+            let result = state.set_authority("Treasury", Some("Gang".into()), &signers);
+            assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        }
+        assert_eq!(state.get_account("Treasury").unwrap().authority, None);
+
+        state.set_authority("Treasury", Some("Gang".into()), &["Treasury".into()]).unwrap();
+        assert_eq!(state.get_account("Treasury").unwrap().authority.as_deref(), Some("Gang"));
+    }
+
+    #[test]
+    fn test_delegates_cannot_spend_from_multisig_controlled_accounts() {
+        let mut state = AppState::new();
+        state.create_account("Treasury".into(), 1000).unwrap();
+        state.create_account("Vendor".into(), 10).unwrap();
+        state.create_multisig("Council".into(), vec!["Alice".into(), "Bob".into()], 2).unwrap();
+        state.approve("Treasury", "Vendor", 100).unwrap();
+
+        // Handing the account to a multisig revokes the earlier delegate
+        state.set_authority("Treasury", Some("Council".into()), &["Treasury".into()]).unwrap();
+        let account = state.get_account("Treasury").unwrap();
+        assert_eq!((account.delegate.as_deref(), account.delegated_amount), (None, 0));
+
+        // A delegate left over some other way still cannot go around the multisig
+        state.get_account_mut("Treasury").unwrap().approve("Vendor".into(), 100).unwrap();
+        let delegated = state.transfer_from("Vendor", "Treasury", "Vendor", 100);
+        assert!(matches!(delegated, Err(AppError::Unauthorized(_))));
+        assert_eq!(state.get_account("Treasury").unwrap().balance, 1000);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,228 @@
+// ============================================
+// SYSTEM PROGRAM
+// ============================================
+// The built-in program behind the five core account instructions. Its
+// instruction data is a one-byte tag followed by a little-endian u64 amount
+// where the instruction takes one.
+
+use crate::error::{AppError, Result};
+use crate::names;
+use crate::program::{account_at, AccountMeta, InvokeContext, Program};
+use crate::pubkey::{Pubkey, SYSTEM_PROGRAM_ID};
+use crate::state::AppState;
+
+/// Instructions understood by the system program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemInstruction {
+    /// Accounts: [new account (signer, writable)]
+    CreateAccount { balance: u64 },
+    /// Accounts: [from (signer, writable), to (writable)]; `to` may be a registered name
+    Transfer { amount: u64 },
+    /// Accounts: [account (writable)]
+    UpdateBalance { amount: u64 },
+    /// Accounts: [account]
+    GetBalance,
+    /// Accounts: [account (signer, writable)]
+    DeleteAccount,
+}
+
+impl SystemInstruction {
+    /// Encode as instruction data
+    pub fn pack(&self) -> Vec<u8> {
+        let (tag, amount) = match self {
+            SystemInstruction::CreateAccount { balance } => (0, Some(*balance)),
+            SystemInstruction::Transfer { amount } => (1, Some(*amount)),
+            SystemInstruction::UpdateBalance { amount } => (2, Some(*amount)),
+            SystemInstruction::GetBalance => (3, None),
+            SystemInstruction::DeleteAccount => (4, None),
+        };
+        let mut data = vec![tag];
+        if let Some(amount) = amount {
+            data.extend_from_slice(&amount.to_le_bytes());
+        }
+        data
+    }
+
+    /// Decode instruction data
+    pub fn unpack(data: &[u8]) -> Result<Self> {
+        let (&tag, rest) = data
+            .split_first()
+            .ok_or_else(|| AppError::InvalidInstructionData("empty instruction data".to_string()))?;
+
+        let amount = || -> Result<u64> {
+            let bytes: [u8; 8] = rest.try_into().map_err(|_| {
+                AppError::InvalidInstructionData(format!("expected 8 byte amount, got {} bytes", rest.len()))
+            })?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let no_amount = || -> Result<()> {
+            if !rest.is_empty() {
+                return Err(AppError::InvalidInstructionData(format!(
+                    "unexpected {} trailing bytes",
+                    rest.len()
+                )));
+            }
+            Ok(())
+        };
+
+        match tag {
+            0 => Ok(SystemInstruction::CreateAccount { balance: amount()? }),
+            1 => Ok(SystemInstruction::Transfer { amount: amount()? }),
+            2 => Ok(SystemInstruction::UpdateBalance { amount: amount()? }),
+            3 => no_amount().map(|_| SystemInstruction::GetBalance),
+            4 => no_amount().map(|_| SystemInstruction::DeleteAccount),
+            _ => Err(AppError::InvalidInstructionData(format!("unknown system instruction {}", tag))),
+        }
+    }
+}
+
+/// The built-in system program
+pub struct SystemProgram;
+
+impl Program for SystemProgram {
+    fn id(&self) -> Pubkey {
+        SYSTEM_PROGRAM_ID
+    }
+
+    fn process(&self, ctx: &mut InvokeContext, accounts: &[AccountMeta], data: &[u8]) -> Result<String> {
+        let state = ctx.state_mut();
+        match SystemInstruction::unpack(data)? {
+            SystemInstruction::CreateAccount { balance } => {
+                let account = signed_writable(accounts, 0)?;
+                process_create_account(account, balance, state)
+            }
+            SystemInstruction::Transfer { amount } => {
+                let from = signed_writable(accounts, 0)?;
+                let to = account_at(accounts, 1)?;
+                to.check_writable()?;
+                process_transfer(from, &to.key, amount, state)
+            }
+            SystemInstruction::UpdateBalance { amount } => {
+                let account = account_at(accounts, 0)?;
+                account.check_writable()?;
+                process_update_balance(&account.key, amount, state)
+            }
+            SystemInstruction::GetBalance => {
+                process_get_balance(&account_at(accounts, 0)?.key, state)
+            }
+            SystemInstruction::DeleteAccount => {
+                let account = signed_writable(accounts, 0)?;
+                process_delete_account(account, state)
+            }
+        }
+    }
+}
+
+/// Key of the account at `index`, which must be a writable signer
+fn signed_writable(accounts: &[AccountMeta], index: usize) -> Result<&str> {
+    let account = account_at(accounts, index)?;
+    account.check_signer()?;
+    account.check_writable()?;
+    Ok(&account.key)
+}
+
+/// Process create account instruction
+fn process_create_account(name: &str, balance: u64, state: &mut AppState) -> Result<String> {
+    // TODO 4.3: Implement create account logic
+    state.create_account(name.to_string(), balance)?;
+    Ok(format!("Created account '{}' with balance {}", name, balance))
+}
+
+/// Process transfer instruction; `to` may be an account name or a registered name
+fn process_transfer(from: &str, to: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    // TODO 4.4: Implement transfer logic
+    // Signatures are checked against the names in the instruction, before any
+    // name resolves, so funds only leave an account named directly
+    if names::is_registered_name(from) {
+        return Err(AppError::Unauthorized(format!("cannot send from registered name {}", from)));
+    }
+    let from_account = from.to_string();
+    let to_account = state.resolve_account(to)?;
+    if from_account == to_account {
+        return Err(AppError::TransferToSelf(from_account));
+    }
+    state.transfer(&from_account, &to_account, amount)?;
+    Ok(format!("Transferred {} from '{}' to '{}'", amount, from, to))
+}
+
+/// Process update balance instruction
+fn process_update_balance(name: &str, amount: u64, state: &mut AppState) -> Result<String> {
+    // TODO 4.5: Implement update balance logic
+    let account = state.get_account_mut(name)?;
+    account.add_balance(amount)?;
+    Ok(format!("Added {} to account '{}'", amount, name))
+}
+
+/// Process get balance instruction
+fn process_get_balance(name: &str, state: &AppState) -> Result<String> {
+    // TODO 4.6: Implement get balance logic
+    let account = state.get_account(name)?;
+    let balance = account.get_balance();
+    Ok(format!("Account '{}' balance: {}", name, balance))
+}
+
+/// Process delete account instruction
+fn process_delete_account(name: &str, state: &mut AppState) -> Result<String> {
+    // TODO 4.7: Implement delete account logic
+    let account = state.get_account(name)?;
+    if account.get_balance() > 0 {
+        return Err(AppError::InvalidAmount(account.get_balance()));
+    }
+    // Token balances would vanish from their mint's supply
+    if let Some((_, amount)) = account.tokens.iter().next() {
+        return Err(AppError::InvalidAmount(*amount));
+    }
+    let deleted_account = state.delete_account(name)?;
+    Ok(format!("Deleted account '{}'", deleted_account.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_roundtrip() {
+        for instruction in [
+            SystemInstruction::CreateAccount { balance: 1000 },
+            SystemInstruction::Transfer { amount: u64::MAX },
+            SystemInstruction::UpdateBalance { amount: 1 },
+            SystemInstruction::GetBalance,
+            SystemInstruction::DeleteAccount,
+        ] {
+            assert_eq!(SystemInstruction::unpack(&instruction.pack()).unwrap(), instruction);
+        }
+
+        assert!(SystemInstruction::unpack(&[]).is_err());
+        assert!(SystemInstruction::unpack(&[1, 0, 0]).is_err());
+        assert!(SystemInstruction::unpack(&[3, 0]).is_err());
+        assert!(SystemInstruction::unpack(&[9]).is_err());
+    }
+
+    #[test]
+    fn test_system_program_privileges() {
+        let registry = crate::program::ProgramRegistry::with_builtins();
+        let mut state = AppState::new();
+        let create = SystemInstruction::CreateAccount { balance: 100 }.pack();
+        for name in ["Alice", "Bob"] {
+            let accounts = [AccountMeta::new(name.into(), true)];
+            registry.invoke(&SYSTEM_PROGRAM_ID, &accounts, &create, &mut state).unwrap();
+        }
+
+        let transfer = SystemInstruction::Transfer { amount: 40 }.pack();
+        let mut accounts = vec![AccountMeta::new("Alice".into(), true), AccountMeta::new("Bob".into(), false)];
+        registry.invoke(&SYSTEM_PROGRAM_ID, &accounts, &transfer, &mut state).unwrap();
+        assert_eq!(state.get_account("Bob").unwrap().balance, 140);
+
+        accounts[1].is_writable = false;
+        let result = registry.invoke(&SYSTEM_PROGRAM_ID, &accounts, &transfer, &mut state);
+        assert!(matches!(result, Err(AppError::AccountNotWritable(_))));
+
+        accounts[0].is_signer = false;
+        let result = registry.invoke(&SYSTEM_PROGRAM_ID, &accounts, &transfer, &mut state);
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+        accounts[0].is_signer = true;
+        let result = registry.invoke(&SYSTEM_PROGRAM_ID, &accounts[..1], &transfer, &mut state);
+        assert!(matches!(result, Err(AppError::InvalidInstructionData(_))));
+    }
+}
@@ -0,0 +1,80 @@
+// ============================================
+// TOKENS
+// ============================================
+// Fungible tokens identified by a mint symbol. Balances live on each
+// `Account` next to its native balance; the mint tracks the total supply.
+
+use crate::error::{AppError, Result};
+
+/// Struct representing a token mint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mint {
+    pub symbol: String,
+    pub authority: String, // Only the authority may mint new tokens
+    pub supply: u64,
+}
+
+impl Mint {
+    /// Create a new mint with zero supply
+    pub fn new(symbol: String, authority: String) -> Result<Self> {
+        Mint::validate_symbol(&symbol)?;
+        Ok(Self {
+            symbol,
+            authority,
+            supply: 0,
+        })
+    }
+
+    /// Check if a token symbol is valid (2-16 ASCII letters, digits or '-')
+    pub fn validate_symbol(symbol: &str) -> Result<()> {
+        let valid_chars = symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if symbol.len() < 2 || symbol.len() > 16 || !valid_chars {
+            return Err(AppError::InvalidAccountName(symbol.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Increase the supply, with overflow check
+    pub fn increase_supply(&mut self, amount: u64) -> Result<()> {
+        self.supply = self
+            .supply
+            .checked_add(amount)
+            .ok_or(AppError::InvalidAmount(amount))?;
+        Ok(())
+    }
+
+    /// Decrease the supply when tokens are burned
+    pub fn decrease_supply(&mut self, amount: u64) -> Result<()> {
+        self.supply = self
+            .supply
+            .checked_sub(amount)
+            .ok_or(AppError::InvalidAmount(amount))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_symbol() {
+        assert!(Mint::validate_symbol("USDC").is_ok());
+        assert!(Mint::validate_symbol("LP-pool1").is_ok());
+        assert!(Mint::validate_symbol("X").is_err());
+        assert!(Mint::validate_symbol("has space").is_err());
+        assert!(Mint::validate_symbol("WAYTOOLONGSYMBOL1").is_err());
+    }
+
+    #[test]
+    fn test_supply_changes() {
+        let mut mint = Mint::new("USDC".into(), "Circle".into()).unwrap();
+        mint.increase_supply(100).unwrap();
+        mint.decrease_supply(40).unwrap();
+        assert_eq!(mint.supply, 60);
+        assert!(mint.decrease_supply(61).is_err());
+        assert!(mint.increase_supply(u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+// ============================================
+// TRANSACTIONS
+// ============================================
+// A transaction is an ordered list of instructions that succeed or fail
+// together. It names a recent block hash, which bounds how long it can be
+// submitted, and may bid a priority fee for earlier inclusion. Its hash
+// identifies it inside blocks. Signatures cover the message and are not part
+// of it, so signing does not change the hash.
+
+use std::str::FromStr;
+
+use crate::codec::{Decoder, Encoder};
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::instruction::Instruction;
+use crate::keypair::{Keypair, Signature};
+use crate::pubkey::Pubkey;
+
+/// Struct representing an atomic list of instructions
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub recent_blockhash: Hash,
+    pub priority_fee: u64, // Higher fees are scheduled first
+    pub instructions: Vec<Instruction>,
+    pub signatures: Vec<(Pubkey, Signature)>, // One per signing key, over `message()`
+}
+
+impl Transaction {
+    /// Create a new transaction valid while `recent_blockhash` is recent
+    pub fn new(instructions: Vec<Instruction>, recent_blockhash: Hash) -> Self {
+        Self {
+            recent_blockhash,
+            priority_fee: 0,
+            instructions,
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Bid `fee` for earlier inclusion
+    pub fn with_priority_fee(mut self, fee: u64) -> Self {
+        self.priority_fee = fee;
+        self
+    }
+
+    /// Canonical encoding of the transaction contents
+    pub fn message(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new()
+            .put(&self.recent_blockhash)
+            .put(&self.priority_fee)
+            .put(&(self.instructions.len() as u32));
+        for instruction in &self.instructions {
+            encoder = encoder.put(&instruction.encode());
+        }
+        encoder.finish()
+    }
+
+    /// Sign with each of `signers`, replacing any earlier signature by the
+    /// same key. Sign last: changing the transaction invalidates signatures.
+    pub fn sign(mut self, signers: &[&Keypair]) -> Self {
+        let message = self.message();
+        for signer in signers {
+            let signature = signer.sign(&message);
+            match self.signatures.iter_mut().find(|(key, _)| *key == signer.pubkey()) {
+                Some((_, existing)) => *existing = signature,
+                None => self.signatures.push((signer.pubkey(), signature)),
+            }
+        }
+        self
+    }
+
+    /// Check every attached signature, and that every keypair account among
+    /// the instructions' signers signed
+    pub fn verify_signatures(&self) -> Result<()> {
+        let message = self.message();
+        for (key, signature) in &self.signatures {
+            if !signature.verify(key, &message) {
+                return Err(AppError::InvalidSignature(key.to_string()));
+            }
+        }
+        for signer in self.instructions.iter().flat_map(Instruction::signers) {
+            // Plain names like "Alice" are not keypair accounts
+            if let Ok(key) = Pubkey::from_str(signer)
+                && !self.signatures.iter().any(|(signed, _)| *signed == key)
+            {
+                return Err(AppError::MissingSignature(signer.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Wire format: the signatures, then the message
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new().put(&(self.signatures.len() as u32));
+        for (key, signature) in &self.signatures {
+            encoder = encoder.put(key).put(signature);
+        }
+        let mut bytes = encoder.finish();
+        bytes.extend_from_slice(&self.message());
+        bytes
+    }
+
+    /// Decode a transaction from its wire format (see `encode`)
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut decoder = Decoder::new(data);
+        let count: u32 = decoder.get()?;
+        let signatures = (0..count)
+            .map(|_| Ok((decoder.get()?, decoder.get()?)))
+            .collect::<Result<_>>()?;
+        let recent_blockhash = decoder.get()?;
+        let priority_fee = decoder.get()?;
+        let instructions = decoder
+            .get::<Vec<Vec<u8>>>()?
+            .iter()
+            .map(|instruction| Instruction::decode(instruction))
+            .collect::<Result<_>>()?;
+        decoder.finish()?;
+        Ok(Self {
+            recent_blockhash,
+            priority_fee,
+            instructions,
+            signatures,
+        })
+    }
+
+    /// Hash identifying the transaction
+    pub fn hash(&self) -> Hash {
+        hash::sha256(&self.message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(amount: u64, blockhash: Hash) -> Transaction {
+        Transaction::new(vec![Instruction::transfer("Alice".into(), "Bob".into(), amount)], blockhash)
+    }
+
+    #[test]
+    fn test_hash_depends_on_contents() {
+        let a = transfer(1, [0; 32]);
+        let b = transfer(2, [0; 32]);
+        let c = transfer(1, [0; 32]);
+        assert_ne!(a.hash(), b.hash());
+        assert_eq!(a.hash(), c.hash());
+
+        // Splitting the same instructions differently changes the hash
+        let one = Transaction::new(vec![a.instructions[0].clone(), b.instructions[0].clone()], [0; 32]);
+        assert_ne!(one.hash(), a.hash());
+
+        // So does the blockhash: a retry with a fresh one is a new transaction
+        assert_ne!(transfer(1, [1; 32]).hash(), a.hash());
+        assert_ne!(a.clone().with_priority_fee(5).hash(), a.hash());
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let transaction = Transaction::new(
+            vec![
+                Instruction::advance_nonce("Cold".into(), "Alice".into()),
+                Instruction::transfer("Alice".into(), "Bob".into(), 7),
+            ],
+            [3; 32],
+        )
+        .with_priority_fee(9);
+        let decoded = Transaction::decode(&transaction.encode()).unwrap();
+        assert_eq!(decoded.hash(), transaction.hash());
+        assert_eq!(decoded.priority_fee, 9);
+
+        let encoded = transaction.encode();
+        assert!(Transaction::decode(&encoded[..encoded.len() - 1]).is_err());
+
+        let signed = transaction.sign(&[&Keypair::from_seed([1; 32])]);
+        let decoded = Transaction::decode(&signed.encode()).unwrap();
+        assert_eq!(decoded.signatures, signed.signatures);
+        assert!(decoded.verify_signatures().is_ok());
+    }
+
+    #[test]
+    fn test_keypair_accounts_must_sign() {
+        let payer = Keypair::from_seed([7; 32]);
+        let other = Keypair::from_seed([8; 32]);
+        let unsigned = Transaction::new(
+            vec![Instruction::transfer(payer.account_name(), "Bob".into(), 5)],
+            [0; 32],
+        );
+        // Plain-named accounts need no signature
+        assert!(transfer(1, [0; 32]).verify_signatures().is_ok());
+        assert!(matches!(unsigned.verify_signatures(), Err(AppError::MissingSignature(_))));
+        assert!(matches!(
+            unsigned.clone().sign(&[&other]).verify_signatures(),
+            Err(AppError::MissingSignature(_))
+        ));
+
+        let signed = unsigned.clone().sign(&[&payer, &other]);
+        assert!(signed.verify_signatures().is_ok());
+        assert_eq!(signed.hash(), unsigned.hash());
+        // Signing again replaces rather than duplicates
+        assert_eq!(signed.clone().sign(&[&payer]).signatures.len(), 2);
+
+        // Changing anything after signing breaks the signature
+        let tampered = signed.with_priority_fee(1);
+        assert!(matches!(tampered.verify_signatures(), Err(AppError::InvalidSignature(_))));
+    }
+}
@@ -0,0 +1,162 @@
+// ============================================
+// VESTING SCHEDULES
+// ============================================
+// Funds held in a program derived vault and unlocked linearly over slots,
+// with nothing available before the cliff
+
+use crate::error::{AppError, Result};
+use crate::hash::{self, Hash};
+use crate::pubkey::Pubkey;
+
+/// Program that owns every vesting vault
+pub const VESTING_PROGRAM_ID: Pubkey = Pubkey::new(*b"VestingProgram111111111111111111");
+
+/// Struct representing one beneficiary's vesting schedule (all times in slots)
+#[derive(Debug, Clone, PartialEq)]
+pub struct VestingSchedule {
+    pub beneficiary: String,
+    pub total: u64,
+    pub start: u64,    // Slot the schedule starts
+    pub cliff: u64,    // Slots after `start` before anything unlocks
+    pub duration: u64, // Slots after `start` until everything is unlocked
+    pub claimed: u64,
+    pub vault: Pubkey, // PDA of VESTING_PROGRAM_ID holding the unclaimed funds
+}
+
+impl VestingSchedule {
+    /// Create a new schedule, checking that cliff <= duration and 0 < duration.
+    /// A cliff of 0 starts unlocking right away.
+    pub fn new(beneficiary: String, total: u64, start: u64, cliff: u64, duration: u64) -> Result<Self> {
+        AppError::validate_amount(total)?;
+        if duration == 0 || cliff > duration || start.checked_add(duration).is_none() {
+            return Err(AppError::InvalidVestingSchedule(format!(
+                "cliff {} and duration {} must satisfy cliff <= duration and duration > 0",
+                cliff, duration
+            )));
+        }
+
+        let (vault, _) = Pubkey::find_program_address(
+            &VestingSchedule::vault_seeds(&hash::sha256(beneficiary.as_bytes())),
+            &VESTING_PROGRAM_ID,
+        )?;
+        Ok(Self {
+            beneficiary,
+            total,
+            start,
+            cliff,
+            duration,
+            claimed: 0,
+            vault,
+        })
+    }
+
+    /// Seeds of the vault PDA, from the hash of the beneficiary's name so
+    /// that every valid name fits in one seed
+    pub fn vault_seeds(beneficiary_hash: &Hash) -> [&[u8]; 2] {
+        [b"vesting", beneficiary_hash]
+    }
+
+    /// Total amount unlocked at `slot`, whether claimed or not
+    pub fn unlocked_at(&self, slot: u64) -> u64 {
+        if slot < self.start + self.cliff {
+            return 0;
+        }
+        let elapsed = slot - self.start;
+        if elapsed >= self.duration {
+            return self.total;
+        }
+        // total * elapsed / duration without overflow; result < total
+        (self.total as u128 * elapsed as u128 / self.duration as u128) as u64
+    }
+
+    /// Amount that can still be claimed at `slot`
+    pub fn claimable_at(&self, slot: u64) -> u64 {
+        self.unlocked_at(slot).saturating_sub(self.claimed)
+    }
+
+    /// Record a claim of `amount`, refusing anything beyond the unlocked portion
+    pub fn claim(&mut self, amount: u64, slot: u64) -> Result<()> {
+        AppError::validate_amount(amount)?;
+        let available = self.claimable_at(slot);
+        if amount > available {
+            return Err(AppError::InsufficientVested {
+                available,
+                required: amount,
+            });
+        }
+
+        self.claimed += amount;
+        Ok(())
+    }
+
+    /// Check whether everything has been claimed
+    pub fn is_fully_claimed(&self) -> bool {
+        self.claimed == self.total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_validation() {
+        assert!(VestingSchedule::new("Grantee".into(), 1000, 10, 5, 100).is_ok());
+        assert!(VestingSchedule::new("Grantee".into(), 1000, 10, 101, 100).is_err());
+        assert!(VestingSchedule::new("Grantee".into(), 1000, 10, 0, 0).is_err());
+        assert!(VestingSchedule::new("Grantee".into(), 0, 10, 0, 100).is_err());
+        assert!(VestingSchedule::new("Grantee".into(), 1000, u64::MAX, 0, 100).is_err());
+    }
+
+    #[test]
+    fn test_unlock_boundaries() {
+        let schedule = VestingSchedule::new("Grantee".into(), 1000, 100, 25, 100).unwrap();
+        assert_eq!(schedule.unlocked_at(0), 0);
+        assert_eq!(schedule.unlocked_at(124), 0); // one slot before the cliff
+        assert_eq!(schedule.unlocked_at(125), 250); // cliff releases the accrued share
+        assert_eq!(schedule.unlocked_at(150), 500);
+        assert_eq!(schedule.unlocked_at(199), 990);
+        assert_eq!(schedule.unlocked_at(200), 1000);
+        assert_eq!(schedule.unlocked_at(u64::MAX), 1000);
+    }
+
+    #[test]
+    fn test_unlock_properties() {
+        // Exhaustive over small schedules: unlocking is monotonic, bounded by
+        // the total, zero before the cliff and complete at the end
+        for total in [1u64, 7, 100, 999, u64::MAX] {
+            for duration in 1..20u64 {
+                for cliff in 0..=duration {
+                    let schedule = VestingSchedule::new("Grantee".into(), total, 5, cliff, duration).unwrap();
+                    let mut previous = 0;
+                    for slot in 0..(5 + duration + 3) {
+                        let unlocked = schedule.unlocked_at(slot);
+                        assert!(unlocked >= previous);
+                        assert!(unlocked <= total);
+                        if slot < 5 + cliff {
+                            assert_eq!(unlocked, 0);
+                        }
+                        if slot >= 5 + duration {
+                            assert_eq!(unlocked, total);
+                        }
+                        previous = unlocked;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_claims_never_exceed_unlocked() {
+        let mut schedule = VestingSchedule::new("Grantee".into(), 1000, 0, 10, 100).unwrap();
+        assert!(matches!(schedule.claim(1, 9), Err(AppError::InsufficientVested { available: 0, .. })));
+
+        schedule.claim(100, 10).unwrap();
+        assert!(schedule.claim(1, 10).is_err());
+        assert_eq!(schedule.claimable_at(50), 400);
+        schedule.claim(400, 50).unwrap();
+        schedule.claim(500, 100).unwrap();
+        assert!(schedule.is_fully_claimed());
+        assert!(schedule.claim(1, 1000).is_err());
+    }
+}
@@ -0,0 +1,462 @@
+// ============================================
+// BYTECODE VM
+// ============================================
+// A small register machine for programs uploaded at runtime. Each instruction
+// is 8 bytes: opcode, dst register, src register, one unused byte and a
+// little-endian i32 immediate. Programs are verified when deployed and run
+// with a fixed compute budget and a fixed amount of memory; they reach the
+// outside world only through syscalls on the accounts they were passed.
+
+use crate::error::{AppError, Result};
+use crate::program::{account_at, AccountMeta, InvokeContext};
+use crate::pubkey::SYSTEM_PROGRAM_ID;
+use crate::system_program::SystemInstruction;
+
+/// Number of general purpose registers, r0..r7
+pub const NUM_REGISTERS: usize = 8;
+/// Size of one encoded instruction
+pub const INSTRUCTION_LEN: usize = 8;
+/// Largest program that can be deployed, in instructions
+pub const MAX_INSTRUCTIONS: usize = 4096;
+/// Bytes of memory a program gets; its input data is copied to address 0
+pub const MEMORY_SIZE: usize = 4096;
+/// Instructions a single invocation may execute
+pub const COMPUTE_BUDGET: u64 = 10_000;
+/// Largest account data a program may write
+pub const MAX_ACCOUNT_DATA: usize = 10 * 1024;
+
+/// Operations understood by the VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Exit = 0x00,    // Stop; r0 is the exit code, 0 for success
+    MovImm = 0x01,  // dst = imm
+    MovReg = 0x02,  // dst = src
+    AddImm = 0x03,  // dst += imm
+    AddReg = 0x04,  // dst += src
+    SubImm = 0x05,  // dst -= imm
+    SubReg = 0x06,  // dst -= src
+    MulImm = 0x07,  // dst *= imm
+    MulReg = 0x08,  // dst *= src
+    DivImm = 0x09,  // dst /= imm
+    DivReg = 0x0a,  // dst /= src
+    Load = 0x10,    // dst = u64 at memory[src + imm]
+    Store = 0x11,   // u64 at memory[dst + imm] = src
+    Ja = 0x20,      // jump by imm instructions
+    Jeq = 0x21,     // jump by imm if dst == src
+    Jne = 0x22,     // jump by imm if dst != src
+    Jlt = 0x23,     // jump by imm if dst < src
+    Jge = 0x24,     // jump by imm if dst >= src
+    Call = 0x30,    // syscall number imm, arguments in r1..r4, result in r0
+}
+
+impl Opcode {
+    fn from_u8(byte: u8) -> Option<Self> {
+        let opcode = match byte {
+            0x00 => Opcode::Exit,
+            0x01 => Opcode::MovImm,
+            0x02 => Opcode::MovReg,
+            0x03 => Opcode::AddImm,
+            0x04 => Opcode::AddReg,
+            0x05 => Opcode::SubImm,
+            0x06 => Opcode::SubReg,
+            0x07 => Opcode::MulImm,
+            0x08 => Opcode::MulReg,
+            0x09 => Opcode::DivImm,
+            0x0a => Opcode::DivReg,
+            0x10 => Opcode::Load,
+            0x11 => Opcode::Store,
+            0x20 => Opcode::Ja,
+            0x21 => Opcode::Jeq,
+            0x22 => Opcode::Jne,
+            0x23 => Opcode::Jlt,
+            0x24 => Opcode::Jge,
+            0x30 => Opcode::Call,
+            _ => return None,
+        };
+        Some(opcode)
+    }
+
+    fn is_jump(self) -> bool {
+        matches!(self, Opcode::Ja | Opcode::Jeq | Opcode::Jne | Opcode::Jlt | Opcode::Jge)
+    }
+}
+
+/// Syscalls a program can make with `Call`
+pub mod syscall {
+    /// Append r1 to the program log
+    pub const LOG: i32 = 1;
+    /// r0 = balance of account r1
+    pub const BALANCE: i32 = 2;
+    /// Move r3 lamports from account r1 to account r2 through the system program
+    pub const TRANSFER: i32 = 3;
+    /// Copy r4 bytes of account r1's data from offset r2 to memory at r3
+    pub const READ_DATA: i32 = 4;
+    /// Copy r4 bytes from memory at r3 into account r1's data at offset r2
+    pub const WRITE_DATA: i32 = 5;
+}
+
+/// One decoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Insn {
+    pub opcode: Opcode,
+    pub dst: u8,
+    pub src: u8,
+    pub imm: i32,
+}
+
+impl Insn {
+    pub fn new(opcode: Opcode, dst: u8, src: u8, imm: i32) -> Self {
+        Self { opcode, dst, src, imm }
+    }
+
+    /// Encode to the 8 byte wire format
+    pub fn encode(&self) -> [u8; INSTRUCTION_LEN] {
+        let imm = self.imm.to_le_bytes();
+        [self.opcode as u8, self.dst, self.src, 0, imm[0], imm[1], imm[2], imm[3]]
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let opcode = Opcode::from_u8(bytes[0])
+            .ok_or_else(|| AppError::InvalidBytecode(format!("unknown opcode {:#04x}", bytes[0])))?;
+        let imm = i32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        Ok(Self::new(opcode, bytes[1], bytes[2], imm))
+    }
+}
+
+/// Encode a list of instructions into deployable bytecode
+pub fn assemble(program: &[Insn]) -> Vec<u8> {
+    program.iter().flat_map(Insn::encode).collect()
+}
+
+/// Decode and check bytecode: known opcodes, valid registers and jumps that
+/// land inside the program
+pub fn verify(bytecode: &[u8]) -> Result<Vec<Insn>> {
+    if bytecode.is_empty() || !bytecode.len().is_multiple_of(INSTRUCTION_LEN) {
+        return Err(AppError::InvalidBytecode(format!(
+            "length {} is not a positive multiple of {}",
+            bytecode.len(),
+            INSTRUCTION_LEN
+        )));
+    }
+    let count = bytecode.len() / INSTRUCTION_LEN;
+    if count > MAX_INSTRUCTIONS {
+        return Err(AppError::InvalidBytecode(format!(
+            "{} instructions is above the {} limit",
+            count, MAX_INSTRUCTIONS
+        )));
+    }
+
+    let program = bytecode
+        .chunks_exact(INSTRUCTION_LEN)
+        .map(Insn::decode)
+        .collect::<Result<Vec<_>>>()?;
+    for (pc, insn) in program.iter().enumerate() {
+        if insn.dst as usize >= NUM_REGISTERS || insn.src as usize >= NUM_REGISTERS {
+            return Err(AppError::InvalidBytecode(format!("bad register at {}", pc)));
+        }
+        if insn.opcode.is_jump() && jump_target(pc, insn.imm).is_none_or(|target| target >= count) {
+            return Err(AppError::InvalidBytecode(format!("jump out of bounds at {}", pc)));
+        }
+    }
+    Ok(program)
+}
+
+fn jump_target(pc: usize, offset: i32) -> Option<usize> {
+    (pc as i64 + 1 + offset as i64).try_into().ok()
+}
+
+/// Run a deployed program. r1 holds the input length and r2 the number of
+/// accounts; on success the logs are returned.
+pub fn execute(ctx: &mut InvokeContext, bytecode: &[u8], accounts: &[AccountMeta], data: &[u8]) -> Result<String> {
+    let program = verify(bytecode)?;
+    if data.len() > MEMORY_SIZE {
+        return Err(AppError::InvalidInstructionData(format!(
+            "{} bytes of input do not fit in {} bytes of memory",
+            data.len(),
+            MEMORY_SIZE
+        )));
+    }
+
+    let mut vm = Vm {
+        registers: [0; NUM_REGISTERS],
+        memory: vec![0; MEMORY_SIZE],
+        logs: Vec::new(),
+    };
+    vm.memory[..data.len()].copy_from_slice(data);
+    vm.registers[1] = data.len() as u64;
+    vm.registers[2] = accounts.len() as u64;
+
+    let used = vm.run(ctx, &program, accounts)?;
+    let code = vm.registers[0];
+    if code != 0 {
+        return Err(AppError::ProgramFailed(code));
+    }
+
+    let mut output = format!("Program succeeded using {} compute units", used);
+    for line in &vm.logs {
+        output.push_str("\n  log: ");
+        output.push_str(line);
+    }
+    Ok(output)
+}
+
+struct Vm {
+    registers: [u64; NUM_REGISTERS],
+    memory: Vec<u8>,
+    logs: Vec<String>,
+}
+
+impl Vm {
+    /// Execute until `Exit`; returns the compute units used
+    fn run(&mut self, ctx: &mut InvokeContext, program: &[Insn], accounts: &[AccountMeta]) -> Result<u64> {
+        let mut pc = 0;
+        let mut used = 0;
+        loop {
+            let insn = program
+                .get(pc)
+                .ok_or_else(|| AppError::VmFault(format!("ran past the end at {}", pc)))?;
+            used += 1;
+            if used > COMPUTE_BUDGET {
+                return Err(AppError::ComputeBudgetExceeded(COMPUTE_BUDGET));
+            }
+
+            let dst = insn.dst as usize;
+            let src = self.registers[insn.src as usize];
+            let imm = insn.imm as i64 as u64; // sign-extended
+            let mut next = pc + 1;
+            match insn.opcode {
+                Opcode::Exit => return Ok(used),
+                Opcode::MovImm => self.registers[dst] = imm,
+                Opcode::MovReg => self.registers[dst] = src,
+                Opcode::AddImm => self.registers[dst] = self.registers[dst].wrapping_add(imm),
+                Opcode::AddReg => self.registers[dst] = self.registers[dst].wrapping_add(src),
+                Opcode::SubImm => self.registers[dst] = self.registers[dst].wrapping_sub(imm),
+                Opcode::SubReg => self.registers[dst] = self.registers[dst].wrapping_sub(src),
+                Opcode::MulImm => self.registers[dst] = self.registers[dst].wrapping_mul(imm),
+                Opcode::MulReg => self.registers[dst] = self.registers[dst].wrapping_mul(src),
+                Opcode::DivImm | Opcode::DivReg => {
+                    let divisor = if insn.opcode == Opcode::DivImm { imm } else { src };
+                    self.registers[dst] = self.registers[dst]
+                        .checked_div(divisor)
+                        .ok_or_else(|| AppError::VmFault(format!("division by zero at {}", pc)))?;
+                }
+                Opcode::Load => {
+                    let addr = self.address(src, insn.imm, 8, pc)?;
+                    let bytes: [u8; 8] = self.memory[addr..addr + 8].try_into().unwrap_or_default();
+                    self.registers[dst] = u64::from_le_bytes(bytes);
+                }
+                Opcode::Store => {
+                    let addr = self.address(self.registers[dst], insn.imm, 8, pc)?;
+                    self.memory[addr..addr + 8].copy_from_slice(&src.to_le_bytes());
+                }
+                Opcode::Ja | Opcode::Jeq | Opcode::Jne | Opcode::Jlt | Opcode::Jge => {
+                    let value = self.registers[dst];
+                    let taken = match insn.opcode {
+                        Opcode::Jeq => value == src,
+                        Opcode::Jne => value != src,
+                        Opcode::Jlt => value < src,
+                        Opcode::Jge => value >= src,
+                        _ => true,
+                    };
+                    if taken {
+                        // Targets were checked by `verify`
+                        next = jump_target(pc, insn.imm).unwrap_or(program.len());
+                    }
+                }
+                Opcode::Call => self.registers[0] = self.syscall(ctx, insn.imm, accounts, pc)?,
+            }
+            pc = next;
+        }
+    }
+
+    /// Bounds-checked memory address `base + offset` for an access of `len` bytes
+    fn address(&self, base: u64, offset: i32, len: usize, pc: usize) -> Result<usize> {
+        let addr = base.wrapping_add(offset as i64 as u64);
+        match usize::try_from(addr) {
+            Ok(addr) if addr.checked_add(len).is_some_and(|end| end <= MEMORY_SIZE) => Ok(addr),
+            _ => Err(AppError::VmFault(format!("memory access at {:#x} out of bounds at {}", addr, pc))),
+        }
+    }
+
+    fn syscall(&mut self, ctx: &mut InvokeContext, number: i32, accounts: &[AccountMeta], pc: usize) -> Result<u64> {
+        let [_, r1, r2, r3, r4, ..] = self.registers;
+        let account = |index: u64| account_at(accounts, index as usize);
+        match number {
+            syscall::LOG => {
+                self.logs.push(r1.to_string());
+                Ok(0)
+            }
+            syscall::BALANCE => Ok(ctx.account(&account(r1)?.key)?.balance),
+            syscall::TRANSFER => {
+                let metas = [
+                    AccountMeta::new(account(r1)?.key.clone(), true),
+                    AccountMeta::new(account(r2)?.key.clone(), false),
+                ];
+                let transfer = SystemInstruction::Transfer { amount: r3 }.pack();
+                ctx.invoke(&SYSTEM_PROGRAM_ID, &metas, &transfer)?;
+                Ok(0)
+            }
+            syscall::READ_DATA => {
+                let len = r4 as usize;
+                let addr = self.address(r3, 0, len, pc)?;
+                let data = &ctx.account(&account(r1)?.key)?.data;
+                let bytes = usize::try_from(r2)
+                    .ok()
+                    .and_then(|start| data.get(start..start.checked_add(len)?))
+                    .ok_or_else(|| AppError::VmFault(format!("account data read out of bounds at {}", pc)))?;
+                self.memory[addr..addr + len].copy_from_slice(bytes);
+                Ok(len as u64)
+            }
+            syscall::WRITE_DATA => {
+                let meta = account(r1)?;
+                meta.check_writable()?;
+                let len = r4 as usize;
+                let addr = self.address(r3, 0, len, pc)?;
+                let end = usize::try_from(r2)
+                    .ok()
+                    .and_then(|start| start.checked_add(len))
+                    .filter(|end| *end <= MAX_ACCOUNT_DATA)
+                    .ok_or_else(|| AppError::VmFault(format!("account data write out of bounds at {}", pc)))?;
+
+                let program_id = ctx.program_id();
+                let target = ctx.account_mut(&meta.key)?;
+                if Some(target.owner) != program_id {
+                    return Err(AppError::Unauthorized(meta.key.clone()));
+                }
+                if target.data.len() < end {
+                    target.data.resize(end, 0);
+                }
+                target.data[end - len..end].copy_from_slice(&self.memory[addr..addr + len]);
+                Ok(len as u64)
+            }
+            _ => Err(AppError::VmFault(format!("unknown syscall {} at {}", number, pc))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::ProgramRegistry;
+    use crate::pubkey::Pubkey;
+    use crate::state::AppState;
+    use Opcode::*;
+
+    fn run(program: &[Insn], accounts: &[AccountMeta], data: &[u8], state: &mut AppState) -> Result<String> {
+        let id = state.deploy_program("Dev", "prog", assemble(program)).unwrap();
+        ProgramRegistry::with_builtins().invoke(&id, accounts, data, state)
+    }
+
+    fn dev_state() -> AppState {
+        let mut state = AppState::new();
+        state.create_account("Dev".into(), 1).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_bytecode() {
+        assert!(verify(&[]).is_err());
+        assert!(verify(&[0; 7]).is_err());
+        assert!(verify(&[0xff, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(verify(&assemble(&[Insn::new(MovImm, 8, 0, 1)])).is_err());
+        assert!(verify(&assemble(&[Insn::new(Ja, 0, 0, 1)])).is_err());
+        assert!(verify(&assemble(&[Insn::new(Ja, 0, 0, -2)])).is_err());
+        assert!(verify(&assemble(&[Insn::new(Ja, 0, 0, 0), Insn::new(Exit, 0, 0, 0)])).is_ok());
+    }
+
+    #[test]
+    fn test_arithmetic_loop_and_logs() {
+        // Sum 1..=r5 where r5 is read from the input, then log it
+        let program = [
+            Insn::new(Load, 5, 0, 0),
+            Insn::new(MovImm, 6, 0, 0),
+            Insn::new(MovImm, 7, 0, 0),
+            Insn::new(AddImm, 7, 0, 1),
+            Insn::new(AddReg, 6, 7, 0),
+            Insn::new(Jlt, 7, 5, -3),
+            Insn::new(MovReg, 1, 6, 0),
+            Insn::new(Call, 0, 0, syscall::LOG),
+            Insn::new(MovImm, 0, 0, 0),
+            Insn::new(Exit, 0, 0, 0),
+        ];
+        let mut state = dev_state();
+        let output = run(&program, &[], &10u64.to_le_bytes(), &mut state).unwrap();
+        assert!(output.ends_with("log: 55"));
+    }
+
+    #[test]
+    fn test_faults_and_limits() {
+        let mut state = dev_state();
+        let fail = [Insn::new(MovImm, 0, 0, 7), Insn::new(Exit, 0, 0, 0)];
+        assert!(matches!(run(&fail, &[], &[], &mut state), Err(AppError::ProgramFailed(7))));
+
+        let mut state = dev_state();
+        let spin = [Insn::new(Ja, 0, 0, -1)];
+        assert!(matches!(run(&spin, &[], &[], &mut state), Err(AppError::ComputeBudgetExceeded(_))));
+
+        let mut state = dev_state();
+        let oob = [Insn::new(MovImm, 1, 0, MEMORY_SIZE as i32 - 4), Insn::new(Load, 0, 1, 0), Insn::new(Exit, 0, 0, 0)];
+        assert!(matches!(run(&oob, &[], &[], &mut state), Err(AppError::VmFault(_))));
+
+        let mut state = dev_state();
+        let div = [Insn::new(DivImm, 0, 0, 0), Insn::new(Exit, 0, 0, 0)];
+        assert!(matches!(run(&div, &[], &[], &mut state), Err(AppError::VmFault(_))));
+
+        let mut state = dev_state();
+        let fall_off = [Insn::new(MovImm, 0, 0, 0)];
+        assert!(matches!(run(&fall_off, &[], &[], &mut state), Err(AppError::VmFault(_))));
+    }
+
+    #[test]
+    fn test_syscalls_on_accounts() {
+        let mut state = dev_state();
+        state.create_account("Alice".into(), 100).unwrap();
+        state.create_account("Bob".into(), 1).unwrap();
+
+        // Pay half of account 0's balance to account 1, then record the amount
+        // in account 2's data
+        let program = [
+            Insn::new(MovImm, 1, 0, 0),
+            Insn::new(Call, 0, 0, syscall::BALANCE),
+            Insn::new(DivImm, 0, 0, 2),
+            Insn::new(MovReg, 3, 0, 0),
+            Insn::new(MovImm, 6, 0, 0),
+            Insn::new(Store, 6, 0, 0),
+            Insn::new(MovImm, 1, 0, 0),
+            Insn::new(MovImm, 2, 0, 1),
+            Insn::new(Call, 0, 0, syscall::TRANSFER),
+            Insn::new(MovImm, 1, 0, 2),
+            Insn::new(MovImm, 2, 0, 0),
+            Insn::new(MovImm, 3, 0, 0),
+            Insn::new(MovImm, 4, 0, 8),
+            Insn::new(Call, 0, 0, syscall::WRITE_DATA),
+            Insn::new(MovImm, 0, 0, 0),
+            Insn::new(Exit, 0, 0, 0),
+        ];
+        let id = state.deploy_program("Dev", "payer", assemble(&program)).unwrap();
+        let (record, _) = Pubkey::find_program_address(&[b"record"], &id).unwrap();
+        state.create_program_account(record, id, 1).unwrap();
+
+        let registry = ProgramRegistry::with_builtins();
+        let mut accounts = vec![
+            AccountMeta::new("Alice".into(), true),
+            AccountMeta::new("Bob".into(), false),
+            AccountMeta::new(record.to_string(), false),
+        ];
+        registry.invoke(&id, &accounts, &[], &mut state).unwrap();
+        assert_eq!(state.get_account("Bob").unwrap().balance, 51);
+        assert_eq!(state.get_account(&record.to_string()).unwrap().data, 50u64.to_le_bytes());
+
+        // The program cannot sign for Alice if the caller did not
+        accounts[0].is_signer = false;
+        let result = registry.invoke(&id, &accounts, &[], &mut state);
+        assert!(matches!(result, Err(AppError::PrivilegeEscalation(_))));
+
+        // Only the owning program may write account data
+        accounts[0].is_signer = true;
+        accounts[2] = AccountMeta::new("Bob".into(), false);
+        let result = registry.invoke(&id, &accounts, &[], &mut state);
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        assert_eq!(state.get_account("Bob").unwrap().balance, 51);
+    }
+}
@@ -0,0 +1,266 @@
+// ============================================
+// WEBSOCKET
+// ============================================
+// The server side of RFC 6455, as much as the subscription server needs: the
+// HTTP upgrade handshake and reading and writing single frames. Client frames
+// are masked, server frames are not; fragmented messages are reassembled.
+
+use std::io::{self, BufRead, Read, Write};
+
+use crate::codec;
+use crate::rpc::{self, RequestHead};
+
+/// Appended to the client's key before hashing, per RFC 6455
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest message accepted from a client
+pub const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Frame opcodes
+pub mod opcode {
+    pub const CONTINUATION: u8 = 0x0;
+    pub const TEXT: u8 = 0x1;
+    pub const BINARY: u8 = 0x2;
+    pub const CLOSE: u8 = 0x8;
+    pub const PING: u8 = 0x9;
+    pub const PONG: u8 = 0xa;
+}
+
+/// A complete message or control frame from the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+/// SHA-1, which the handshake requires; not used for anything else
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, word) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// `Sec-WebSocket-Accept` value answering the client's `Sec-WebSocket-Key`
+pub fn accept_key(key: &str) -> String {
+    codec::base64_encode(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()))
+}
+
+/// Read the client's upgrade request and complete the handshake. Returns
+/// `false` (after answering 400) if the request was not a WebSocket upgrade.
+pub fn handshake(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<bool> {
+    let head = rpc::read_head(reader)?.unwrap_or_else(RequestHead::default);
+    let upgrade = head.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    let key = head.header("sec-websocket-key");
+    let (true, Some(key)) = (head.method == "GET" && upgrade, key) else {
+        write!(writer, "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")?;
+        writer.flush()?;
+        return Ok(false);
+    };
+
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )?;
+    writer.flush()?;
+    Ok(true)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Read one frame: (fin, opcode, unmasked payload)
+fn read_frame(reader: &mut impl Read) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0f;
+    let masked = header[1] & 0x80 != 0;
+
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_BYTES as u64 {
+        return Err(invalid("frame too large"));
+    }
+
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    if masked {
+        for (index, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[index % 4];
+        }
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Read the next message, reassembling fragments and answering nothing itself
+pub fn read_message(reader: &mut impl Read) -> io::Result<Message> {
+    let (mut fin, opcode, mut payload) = read_frame(reader)?;
+    match opcode {
+        opcode::CLOSE => return Ok(Message::Close),
+        opcode::PING => return Ok(Message::Ping(payload)),
+        opcode::PONG => return Ok(Message::Pong(payload)),
+        opcode::TEXT | opcode::BINARY => {}
+        _ => return Err(invalid("unexpected opcode")),
+    }
+
+    while !fin {
+        let (next_fin, next_opcode, fragment) = read_frame(reader)?;
+        if next_opcode != opcode::CONTINUATION {
+            return Err(invalid("interleaved frames are not supported"));
+        }
+        if payload.len() + fragment.len() > MAX_MESSAGE_BYTES {
+            return Err(invalid("message too large"));
+        }
+        payload.extend_from_slice(&fragment);
+        fin = next_fin;
+    }
+
+    if opcode == opcode::BINARY {
+        return Ok(Message::Binary(payload));
+    }
+    String::from_utf8(payload)
+        .map(Message::Text)
+        .map_err(|_| invalid("text message is not UTF-8"))
+}
+
+/// Write one unmasked, unfragmented frame
+pub fn write_frame(writer: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::to_hex;
+
+    /// A masked frame, as a client sends it
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(index, byte)| byte ^ mask[index % 4]));
+        frame
+    }
+
+    #[test]
+    fn test_sha1_and_accept_key() {
+        assert_eq!(to_hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(to_hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // Example from RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_handshake() {
+        let request = "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                       Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+        let mut response = Vec::new();
+        assert!(handshake(&mut request.as_bytes(), &mut response).unwrap());
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let mut response = Vec::new();
+        assert!(!handshake(&mut "GET / HTTP/1.1\r\n\r\n".as_bytes(), &mut response).unwrap());
+        assert!(response.starts_with(b"HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut input = client_frame(false, opcode::TEXT, b"hel");
+        input.extend(client_frame(true, opcode::CONTINUATION, b"lo"));
+        input.extend(client_frame(true, opcode::PING, b"p"));
+        input.extend(client_frame(true, opcode::CLOSE, b""));
+        let mut reader = input.as_slice();
+        assert_eq!(read_message(&mut reader).unwrap(), Message::Text("hello".into()));
+        assert_eq!(read_message(&mut reader).unwrap(), Message::Ping(b"p".to_vec()));
+        assert_eq!(read_message(&mut reader).unwrap(), Message::Close);
+        assert!(read_message(&mut reader).is_err());
+
+        // Server frames use the extended length forms and read back
+        for len in [5, 300, 70_000] {
+            let mut out = Vec::new();
+            write_frame(&mut out, opcode::BINARY, &vec![7; len]).unwrap();
+            if len <= MAX_MESSAGE_BYTES {
+                assert_eq!(read_message(&mut out.as_slice()).unwrap(), Message::Binary(vec![7; len]));
+            } else {
+                assert!(read_message(&mut out.as_slice()).is_err());
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// Uses the crate only through its public API, as a dependent crate would
+
+use solana101::state::{Escrow, EscrowStatus};
+use solana101::{
+    AppError, AppState, Instruction, Keypair, Ledger, Transaction, process_instruction, process_transaction,
+};
+
+#[test]
+fn instructions_update_state() {
+    let mut state = AppState::new();
+    process_instruction(&Instruction::create_account("Alice".into(), 1000), &mut state).unwrap();
+    process_instruction(&Instruction::create_account("Bob".into(), 1), &mut state).unwrap();
+    process_instruction(&Instruction::transfer("Alice".into(), "Bob".into(), 250), &mut state).unwrap();
+    assert_eq!(state.get_account("Bob").unwrap().balance, 251);
+
+    let error = process_instruction(&Instruction::transfer("Bob".into(), "Alice".into(), 999), &mut state).unwrap_err();
+    assert!(matches!(error, AppError::InsufficientBalance { .. }));
+    assert_eq!(error.code(), "BAL_001");
+
+    // A failing transaction leaves nothing behind
+    let batch = [
+        Instruction::transfer("Alice".into(), "Bob".into(), 10),
+        Instruction::delete_account("Nobody".into()),
+    ];
+    assert!(process_transaction(&batch, &mut state).is_err());
+    assert_eq!(state.get_account("Alice").unwrap().balance, 750);
+}
+
+#[test]
+fn program_records_are_readable() {
+    let mut state = AppState::new();
+    for (name, balance) in [("Alice", 500), ("Bob", 1), ("Judge", 1)] {
+        process_instruction(&Instruction::create_account(name.into(), balance), &mut state).unwrap();
+    }
+    let create = Instruction::escrow_create("Deal".into(), "Alice".into(), "Bob".into(), "Judge".into(), 100, 50);
+    process_instruction(&create, &mut state).unwrap();
+
+    let escrow: &Escrow = state.get_escrow("Deal").unwrap();
+    assert_eq!(escrow.amount, 100);
+    assert_eq!(escrow.status, EscrowStatus::Active);
+}
+
+#[test]
+fn ledger_accepts_signed_transactions() {
+    let payer = Keypair::from_seed([42; 32]);
+    let mut state = AppState::new();
+    state.create_account("Bob".into(), 1).unwrap();
+    let mut ledger = Ledger::new(state);
+
+    let open = Transaction::new(
+        vec![Instruction::create_account(payer.account_name(), 100)],
+        ledger.recent_blockhash(),
+    );
+    assert!(matches!(ledger.submit(open.clone()), Err(AppError::MissingSignature(_))));
+    let hash = ledger.submit(open.sign(&[&payer])).unwrap();
+    ledger.produce_block();
+
+    let (block, index) = ledger.find_transaction(&hash).unwrap();
+    assert!(block.results[index].is_ok());
+    assert_eq!(ledger.state().get_account(&payer.account_name()).unwrap().balance, 100);
+}